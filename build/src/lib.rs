@@ -0,0 +1,537 @@
+//! # Packaging for Neovim plugins written in Rust
+//! Cargo builds a plugin as `lib<name>.so`, `lib<name>.dylib` or `<name>.dll` depending on the platform,
+//! but Neovim's Lua loader looks for `lua/<name>.so` (or `lua/<name>.dll` on Windows) on the runtimepath.
+//! [`Plugin`] finds the artifact cargo built for a profile and installs it where Neovim can load it, lays out a plugin's
+//! `plugin/`, `doc/` and `lua/` directories, and packs them into a tarball for distribution.
+//!
+//! By default the library is installed as `lua/<name>/<name>-<os>-<arch>.so` next to a generated `lua/<name>/init.lua`.
+//! The loader picks the library built for the running platform, checks that it was built from the same version
+//! as the loader (libraries using `#[nvim_utils::module]` export their version), and reports missing or stale
+//! libraries with `vim.notify`, including the command to rebuild them. Commit the loader along with the plugin's Lua
+//! files, so updating the plugin without rebuilding it is caught. [`Plugin::with_loader`] turns it off.
+//!
+//! The `cargo nvim` subcommand wraps this library:
+//! ```sh
+//! cargo install nvim-utils-build
+//! cargo nvim build --release   # builds and installs to lua/<name>/<name>-<os>-<arch>.so with lua/<name>/init.lua
+//! cargo nvim package --release # builds and writes target/nvim/<name>-<version>-<os>-<arch>.tar.gz
+//! ```
+//!
+//! ## Example
+//! ```no_run
+//! use nvim_utils_build::Plugin;
+//!
+//! fn main() -> Result<(), nvim_utils_build::Error> {
+//!     let plugin = Plugin::new("Cargo.toml")?.with_profile("release");
+//!     plugin.build(&[])?;
+//!     plugin.install()?;
+//!     println!("packaged {}", plugin.tarball()?.display());
+//!     Ok(())
+//! }
+//! ```
+
+use flate2::{write::GzEncoder, Compression};
+use serde::Deserialize;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The directories copied into a packaged plugin when they exist, see [`Plugin::with_runtime_dir`]
+pub const DEFAULT_RUNTIME_DIRS: [&str; 3] = ["plugin", "doc", "lua"];
+
+const LOADER: &str = include_str!("loader.lua");
+
+/// The first line of the generated loader, which marks a `lua/<module>/init.lua` that may be overwritten
+const LOADER_HEADER: &str = "-- Generated by nvim-utils-build";
+
+/// An error returned while building or packaging a plugin
+#[derive(Debug)]
+pub enum Error {
+    /// A cargo command failed
+    Cargo {
+        command: String,
+        stderr: String,
+    },
+    /// The output of `cargo metadata` could not be parsed
+    Metadata(String),
+    /// No package in the workspace matched the manifest or package name
+    PackageNotFound(String),
+    /// The package has no `cdylib` library target
+    NoCdylib(String),
+    /// The built library was not found, usually because the plugin hasn't been built with the profile
+    ArtifactNotFound(PathBuf),
+    /// A `lua/<module>/init.lua` that wasn't generated is in the way of the loader
+    LoaderConflict(PathBuf),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Cargo { command, stderr } => write!(f, "`{}` failed:\n{}", command, stderr),
+            Error::Metadata(err) => write!(f, "could not read cargo metadata: {}", err),
+            Error::PackageNotFound(name) => write!(f, "package {} not found", name),
+            Error::NoCdylib(name) => write!(
+                f,
+                "package {} has no cdylib target, add `crate-type = [\"cdylib\"]` to its [lib] section",
+                name
+            ),
+            Error::ArtifactNotFound(path) => write!(
+                f,
+                "{} not found, build the plugin with the same profile first",
+                path.display()
+            ),
+            Error::LoaderConflict(path) => write!(
+                f,
+                "{} exists and wasn't generated by nvim-utils-build, move it or install without the loader",
+                path.display()
+            ),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    target_directory: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct MetadataPackage {
+    name: String,
+    version: String,
+    manifest_path: PathBuf,
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Deserialize)]
+struct MetadataTarget {
+    name: String,
+    crate_types: Vec<String>,
+}
+
+/// A plugin crate with a `cdylib` target, and where to build and install it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plugin {
+    name: String,
+    version: String,
+    lib_name: String,
+    manifest_path: PathBuf,
+    target_dir: PathBuf,
+    profile: String,
+    target: Option<String>,
+    module: String,
+    out_dir: PathBuf,
+    runtime_dirs: Vec<String>,
+    loader: bool,
+    /// Whether the package was picked by name, so rebuilding it needs `-p`
+    by_package: bool,
+}
+
+impl Plugin {
+    /// Reads the package at `manifest_path` with `cargo metadata`, using the `dev` profile
+    pub fn new(manifest_path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::find(manifest_path, None)
+    }
+
+    /// Reads a package of the workspace at `manifest_path` by name, like `cargo build -p`
+    pub fn from_package(manifest_path: impl AsRef<Path>, package: &str) -> Result<Self, Error> {
+        Self::find(manifest_path, Some(package))
+    }
+
+    fn find(manifest_path: impl AsRef<Path>, package: Option<&str>) -> Result<Self, Error> {
+        let by_package = package.is_some();
+        let manifest_path = fs::canonicalize(manifest_path.as_ref())?;
+        let mut command = Command::new(cargo());
+        command
+            .args(["metadata", "--format-version", "1", "--no-deps"])
+            .arg("--manifest-path")
+            .arg(&manifest_path);
+        let metadata: Metadata = serde_json::from_slice(&run(&mut command)?)
+            .map_err(|err| Error::Metadata(err.to_string()))?;
+
+        let package = metadata
+            .packages
+            .into_iter()
+            .find(|pkg| match package {
+                Some(name) => pkg.name == name,
+                None => pkg.manifest_path == manifest_path,
+            })
+            .ok_or_else(|| {
+                Error::PackageNotFound(
+                    package
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| manifest_path.display().to_string()),
+                )
+            })?;
+        let lib = package
+            .targets
+            .iter()
+            .find(|target| target.crate_types.iter().any(|ty| ty == "cdylib"))
+            .ok_or_else(|| Error::NoCdylib(package.name.clone()))?;
+
+        let manifest_dir = package
+            .manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(Self {
+            version: package.version,
+            lib_name: lib.name.replace('-', "_"),
+            module: lib.name.replace('-', "_"),
+            manifest_path: package.manifest_path,
+            target_dir: metadata.target_directory,
+            profile: "dev".to_owned(),
+            target: None,
+            out_dir: manifest_dir,
+            runtime_dirs: DEFAULT_RUNTIME_DIRS.map(str::to_owned).to_vec(),
+            loader: true,
+            by_package,
+            name: package.name,
+        })
+    }
+
+    /// Sets the cargo profile to build with and find the artifact for, like `dev` or `release`
+    pub fn with_profile(mut self, profile: &str) -> Self {
+        self.profile = profile.to_owned();
+        self
+    }
+
+    /// Sets the target triple to build for, like `cargo build --target`
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_owned());
+        self
+    }
+
+    /// Sets the Lua module name the library is installed as, which defaults to the library name.
+    /// It must match the name of the `luaopen_` function the plugin exports.
+    pub fn with_module(mut self, module: &str) -> Self {
+        self.module = module.to_owned();
+        self
+    }
+
+    /// Sets the directory the library is installed into by [`Plugin::install`], which defaults to the package directory
+    pub fn with_out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = dir.into();
+        self
+    }
+
+    /// Sets whether to install the library behind a generated `lua/<module>/init.lua` loader, which is the default.
+    /// Without it, the library is installed as `lua/<module>.so` and loaded by Neovim directly.
+    pub fn with_loader(mut self, loader: bool) -> Self {
+        self.loader = loader;
+        self
+    }
+
+    /// Adds a directory of the package to copy into packaged plugins, like `ftplugin` or `queries`
+    pub fn with_runtime_dir(mut self, dir: &str) -> Self {
+        self.runtime_dirs.push(dir.to_owned());
+        self
+    }
+
+    /// The package name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The package version
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The Lua module name the library is installed as
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// The `luaopen_` function Lua calls to load the module, without the prefix
+    pub fn symbol(&self) -> String {
+        self.module.replace('.', "_")
+    }
+
+    /// The directory containing the package's `Cargo.toml`
+    pub fn manifest_dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// The directory cargo writes artifacts to for the profile and target
+    pub fn profile_dir(&self) -> PathBuf {
+        let dir = match self.profile.as_str() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            profile => profile,
+        };
+        match &self.target {
+            Some(target) => self.target_dir.join(target).join(dir),
+            None => self.target_dir.join(dir),
+        }
+    }
+
+    /// The library cargo builds, like `target/release/lib<name>.so`
+    pub fn artifact(&self) -> PathBuf {
+        let file = match self.target_os() {
+            "windows" => format!("{}.dll", self.lib_name),
+            "macos" => format!("lib{}.dylib", self.lib_name),
+            _ => format!("lib{}.so", self.lib_name),
+        };
+        self.profile_dir().join(file)
+    }
+
+    /// The path of the library relative to the root of the plugin.
+    /// This is `lua/<module>/<module>-<os>-<arch>.so` with the loader, and `lua/<module>.so` without it.
+    /// Dots in the module name are directories, like they are for `require`.
+    pub fn module_path(&self) -> PathBuf {
+        let ext = if self.target_os() == "windows" {
+            "dll"
+        } else {
+            "so"
+        };
+        let module = self.module_dir();
+        if self.loader {
+            let name = module.file_name().unwrap_or_default().to_string_lossy();
+            module.join(format!(
+                "{}-{}-{}.{}",
+                name,
+                self.target_os(),
+                self.target_arch(),
+                ext
+            ))
+        } else {
+            module.with_extension(ext)
+        }
+    }
+
+    /// The path of the generated loader relative to the root of the plugin, `lua/<module>/init.lua`,
+    /// or `None` if the loader is disabled
+    pub fn loader_path(&self) -> Option<PathBuf> {
+        self.loader.then(|| self.module_dir().join("init.lua"))
+    }
+
+    /// The source of the generated loader
+    pub fn loader_source(&self) -> String {
+        let mut build = match self.profile.as_str() {
+            "dev" => "cargo nvim build".to_owned(),
+            "release" => "cargo nvim build --release".to_owned(),
+            profile => format!("cargo nvim build --profile {}", profile),
+        };
+        // The hint is shown to whoever installed the plugin, so it repeats the options that change what's built
+        if self.by_package {
+            build.push_str(&format!(" -p {}", self.name));
+        }
+        if self.module != self.lib_name {
+            build.push_str(&format!(" --module {}", self.module));
+        }
+        LOADER
+            .replace("{{module}}", &self.module)
+            .replace("{{package}}", &self.name)
+            .replace("{{version}}", &self.version)
+            .replace("{{build}}", &build)
+            .replace("{{symbol}}", &self.symbol())
+    }
+
+    /// `lua/` followed by the module name, with dots as directories
+    fn module_dir(&self) -> PathBuf {
+        self.module
+            .split('.')
+            .fold(PathBuf::from("lua"), |path, part| path.join(part))
+    }
+
+    /// The operating system the plugin is built for, in the format of [`std::env::consts::OS`]
+    pub fn target_os(&self) -> &str {
+        match &self.target {
+            Some(target) if target.contains("windows") => "windows",
+            Some(target) if target.contains("apple") => "macos",
+            Some(target) if target.contains("linux") => "linux",
+            Some(target) => target.split('-').nth(2).unwrap_or(target),
+            None => std::env::consts::OS,
+        }
+    }
+
+    /// The architecture the plugin is built for, in the format of [`std::env::consts::ARCH`]
+    pub fn target_arch(&self) -> &str {
+        match &self.target {
+            Some(target) => match target.split('-').next().unwrap_or(target) {
+                "i386" | "i586" | "i686" => "x86",
+                arch if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+                arch => arch,
+            },
+            None => std::env::consts::ARCH,
+        }
+    }
+
+    /// Runs `cargo build --lib` for the plugin, passing `args` through to cargo
+    pub fn build(&self, args: &[String]) -> Result<(), Error> {
+        let mut command = Command::new(cargo());
+        command
+            .args(["build", "--lib", "--profile", &self.profile])
+            .arg("--manifest-path")
+            .arg(&self.manifest_path)
+            .args(["--package", &self.name]);
+        if let Some(target) = &self.target {
+            command.args(["--target", target]);
+        }
+        command.args(args);
+        // Let cargo's progress through, only the error is captured
+        let status = command.status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::Cargo {
+                command: describe(&command),
+                stderr: format!("exited with {}", status),
+            })
+        }
+    }
+
+    /// Copies the built library to [`Plugin::module_path`] in the output directory and writes the loader,
+    /// returning the path of the installed library
+    ///
+    /// A loader from an earlier install is replaced, but a hand-written `lua/<module>/init.lua` isn't:
+    /// installing fails with [`Error::LoaderConflict`] before anything is copied.
+    pub fn install(&self) -> Result<PathBuf, Error> {
+        self.install_to(&self.out_dir)?;
+        Ok(self.out_dir.join(self.module_path()))
+    }
+
+    /// Lays out the plugin in `dest`: the package's runtime directories, and the built library in `lua/`
+    pub fn stage(&self, dest: &Path) -> Result<(), Error> {
+        fs::create_dir_all(dest.join("lua"))?;
+        for dir in &self.runtime_dirs {
+            let src = self.manifest_dir().join(dir);
+            if src.is_dir() {
+                copy_dir(&src, &dest.join(dir))?;
+            }
+        }
+        self.install_to(dest)
+    }
+
+    /// Stages the plugin and packs it into `target/nvim/<name>-<version>-<os>-<arch>.tar.gz`,
+    /// with every file under a `<name>/` directory. Returns the path of the tarball.
+    pub fn tarball(&self) -> Result<PathBuf, Error> {
+        let dir = self.target_dir.join("nvim");
+        let path = dir.join(format!(
+            "{}-{}-{}-{}.tar.gz",
+            self.name,
+            self.version,
+            self.target_os(),
+            self.target_arch()
+        ));
+        self.tarball_to(&path)?;
+        Ok(path)
+    }
+
+    /// Stages the plugin and packs it into a gzipped tarball at `path`, with every file under a `<name>/` directory
+    pub fn tarball_to(&self, path: &Path) -> Result<(), Error> {
+        let staging = self.target_dir.join("nvim").join(&self.name);
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        self.stage(&staging)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut archive = tar::Builder::new(GzEncoder::new(
+            fs::File::create(path)?,
+            Compression::default(),
+        ));
+        archive.append_dir_all(&self.name, &staging)?;
+        archive.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    fn install_to(&self, root: &Path) -> Result<(), Error> {
+        let loader = self.loader_path().map(|loader| root.join(loader));
+        if let Some(loader) = &loader {
+            check_loader(loader)?;
+        }
+        self.copy_artifact(&root.join(self.module_path()))?;
+        if let Some(loader) = loader {
+            fs::write(loader, self.loader_source())?;
+        }
+        Ok(())
+    }
+
+    fn copy_artifact(&self, dest: &Path) -> Result<(), Error> {
+        let artifact = self.artifact();
+        if !artifact.is_file() {
+            return Err(Error::ArtifactNotFound(artifact));
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Removed first, since overwriting a library Neovim has loaded can crash it on some platforms
+        if dest.exists() {
+            fs::remove_file(dest)?;
+        }
+        fs::copy(&artifact, dest)?;
+        Ok(())
+    }
+}
+
+/// Fails if `path` exists and doesn't start with the loader's header, so hand-written modules aren't overwritten
+fn check_loader(path: &Path) -> Result<(), Error> {
+    match fs::read(path) {
+        Ok(source) if !source.starts_with(LOADER_HEADER.as_bytes()) => {
+            Err(Error::LoaderConflict(path.to_owned()))
+        }
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The cargo binary running this process, or `cargo` from the `PATH`
+fn cargo() -> PathBuf {
+    std::env::var_os("CARGO")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("cargo"))
+}
+
+fn describe(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn run(command: &mut Command) -> Result<Vec<u8>, Error> {
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(Error::Cargo {
+            command: describe(command),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Copies a directory recursively, skipping libraries left over from installing into the source tree
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &dest.join(entry.file_name()))?;
+        } else if !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("so" | "dll" | "dylib" | "rlib")
+        ) {
+            fs::copy(&path, dest.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
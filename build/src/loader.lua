@@ -0,0 +1,59 @@
+-- Generated by nvim-utils-build, do not edit.
+-- Loads the native library of {{module}} built for this platform, checking it was built from the same version as
+-- this file, and explains how to build it if it can't be loaded.
+
+local module = "{{module}}"
+local package_name = "{{package}}"
+local expected_version = "{{version}}"
+local build_command = "{{build}}"
+local symbol = "{{symbol}}"
+
+local source = debug.getinfo(1, "S").source:sub(2)
+local dir = source:match("^(.*)[/\\]") or "."
+local root = source:match("^(.*)[/\\]lua[/\\]") or dir
+
+local function platform()
+  local system, arch
+  if jit then
+    system = ({ Linux = "linux", OSX = "macos", Windows = "windows", BSD = "freebsd" })[jit.os] or jit.os:lower()
+    arch = ({ x64 = "x86_64", x86 = "x86", arm64 = "aarch64", arm = "arm", ppc = "powerpc" })[jit.arch] or jit.arch
+  else
+    local uname = (vim.uv or vim.loop).os_uname()
+    system = ({ Linux = "linux", Darwin = "macos", Windows_NT = "windows" })[uname.sysname] or uname.sysname:lower()
+    arch = ({ amd64 = "x86_64", arm64 = "aarch64" })[uname.machine:lower()] or uname.machine:lower()
+  end
+  return system, arch
+end
+
+local system, arch = platform()
+local ext = system == "windows" and "dll" or "so"
+local library = ("%s/%s-%s-%s.%s"):format(dir, module:match("[^.]+$"), system, arch, ext)
+
+local function fail(reason)
+  local msg = ("%s: %s\nBuild it by running `%s` in %s"):format(package_name, reason, build_command, root)
+  vim.schedule(function()
+    vim.notify(msg, vim.log.levels.ERROR)
+  end)
+  error(msg, 0)
+end
+
+if not (vim.uv or vim.loop).fs_stat(library) then
+  fail(("the native library for %s-%s was not found at %s"):format(system, arch, library))
+end
+
+-- Libraries built without `#[nvim_utils::module]` don't export a version, so they aren't checked
+local version = package.loadlib(library, "nvim_utils_version_" .. symbol)
+if version then
+  local ok, found = pcall(version)
+  if not ok then
+    fail(("could not read the version of %s: %s"):format(library, found))
+  elseif found ~= expected_version then
+    fail(("%s was built from version %s, but version %s is installed"):format(library, found, expected_version))
+  end
+end
+
+local open, err = package.loadlib(library, "luaopen_" .. symbol)
+if not open then
+  fail(("could not load %s: %s"):format(library, err))
+end
+return open(module)
@@ -0,0 +1,152 @@
+//! `cargo nvim`, builds a Neovim plugin and installs or packages it, see [`nvim_utils_build::Plugin`]
+
+use nvim_utils_build::{Error, Plugin};
+use std::{env, path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "\
+Builds a Neovim plugin written in Rust and lays it out for the runtimepath
+
+Usage: cargo nvim <COMMAND> [OPTIONS] [-- <CARGO_ARGS>...]
+
+Commands:
+  build     Build the plugin and install it to lua/<module>/ with a loader
+  install   Install an already built plugin to lua/<module>/ with a loader
+  package   Build the plugin and pack it into target/nvim/<name>-<version>-<os>-<arch>.tar.gz
+
+Options:
+  -r, --release               Build with the release profile
+      --profile <PROFILE>     Build with the given profile
+      --target <TRIPLE>       Build for the given target triple
+  -p, --package <PACKAGE>     The workspace package to build
+      --manifest-path <PATH>  Path to Cargo.toml [default: Cargo.toml]
+      --module <NAME>         The Lua module name to install the library as
+      --out-dir <DIR>         Where to install lua/<module>/ [default: the package directory]
+      --no-loader             Install the library as lua/<module>.so without a loader
+  -o, --output <PATH>         Where to write the tarball
+  -h, --help                  Print this message
+
+Arguments after `--` are passed to `cargo build`.";
+
+#[derive(Debug, Default)]
+struct Args {
+    command: String,
+    profile: Option<String>,
+    target: Option<String>,
+    package: Option<String>,
+    manifest_path: Option<PathBuf>,
+    module: Option<String>,
+    out_dir: Option<PathBuf>,
+    no_loader: bool,
+    output: Option<PathBuf>,
+    cargo_args: Vec<String>,
+}
+
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = Args::default();
+    // Cargo passes the subcommand name as the first argument
+    let mut iter = env::args().skip(1).peekable();
+    if iter.peek().map(String::as_str) == Some("nvim") {
+        iter.next();
+    }
+
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-r" | "--release" => args.profile = Some("release".to_owned()),
+            "--profile" => args.profile = Some(value(&arg)?),
+            "--target" => args.target = Some(value(&arg)?),
+            "-p" | "--package" => args.package = Some(value(&arg)?),
+            "--manifest-path" => args.manifest_path = Some(value(&arg)?.into()),
+            "--module" => args.module = Some(value(&arg)?),
+            "--out-dir" => args.out_dir = Some(value(&arg)?.into()),
+            "--no-loader" => args.no_loader = true,
+            "-o" | "--output" => args.output = Some(value(&arg)?.into()),
+            "--" => {
+                args.cargo_args = iter.by_ref().collect();
+                break;
+            }
+            command if args.command.is_empty() && !command.starts_with('-') => {
+                args.command = command.to_owned();
+            }
+            other => return Err(format!("unexpected argument {}", other)),
+        }
+    }
+    match args.command.as_str() {
+        "" => Ok(None),
+        "build" | "install" | "package" => Ok(Some(args)),
+        other => Err(format!("unknown command {}", other)),
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"));
+    let mut plugin = match &args.package {
+        Some(package) => Plugin::from_package(&manifest_path, package)?,
+        None => Plugin::new(&manifest_path)?,
+    };
+    if let Some(profile) = &args.profile {
+        plugin = plugin.with_profile(profile);
+    }
+    if let Some(target) = &args.target {
+        plugin = plugin.with_target(target);
+    }
+    if let Some(module) = &args.module {
+        plugin = plugin.with_module(module);
+    }
+    if let Some(out_dir) = args.out_dir {
+        plugin = plugin.with_out_dir(out_dir);
+    }
+    if args.no_loader {
+        plugin = plugin.with_loader(false);
+    }
+
+    match args.command.as_str() {
+        "build" => {
+            plugin.build(&args.cargo_args)?;
+            let path = plugin.install()?;
+            eprintln!("Installed {}", path.display());
+        }
+        "install" => {
+            let path = plugin.install()?;
+            eprintln!("Installed {}", path.display());
+        }
+        _ => {
+            plugin.build(&args.cargo_args)?;
+            let path = match args.output {
+                Some(path) => {
+                    plugin.tarball_to(&path)?;
+                    path
+                }
+                None => plugin.tarball()?,
+            };
+            eprintln!("Packaged {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match parse_args() {
+        Ok(Some(args)) => match run(args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        },
+        Ok(None) => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
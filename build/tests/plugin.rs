@@ -0,0 +1,149 @@
+//! Tests for building and packaging the test plugin
+
+use flate2::read::GzDecoder;
+use nvim_utils_build::{Error, Plugin};
+use std::{fs, path::PathBuf};
+
+fn test_plugin() -> Plugin {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-plugin/Cargo.toml");
+    Plugin::new(manifest).expect("test-plugin should be in the workspace")
+}
+
+#[test]
+fn reads_metadata() {
+    let plugin = test_plugin();
+    assert_eq!(plugin.name(), "test-plugin");
+    assert_eq!(plugin.module(), "test_plugin");
+    assert!(plugin.profile_dir().ends_with("debug"));
+    assert!(plugin
+        .clone()
+        .with_profile("release")
+        .profile_dir()
+        .ends_with("release"));
+    assert!(plugin
+        .with_target("x86_64-pc-windows-msvc")
+        .artifact()
+        .ends_with("x86_64-pc-windows-msvc/debug/test_plugin.dll"));
+}
+
+#[test]
+fn module_paths() {
+    let plugin = test_plugin().with_module("renamed.sub");
+    let mac = plugin.clone().with_target("aarch64-apple-darwin");
+    assert_eq!(
+        mac.module_path(),
+        PathBuf::from("lua/renamed/sub/sub-macos-aarch64.so")
+    );
+    assert_eq!(
+        mac.loader_path(),
+        Some(PathBuf::from("lua/renamed/sub/init.lua"))
+    );
+    assert!(mac.artifact().ends_with("libtest_plugin.dylib"));
+    assert_eq!(mac.target_os(), "macos");
+    assert_eq!(mac.target_arch(), "aarch64");
+
+    let windows = plugin
+        .with_target("i686-pc-windows-msvc")
+        .with_loader(false);
+    assert_eq!(windows.module_path(), PathBuf::from("lua/renamed/sub.dll"));
+    assert_eq!(windows.loader_path(), None);
+    assert_eq!(windows.target_arch(), "x86");
+}
+
+#[test]
+fn loader_source() -> Result<(), Error> {
+    let plugin = test_plugin().with_module("renamed.sub");
+    let source = plugin.clone().with_profile("release").loader_source();
+    assert!(!source.contains("{{"));
+    assert!(source.contains(r#"local module = "renamed.sub""#));
+    assert!(source.contains(r#"local package_name = "test-plugin""#));
+    assert!(source.contains(&format!(
+        r#"local expected_version = "{}""#,
+        plugin.version()
+    )));
+    assert!(source
+        .contains(r#"local build_command = "cargo nvim build --release --module renamed.sub""#));
+    assert!(source.contains(r#"local symbol = "renamed_sub""#));
+    assert!(plugin
+        .with_profile("dist")
+        .loader_source()
+        .contains("cargo nvim build --profile dist --module renamed.sub"));
+    assert!(test_plugin()
+        .loader_source()
+        .contains(r#"local build_command = "cargo nvim build""#));
+
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../Cargo.toml");
+    let by_package = Plugin::from_package(manifest, "test-plugin")?;
+    assert!(by_package
+        .loader_source()
+        .contains(r#"local build_command = "cargo nvim build -p test-plugin""#));
+    Ok(())
+}
+
+#[test]
+fn rejects_packages_without_cdylib() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    match Plugin::new(manifest) {
+        Err(Error::NoCdylib(name)) => assert_eq!(name, "nvim-utils-build"),
+        other => panic!("expected NoCdylib, got {:?}", other),
+    }
+}
+
+#[test]
+fn builds_installs_and_packages() -> Result<(), Error> {
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("nvim-utils-build");
+    if out.exists() {
+        fs::remove_dir_all(&out)?;
+    }
+    let plugin = test_plugin().with_out_dir(&out);
+    plugin.build(&[])?;
+
+    let installed = plugin.install()?;
+    assert_eq!(installed, out.join(plugin.module_path()));
+    assert!(installed.is_file());
+    let loader = out.join(plugin.loader_path().unwrap());
+    assert_eq!(fs::read_to_string(loader)?, plugin.loader_source());
+
+    let tarball = out.join("test-plugin.tar.gz");
+    plugin.tarball_to(&tarball)?;
+    let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(&tarball)?));
+    let entries = archive
+        .entries()?
+        .map(|entry| Ok(entry?.path()?.into_owned()))
+        .collect::<Result<Vec<_>, Error>>()?;
+    assert!(entries.contains(&PathBuf::from("test-plugin").join(plugin.module_path())));
+    assert!(entries.contains(&PathBuf::from("test-plugin").join(plugin.loader_path().unwrap())));
+    assert!(entries.iter().all(|path| path.starts_with("test-plugin")));
+    Ok(())
+}
+
+#[test]
+fn keeps_handwritten_init_lua() -> Result<(), Error> {
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("nvim-utils-build-conflict");
+    if out.exists() {
+        fs::remove_dir_all(&out)?;
+    }
+    let plugin = test_plugin().with_out_dir(&out);
+    let loader = out.join(plugin.loader_path().unwrap());
+    fs::create_dir_all(loader.parent().unwrap())?;
+    fs::write(&loader, "return require('test_plugin.core')")?;
+
+    match plugin.install() {
+        Err(Error::LoaderConflict(path)) => assert_eq!(path, loader),
+        other => panic!("expected LoaderConflict, got {:?}", other),
+    }
+    assert_eq!(
+        fs::read_to_string(&loader)?,
+        "return require('test_plugin.core')"
+    );
+    assert!(!out.join(plugin.module_path()).exists());
+
+    // A loader from an earlier install is replaced
+    fs::write(&loader, "-- Generated by nvim-utils-build, do not edit.\n")?;
+    plugin.build(&[])?;
+    plugin.install()?;
+    assert_eq!(fs::read_to_string(&loader)?, plugin.loader_source());
+    // Including the one it just wrote
+    plugin.install()?;
+    Ok(())
+}
@@ -0,0 +1,11 @@
+fn main() {
+    let artifacts = luajit_src::Build::new().build();
+    println!(
+        "cargo:rustc-link-search=native={}",
+        artifacts.lib_dir().display()
+    );
+    // Linked whole, since mlua comes after this crate in the link order and needs every symbol
+    for lib in artifacts.libs() {
+        println!("cargo:rustc-link-lib=static:+whole-archive={}", lib);
+    }
+}
@@ -0,0 +1,10 @@
+//! Links a LuaJIT into test binaries, for testing plugins with `nvim_utils::mock`
+//!
+//! mlua's `module` feature leaves Lua's symbols to be resolved from the Neovim executable,
+//! so tests that run without Neovim need a Lua of their own.
+//! Only add this crate to `[dev-dependencies]`: linked into a plugin, it would replace the Lua Neovim provides.
+//!
+//! Crates that are never named aren't linked, so the tests have to name it:
+//! ```rust
+//! extern crate nvim_utils_luajit;
+//! ```
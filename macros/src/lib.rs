@@ -3,33 +3,162 @@ use mlua::lua_State;
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
-use syn::{AttributeArgs, Error, Ident, Path, Result};
+use syn::{spanned::Spanned, AttributeArgs, Error, Ident, Result};
+
+/// A Lua module exported by the library, like `my_plugin.sub`
+struct Entry {
+    name: String,
+    span: Span,
+}
+
+impl Entry {
+    /// The name of the C functions for the module, with dots replaced by underscores like Lua's `require` does
+    fn symbol(&self) -> String {
+        self.name.replace('.', "_")
+    }
+}
 
 struct Plugin {
-    path: Path,
+    entries: Vec<Entry>,
 }
 
 impl Plugin {
     fn parse(args: AttributeArgs) -> Result<Self> {
-        let mut path = None;
+        let mut entries: Vec<Entry> = Vec::new();
 
         for arg in args {
-            use syn::Meta::Path;
+            use syn::Lit::Str;
+            use syn::Meta::{NameValue, Path};
             use syn::NestedMeta::*;
-            match arg {
-                Meta(Path(p)) => path = Some(p),
+            let entry = match &arg {
+                Meta(Path(path)) => Entry {
+                    name: path
+                        .segments
+                        .iter()
+                        .map(|s| s.ident.to_string())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                    span: path.span(),
+                },
+                Meta(NameValue(nv)) if nv.path.is_ident("name") => match &nv.lit {
+                    Str(name) => Entry {
+                        name: name.value(),
+                        span: name.span(),
+                    },
+                    lit => return Err(Error::new_spanned(lit, "expected a string")),
+                },
                 _ => {
-                    return Err(Error::new_spanned(arg, "expected `name = \"...\"`"));
+                    return Err(Error::new_spanned(
+                        arg,
+                        "expected a module path or `name = \"...\"`",
+                    ));
                 }
+            };
+
+            let valid = |part: &str| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            };
+            if !entry.name.split('.').all(valid) {
+                return Err(Error::new(
+                    entry.span,
+                    format!(
+                        "invalid module name `{}`, expected names like `my_plugin.sub` made of letters, digits and underscores",
+                        entry.name
+                    ),
+                ));
             }
+            if let Some(other) = entries.iter().find(|e| e.symbol() == entry.symbol()) {
+                return Err(Error::new(
+                    entry.span,
+                    format!(
+                        "module `{}` has the same entry point as `{}`, `luaopen_{}`",
+                        entry.name,
+                        other.name,
+                        entry.symbol()
+                    ),
+                ));
+            }
+            entries.push(entry);
         }
 
-        let path = path.ok_or_else(|| Error::new(Span::call_site(), "expected module path"))?;
+        if entries.is_empty() {
+            return Err(Error::new(
+                Span::call_site(),
+                "expected a module name, like `#[module(name = \"my_plugin\")]`",
+            ));
+        }
 
-        Ok(Self { path })
+        Ok(Self { entries })
     }
 }
 
+/// Exports a function returning the module table as one or more Lua modules.
+///
+/// `#[module(my_plugin)]` or `#[module(name = "my_plugin")]` generates `luaopen_my_plugin`, the entry point
+/// `require("my_plugin")` looks for, and `nvim_utils_version_my_plugin`, a Lua C function returning the crate version.
+/// The loader generated by `cargo nvim` calls it to check that the library matches the plugin's Lua files.
+///
+/// Dots in a name become underscores, so `#[module(name = "my_plugin.sub")]` generates `luaopen_my_plugin_sub`
+/// for `require("my_plugin.sub")`. A library can annotate several functions, and a function can be exported under
+/// several names with `#[module(name = "a", name = "b")]`. Names with the same entry point are a compile error,
+/// like `my_plugin.sub` and `my_plugin_sub`.
+///
+/// ## Example
+/// ```rust
+/// # use nvim_utils_macros as nvim_utils;
+/// # use mlua::prelude::*;
+/// #[nvim_utils::module(my_plugin)]
+/// fn my_plugin(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///     lua.create_table()
+/// }
+///
+/// #[nvim_utils::module(name = "my_plugin.sub", name = "my_plugin.alias")]
+/// fn sub(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///     lua.create_table()
+/// }
+/// ```
+///
+/// Names with the same entry point fail to compile, in one attribute:
+/// ```compile_fail
+/// # use nvim_utils_macros as nvim_utils;
+/// # use mlua::prelude::*;
+/// #[nvim_utils::module(name = "my_plugin.sub", name = "my_plugin_sub")]
+/// fn sub(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///     lua.create_table()
+/// }
+/// ```
+///
+/// Or on different functions, even in different modules:
+/// ```compile_fail
+/// mod first {
+///     # use nvim_utils_macros as nvim_utils;
+///     # use mlua::prelude::*;
+///     #[nvim_utils::module(my_plugin)]
+///     fn first(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///         lua.create_table()
+///     }
+/// }
+///
+/// mod second {
+///     # use nvim_utils_macros as nvim_utils;
+///     # use mlua::prelude::*;
+///     #[nvim_utils::module(name = "my_plugin")]
+///     fn second(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///         lua.create_table()
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// And so do names that `require` can't load:
+/// ```compile_fail
+/// # use nvim_utils_macros as nvim_utils;
+/// # use mlua::prelude::*;
+/// #[nvim_utils::module(name = "my_plugin..sub")]
+/// fn sub(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///     lua.create_table()
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn module(attr: TokenStream, item: TokenStream) -> TokenStream {
     let attr = syn::parse_macro_input!(attr as AttributeArgs);
@@ -38,27 +167,44 @@ pub fn module(attr: TokenStream, item: TokenStream) -> TokenStream {
         Err(err) => return err.to_compile_error().into(),
     };
 
-    let path = plugin
-        .path
-        .segments
-        .iter()
-        .map(|s| s.ident.to_string())
-        .collect::<Vec<_>>()
-        .join("_");
-
     let func = syn::parse_macro_input!(item as syn::ItemFn);
     let name = func.sig.ident.clone();
 
-    let entry = Ident::new(&format!("luaopen_{path}"), Span::call_site());
+    let entries = plugin.entries.iter().map(|entry| {
+        let symbol = entry.symbol();
+        let entry_fn = Ident::new(&format!("luaopen_{symbol}"), entry.span);
+        let version = Ident::new(&format!("nvim_utils_version_{symbol}"), entry.span);
+        // Unmangled symbols are unique across the whole crate, so annotating two functions with the same entry point,
+        // even in different modules, fails with "symbol `DUPLICATE_MODULE_NAME_luaopen_...` is already defined".
+        // It comes before the entry point, whose own error would be reported first otherwise.
+        let guard = Ident::new(
+            &format!("DUPLICATE_MODULE_NAME_luaopen_{symbol}"),
+            entry.span,
+        );
+        quote! {
+            #[allow(non_upper_case_globals)]
+            #[no_mangle]
+            static #guard: () = ();
+
+            #[no_mangle]
+            unsafe extern "C" fn #entry_fn(state: *mut mlua::lua_State) -> std::os::raw::c_int {
+                mlua::Lua::init_from_ptr(state)
+                    .entrypoint1(#name)
+                    .expect("failed to register module")
+            }
+
+            #[no_mangle]
+            unsafe extern "C" fn #version(state: *mut mlua::lua_State) -> std::os::raw::c_int {
+                mlua::Lua::init_from_ptr(state)
+                    .entrypoint1(|_| Ok(env!("CARGO_PKG_VERSION")))
+                    .expect("failed to get module version")
+            }
+        }
+    });
     let wrapped = quote! {
         #func
 
-        #[no_mangle]
-        unsafe extern "C" fn #entry(state: *mut lua_State) -> std::os::raw::c_int {
-            mlua::Lua::init_from_ptr(state)
-                .entrypoint1(#name)
-                .expect("failed to register module")
-        }
+        #(#entries)*
     };
 
     wrapped.into()
@@ -69,12 +69,12 @@ impl<'a> ModuleBuilder<'a> {
     }
 
     /// Produces an iterator over the fields in the builder
-    pub fn fields(&'a self) -> impl Iterator<Item = (&String, &LuaValue)> {
+    pub fn fields(&'a self) -> impl Iterator<Item = (&'a String, &'a LuaValue<'a>)> {
         self.fields.iter()
     }
 
     /// Produces a mutable iterator over the fields in the builder
-    pub fn fields_mut(&'a mut self) -> impl Iterator<Item = (&String, &mut LuaValue)> {
+    pub fn fields_mut(&'a mut self) -> impl Iterator<Item = (&'a String, &'a mut LuaValue<'a>)> {
         self.fields.iter_mut()
     }
 
@@ -93,16 +93,20 @@ impl<'a> ModuleBuilder<'a> {
 
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-    /// Adds an async function to the module
+    /// Adds an async function to the module, which runs on [`vim::runtime`]
+    ///
+    /// Calling it from a Lua coroutine suspends the coroutine until the function returns,
+    /// otherwise it runs in the background and errors are reported with `vim.notify`.
+    /// The future can't borrow the Lua state, it calls Lua with [`vim::runtime::with_lua`].
     pub fn add_fn_async<A, R, F, FR>(&mut self, name: &str, func: F) -> LuaResult<&mut Self>
     where
         F: 'static + Send + Fn(&'a Lua, A) -> FR,
         A: FromLuaMulti<'a>,
-        R: ToLuaMulti<'a>,
-        FR: 'a + Send + Future<Output = LuaResult<R>>,
+        R: for<'r> ToLuaMulti<'r>,
+        FR: 'static + Future<Output = LuaResult<R>>,
     {
         self.check_collision(name)?;
-        let func = self.lua.create_async_function(func)?;
+        let func = vim::runtime::create_async_function(self.lua, func)?;
         self.fields.insert(name.to_owned(), self.lua.pack(func)?);
         Ok(self)
     }
@@ -197,16 +201,17 @@ impl<'a> ModuleBuilder<'a> {
 
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-    /// Adds an async function to the module, consuming and returning the builder
+    /// Adds an async function to the module, consuming and returning the builder.
+    /// See [`ModuleBuilder::add_fn_async`].
     pub fn with_fn_async<A, R, F, FR>(mut self, name: &str, func: F) -> LuaResult<Self>
     where
         F: 'static + Send + Fn(&'a Lua, A) -> FR,
         A: FromLuaMulti<'a>,
-        R: ToLuaMulti<'a>,
-        FR: 'a + Send + Future<Output = LuaResult<R>>,
+        R: for<'r> ToLuaMulti<'r>,
+        FR: 'static + Future<Output = LuaResult<R>>,
     {
         self.check_collision(name)?;
-        let func = self.lua.create_async_function(func)?;
+        let func = vim::runtime::create_async_function(self.lua, func)?;
         self.fields.insert(name.to_owned(), self.lua.pack(func)?);
         Ok(self)
     }
@@ -298,6 +303,6 @@ impl<'a> ModuleBuilder<'a> {
 
 impl<'a> ToLua<'a> for ModuleBuilder<'a> {
     fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
-        Ok(lua.pack(self.build()?)?)
+        lua.pack(self.build()?)
     }
 }
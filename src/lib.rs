@@ -13,8 +13,10 @@
 //! #### Features
 //! - `builder` enables the [`builder`] module, containing [`ModuleBuilder`](struct@builder) (enabled by default)
 //! - `vim` enables the [`vim`] module (enabled by default)
-//! - `async` enables async functions in [`builder::ModuleBuilder`], and the `async` feature in mlua (disabled by default)
-//! - `send` enables the `send` feature for [`mlua`], which enables `Send` for lua types (disabled by default)
+//! - `async` enables the `vim::runtime` executor, async functions in [`builder::ModuleBuilder`], and the `async` feature in mlua (disabled by default)
+//! - `send` enables `vim::thread` for running closures on the main thread from other threads (Unix only), and the `send` feature for [`mlua`], which enables `Send` for lua types (disabled by default)
+//! - `mock` enables the [`mock`] module, a fake `vim` global for testing plugins without Neovim (disabled by default)
+//! - `testing` enables the [`testing`] module, a harness for running tests against compiled plugins in headless Neovim (disabled by default)
 //! - `unstable` includes unstable / untested API features (disabled by default)
 
 /// Includes [`mlua::prelude`], [`vim`], [`vim::ext::log`], and [`builder::ModuleBuilder`] if the corresponding features are enabled
@@ -37,6 +39,32 @@ pub mod prelude {
     pub use mlua::prelude::*;
 }
 
+/// Bound on callbacks passed to Lua, which is `Send` only when the `send` feature is enabled, like `mlua`'s own bound.
+/// Without `send`, callbacks can capture `Rc` and `RefCell`.
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "send")]
+impl<T: Send> MaybeSend for T {}
+
+/// Bound on callbacks passed to Lua, which is `Send` only when the `send` feature is enabled, like `mlua`'s own bound.
+/// Without `send`, callbacks can capture `Rc` and `RefCell`.
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send"))]
+impl<T> MaybeSend for T {}
+
+// Boxes a callback trait object, adding `Send` when the `send` feature is enabled, see [`MaybeSend`]
+#[cfg(feature = "send")]
+#[allow(unused_macros)]
+macro_rules! maybe_send_box {
+    ($($callback:tt)+) => { Box<$($callback)+ + Send + 'static> };
+}
+#[cfg(not(feature = "send"))]
+#[allow(unused_macros)]
+macro_rules! maybe_send_box {
+    ($($callback:tt)+) => { Box<$($callback)+ + 'static> };
+}
+
 #[allow(unused_imports)]
 #[macro_use]
 extern crate nvim_utils_macros;
@@ -45,6 +73,12 @@ pub use nvim_utils_macros::module;
 #[cfg(feature = "builder")]
 #[cfg_attr(docsrs, doc(cfg(feature = "builder")))]
 pub mod builder;
+#[cfg(feature = "mock")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mock")))]
+pub mod mock;
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
 #[cfg(feature = "vim")]
 #[cfg_attr(docsrs, doc(cfg(feature = "vim")))]
 pub mod vim;
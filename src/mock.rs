@@ -0,0 +1,124 @@
+//! A fake `vim` global for testing plugins without Neovim
+//!
+//! [`install`] sets up an in-memory editor in a bare [`Lua`] state: buffers, windows, a single tabpage, options,
+//! variables, keymaps, autocommands, user commands, extmarks, highlights and a log of notifications.
+//! It implements the subset of `vim.api` and `vim.fn` that this crate wraps, so modules built with
+//! [`ModuleBuilder`](crate::builder::ModuleBuilder) can be tested with ordinary `#[test]`s.
+//!
+//! Nothing runs on its own: scheduled callbacks only run in [`run_scheduled`], and `vim.uv` timers use a virtual clock
+//! that only moves in [`advance`]. File watchers never fire, and processes can't be spawned.
+//!
+//! mlua's `module` feature expects to be loaded by Neovim, so the mock needs a Lua of its own.
+//! Add `nvim-utils-luajit` to `[dev-dependencies]` along with this feature, and name it in the tests
+//! with `extern crate nvim_utils_luajit;`, so LuaJIT is only linked into test binaries and never into the plugin.
+//!
+//! ## Example
+//! ```rust
+//! # extern crate nvim_utils_luajit;
+//! use nvim_utils::prelude::*;
+//! use vim::api::Buffer;
+//!
+//! fn main() -> LuaResult<()> {
+//!     let lua = nvim_utils::mock::new()?;
+//!     Buffer::CURRENT.set_lines(&lua, .., false, vec!["hello"])?;
+//!     assert_eq!(Buffer::CURRENT.get_lines(&lua, .., false)?, vec!["hello"]);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::prelude::*;
+use std::time::Duration;
+use vim::log::LogLevel;
+
+const VIM: &str = include_str!("mock/vim.lua");
+
+/// Creates a Lua state with the mock `vim` global installed
+pub fn new() -> LuaResult<Lua> {
+    let lua = Lua::new();
+    install(&lua)?;
+    Ok(lua)
+}
+
+/// Installs the mock `vim` global, replacing any existing one
+pub fn install(lua: &Lua) -> LuaResult<()> {
+    let vim: LuaTable = lua.load(VIM).set_name("nvim_utils::mock")?.call(())?;
+    lua.globals().set("vim", vim)
+}
+
+/// Gets the mock's internal state, `vim._mock`
+fn state(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+    vim::get(lua)?.get("_mock")
+}
+
+/// A message sent with `vim.notify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub message: String,
+    pub level: LogLevel,
+}
+
+/// Returns every message sent with `vim.notify` so far, oldest first
+pub fn notifications(lua: &Lua) -> LuaResult<Vec<Notification>> {
+    state(lua)?
+        .get::<_, LuaTable>("notifications")?
+        .sequence_values::<LuaTable>()
+        .map(|notification| {
+            let notification = notification?;
+            let level = match notification.get::<_, u8>("level")? {
+                0 => LogLevel::Trace,
+                1 => LogLevel::Debug,
+                2 => LogLevel::Info,
+                3 => LogLevel::Warn,
+                4 => LogLevel::Error,
+                _ => LogLevel::Off,
+            };
+            Ok(Notification {
+                message: notification.get("message")?,
+                level,
+            })
+        })
+        .collect()
+}
+
+/// Returns every message written with `nvim_echo`, `nvim_out_write` or `nvim_err_write` so far, oldest first
+pub fn messages(lua: &Lua) -> LuaResult<Vec<String>> {
+    state(lua)?
+        .get::<_, LuaTable>("messages")?
+        .sequence_values()
+        .collect()
+}
+
+/// Returns the Ex commands that were run but not handled by a user command, oldest first.
+/// Keys passed to `nvim_feedkeys` that don't match a mapping are logged as `normal <keys>`.
+pub fn commands(lua: &Lua) -> LuaResult<Vec<String>> {
+    state(lua)?
+        .get::<_, LuaTable>("ex_commands")?
+        .sequence_values()
+        .collect()
+}
+
+/// Runs callbacks queued with `vim.schedule` and pending `vim.uv` async handles,
+/// until there's nothing left to run at the current time
+pub fn run_scheduled(lua: &Lua) -> LuaResult<()> {
+    state(lua)?.call_function::<_, _, ()>("tick", ())
+}
+
+/// Moves the virtual clock forward, firing due timers in order and running scheduled callbacks after each one
+pub fn advance(lua: &Lua, duration: Duration) -> LuaResult<()> {
+    state(lua)?.call_function::<_, _, ()>("advance", duration.as_millis() as u64)
+}
+
+/// Sets the mode returned by `mode()` and `nvim_get_mode()`.
+/// Entering a visual mode anchors the selection at the cursor, so `getpos("v")` stays there while the cursor moves,
+/// and sets `visualmode()`.
+pub fn set_mode(lua: &Lua, mode: &str) -> LuaResult<()> {
+    let state = state(lua)?;
+    if vim::ext::SelectionMode::from_mode(mode).is_some() {
+        let (row, col) = vim::api::Window::CURRENT.get_cursor(lua)?.to_cursor();
+        state.set("visual_start", [row, col])?;
+        state.set("visualmode", &mode[..1])?;
+    } else {
+        state.set("visual_start", LuaNil)?;
+    }
+    state.set("mode", mode)
+}
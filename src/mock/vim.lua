@@ -0,0 +1,2401 @@
+-- A fake `vim` global, installed by `nvim_utils::mock::install`
+--
+-- Everything lives in memory: buffers, windows, a single tabpage, options, variables, keymaps, autocommands,
+-- user commands, extmarks, highlights and a log of notifications. `vim.uv` timers run on a virtual clock that
+-- only moves when the tests advance it. Errors use the same messages as Neovim where it matters.
+
+local vim = {}
+local api = {}
+local fn = {}
+local uv = {}
+
+local unpack = unpack or table.unpack
+
+local state = {
+  buffers = {},
+  next_buf = 1,
+  windows = {},
+  next_win = 1000,
+  tabpages = {},
+  next_tab = 1,
+  current = {},
+  options = {},
+  g = {},
+  keymaps = {},
+  augroups = {},
+  autocmds = {},
+  next_autocmd = 1,
+  commands = {},
+  namespaces = {},
+  next_ns = 1,
+  highlights = {},
+  hl_ids = {},
+  next_hl = 1,
+  marks = {},
+  mode = "n",
+  visualmode = "",
+  visual_start = nil,
+  notifications = {},
+  messages = {},
+  ex_commands = {},
+  health = {},
+  scheduled = {},
+  now = 0,
+  timers = {},
+  checks = {},
+  asyncs = {},
+  spawned = {},
+  cwd = os.getenv("PWD") or "/",
+}
+vim._mock = state
+
+-- Options --------------------------------------------------------------------------------------------------------
+
+-- name = { shortname, type, scope, default, commalist, flaglist }
+local option_defs = {
+  autoindent = { "ai", "boolean", "buf", false },
+  buftype = { "bt", "string", "buf", "" },
+  columns = { "co", "number", "global", 80 },
+  completeopt = { "cot", "string", "global", "menu,preview", true },
+  cursorline = { "cul", "boolean", "win", false },
+  expandtab = { "et", "boolean", "buf", false },
+  fileformat = { "ff", "string", "buf", "unix" },
+  filetype = { "ft", "string", "buf", "" },
+  hidden = { "hid", "boolean", "global", true },
+  ignorecase = { "ic", "boolean", "global", false },
+  lines = { "lines", "number", "global", 24 },
+  list = { "list", "boolean", "win", false },
+  modifiable = { "ma", "boolean", "buf", true },
+  modified = { "mod", "boolean", "buf", false },
+  number = { "nu", "boolean", "win", false },
+  operatorfunc = { "opfunc", "string", "global", "" },
+  readonly = { "ro", "boolean", "buf", false },
+  relativenumber = { "rnu", "boolean", "win", false },
+  runtimepath = { "rtp", "string", "global", "", true },
+  scrolloff = { "so", "number", "global", 0 },
+  shiftwidth = { "sw", "number", "buf", 8 },
+  shortmess = { "shm", "string", "global", "filnxtToOF", false, true },
+  signcolumn = { "scl", "string", "win", "auto" },
+  smartcase = { "scs", "boolean", "global", false },
+  softtabstop = { "sts", "number", "buf", 0 },
+  spell = { "spell", "boolean", "win", false },
+  tabstop = { "ts", "number", "buf", 8 },
+  termguicolors = { "tgc", "boolean", "global", false },
+  textwidth = { "tw", "number", "buf", 0 },
+  updatetime = { "ut", "number", "global", 4000 },
+  wildmode = { "wim", "string", "global", "full", true },
+  winhighlight = { "winhl", "string", "win", "", true },
+  wrap = { "wrap", "boolean", "win", true },
+}
+
+local option_aliases = {}
+for name, def in pairs(option_defs) do
+  option_aliases[def[1]] = name
+  state.options[name] = def[4]
+end
+
+local function option_name(name)
+  local full = option_defs[name] and name or option_aliases[name]
+  if not full then
+    error("Unknown option '" .. tostring(name) .. "'", 3)
+  end
+  return full
+end
+
+-- Buffers and windows --------------------------------------------------------------------------------------------
+
+local function copy(t)
+  local new = {}
+  for k, v in pairs(t) do
+    new[k] = v
+  end
+  return new
+end
+
+local function new_buffer(listed, scratch)
+  local id = state.next_buf
+  state.next_buf = id + 1
+  state.buffers[id] = {
+    id = id,
+    name = "",
+    lines = { "" },
+    listed = listed ~= false,
+    loaded = true,
+    vars = {},
+    opts = scratch and { buftype = "nofile" } or {},
+    changedtick = 2,
+    marks = {},
+    extmarks = {},
+    attached = {},
+    keymaps = {},
+    commands = {},
+  }
+  return id
+end
+
+local function buffer(id, level)
+  if id == nil or id == 0 then
+    id = state.current.buf
+  end
+  local buf = state.buffers[id]
+  if not buf then
+    error("Invalid buffer id: " .. tostring(id), (level or 1) + 2)
+  end
+  return buf
+end
+
+local function window(id, level)
+  if id == nil or id == 0 then
+    id = state.current.win
+  end
+  local win = state.windows[id]
+  if not win then
+    error("Invalid window id: " .. tostring(id), (level or 1) + 2)
+  end
+  return win
+end
+
+local function tabpage(id, level)
+  if id == nil or id == 0 then
+    id = state.current.tab
+  end
+  local tab = state.tabpages[id]
+  if not tab then
+    error("Invalid tabpage id: " .. tostring(id), (level or 1) + 2)
+  end
+  return tab
+end
+
+local function new_window(buf, tab, config)
+  local id = state.next_win
+  state.next_win = id + 1
+  state.windows[id] = {
+    id = id,
+    buf = buf,
+    tab = tab,
+    cursor = { 1, 0 },
+    width = config and config.width or 80,
+    height = config and config.height or 24,
+    vars = {},
+    opts = {},
+    config = config,
+    hl_ns = 0,
+  }
+  table.insert(state.tabpages[tab].wins, id)
+  return id
+end
+
+local function new_tabpage()
+  local id = state.next_tab
+  state.next_tab = id + 1
+  state.tabpages[id] = { id = id, wins = {}, vars = {} }
+  return id
+end
+
+local function sorted_keys(t)
+  local keys = {}
+  for k in pairs(t) do
+    table.insert(keys, k)
+  end
+  table.sort(keys)
+  return keys
+end
+
+-- Autocommands ---------------------------------------------------------------------------------------------------
+
+local function glob_to_pattern(glob)
+  local pattern = glob:gsub("[%^%$%(%)%%%.%[%]%+%-]", "%%%0"):gsub("%*", ".*"):gsub("%?", ".")
+  return "^" .. pattern .. "$"
+end
+
+local function autocmd_matches(autocmd, event, opts)
+  if not autocmd.events[event] then
+    return false
+  end
+  if opts.group and autocmd.group ~= opts.group then
+    return false
+  end
+  if autocmd.buffer then
+    return opts.buffer == autocmd.buffer
+  end
+  if not autocmd.patterns then
+    return true
+  end
+  local subject = opts.pattern or opts.file or ""
+  for _, pattern in ipairs(autocmd.patterns) do
+    if pattern == "*" or subject:match(glob_to_pattern(pattern)) then
+      return true
+    end
+  end
+  return false
+end
+
+local function list(value)
+  if value == nil then
+    return nil
+  elseif type(value) == "table" then
+    return value
+  else
+    return { value }
+  end
+end
+
+local function group_id(group, level)
+  if group == nil then
+    return nil
+  elseif type(group) == "string" then
+    local id = state.augroups[group]
+    if not id then
+      error("Invalid 'group': '" .. group .. "'", (level or 1) + 2)
+    end
+    return id
+  end
+  return group
+end
+
+local function exec_autocmds(events, opts)
+  opts = opts or {}
+  local group = group_id(opts.group, 2)
+  local buf = opts.buffer
+  if buf == 0 then
+    buf = state.current.buf
+  end
+  local patterns = list(opts.pattern)
+  if patterns == nil or #patterns == 0 then
+    patterns = { false }
+  end
+  for _, event in ipairs(list(events)) do
+    for _, pattern in ipairs(patterns) do
+      pattern = pattern or nil
+      local file = pattern
+      if not file and buf and state.buffers[buf] then
+        file = state.buffers[buf].name
+      end
+      local match_opts = { group = group, buffer = buf, pattern = pattern, file = file }
+      -- Copied, since callbacks can create and delete autocommands
+      for _, autocmd in ipairs(copy(state.autocmds)) do
+        if not autocmd.deleted and autocmd_matches(autocmd, event, match_opts) then
+          if autocmd.once then
+            api.nvim_del_autocmd(autocmd.id)
+          end
+          if autocmd.callback then
+            local args = {
+              id = autocmd.id,
+              event = event,
+              group = autocmd.group,
+              match = file or "",
+              buf = buf or state.current.buf,
+              file = file or "",
+              data = opts.data,
+            }
+            if autocmd.callback(args) == true then
+              api.nvim_del_autocmd(autocmd.id)
+            end
+          elseif autocmd.command then
+            vim.cmd(autocmd.command)
+          end
+        end
+      end
+    end
+  end
+end
+
+local function fire(event, buf, extra)
+  local opts = { buffer = buf }
+  if extra then
+    for k, v in pairs(extra) do
+      opts[k] = v
+    end
+  end
+  exec_autocmds(event, opts)
+end
+
+function api.nvim_create_augroup(name, opts)
+  local id = state.augroups[name]
+  if not id then
+    id = state.next_autocmd
+    state.next_autocmd = id + 1
+    state.augroups[name] = id
+  elseif opts == nil or opts.clear ~= false then
+    api.nvim_clear_autocmds({ group = id })
+  end
+  return id
+end
+
+function api.nvim_del_augroup_by_id(id)
+  for name, group in pairs(state.augroups) do
+    if group == id then
+      api.nvim_clear_autocmds({ group = id })
+      state.augroups[name] = nil
+    end
+  end
+end
+
+function api.nvim_del_augroup_by_name(name)
+  local id = state.augroups[name]
+  if not id then
+    error("E367: No such group: \"" .. name .. "\"", 2)
+  end
+  api.nvim_del_augroup_by_id(id)
+end
+
+function api.nvim_create_autocmd(events, opts)
+  opts = opts or {}
+  if opts.callback and opts.command then
+    error("Cannot use both 'callback' and 'command'", 2)
+  end
+  local id = state.next_autocmd
+  state.next_autocmd = id + 1
+  local event_set = {}
+  for _, event in ipairs(list(events)) do
+    event_set[event] = true
+  end
+  local buf = opts.buffer
+  if buf == 0 then
+    buf = state.current.buf
+  end
+  local callback = opts.callback
+  if type(callback) == "string" then
+    local name = callback
+    callback = function(args)
+      return vim.fn[name](args)
+    end
+  end
+  table.insert(state.autocmds, {
+    id = id,
+    events = event_set,
+    group = group_id(opts.group, 1),
+    patterns = list(opts.pattern),
+    buffer = buf,
+    callback = callback,
+    command = opts.command,
+    once = opts.once or false,
+    nested = opts.nested or false,
+    desc = opts.desc,
+  })
+  return id
+end
+
+function api.nvim_del_autocmd(id)
+  for i, autocmd in ipairs(state.autocmds) do
+    if autocmd.id == id then
+      autocmd.deleted = true
+      table.remove(state.autocmds, i)
+      return
+    end
+  end
+end
+
+local function autocmd_filter(opts)
+  opts = opts or {}
+  local events = list(opts.event)
+  local group = group_id(opts.group, 2)
+  local buf = opts.buffer
+  if buf == 0 then
+    buf = state.current.buf
+  end
+  return function(autocmd)
+    if group and autocmd.group ~= group then
+      return false
+    end
+    if buf and autocmd.buffer ~= buf then
+      return false
+    end
+    if events then
+      local found = false
+      for _, event in ipairs(events) do
+        found = found or autocmd.events[event] == true
+      end
+      if not found then
+        return false
+      end
+    end
+    if opts.pattern then
+      local found = false
+      for _, pattern in ipairs(list(opts.pattern)) do
+        for _, p in ipairs(autocmd.patterns or {}) do
+          found = found or p == pattern
+        end
+      end
+      return found
+    end
+    return true
+  end
+end
+
+function api.nvim_clear_autocmds(opts)
+  local matches = autocmd_filter(opts)
+  for i = #state.autocmds, 1, -1 do
+    if matches(state.autocmds[i]) then
+      state.autocmds[i].deleted = true
+      table.remove(state.autocmds, i)
+    end
+  end
+end
+
+function api.nvim_get_autocmds(opts)
+  local matches = autocmd_filter(opts)
+  local result = {}
+  for _, autocmd in ipairs(state.autocmds) do
+    if matches(autocmd) then
+      for _, event in ipairs(sorted_keys(autocmd.events)) do
+        local group_name
+        for name, id in pairs(state.augroups) do
+          if id == autocmd.group then
+            group_name = name
+          end
+        end
+        table.insert(result, {
+          id = autocmd.id,
+          event = event,
+          group = autocmd.group,
+          group_name = group_name,
+          pattern = autocmd.patterns and table.concat(autocmd.patterns, ",") or "*",
+          buffer = autocmd.buffer,
+          buflocal = autocmd.buffer ~= nil,
+          callback = autocmd.callback,
+          command = autocmd.command or "",
+          once = autocmd.once,
+          desc = autocmd.desc,
+        })
+      end
+    end
+  end
+  return result
+end
+
+function api.nvim_exec_autocmds(events, opts)
+  exec_autocmds(events, opts)
+end
+
+-- Buffer contents ------------------------------------------------------------------------------------------------
+
+local function line_index(buf, index, strict, level)
+  local count = #buf.lines
+  if index < 0 then
+    index = count + 1 + index
+  end
+  if index < 0 or index > count then
+    if strict then
+      error("Index out of bounds", level + 1)
+    end
+    index = math.max(0, math.min(index, count))
+  end
+  return index
+end
+
+local function notify_lines(buf, first, last_old, last_new)
+  for _, attached in ipairs(copy(buf.attached)) do
+    if attached.on_lines then
+      local detach = attached.on_lines("lines", buf.id, buf.changedtick, first, last_old, last_new, 0)
+      if detach == true then
+        api.nvim_buf_detach(buf.id, attached)
+      end
+    end
+  end
+end
+
+local function shift_marks(buf, first, last_old, last_new)
+  local delta = last_new - last_old
+  for _, marks in pairs(buf.extmarks) do
+    for _, mark in pairs(marks) do
+      if mark.row >= last_old then
+        mark.row = mark.row + delta
+      elseif mark.row >= first and mark.row >= last_new then
+        mark.row = math.max(first, last_new - 1)
+        mark.col = 0
+      end
+    end
+  end
+end
+
+local function replace_lines(buf, first, last, replacement)
+  local new = {}
+  for i = 1, first do
+    table.insert(new, buf.lines[i])
+  end
+  for _, line in ipairs(replacement) do
+    table.insert(new, line)
+  end
+  for i = last + 1, #buf.lines do
+    table.insert(new, buf.lines[i])
+  end
+  if #new == 0 then
+    new = { "" }
+  end
+  buf.lines = new
+  buf.changedtick = buf.changedtick + 1
+  if buf.opts.modified == nil or not buf.opts.modified then
+    buf.opts.modified = true
+  end
+  shift_marks(buf, first, last, first + #replacement)
+  -- Keep cursors inside the buffer
+  for _, win in pairs(state.windows) do
+    if win.buf == buf.id and win.cursor[1] > #buf.lines then
+      win.cursor = { #buf.lines, 0 }
+    end
+  end
+  notify_lines(buf, first, last, first + #replacement)
+end
+
+function api.nvim_create_buf(listed, scratch)
+  local id = new_buffer(listed, scratch)
+  fire("BufNew", id)
+  return id
+end
+
+function api.nvim_list_bufs()
+  return sorted_keys(state.buffers)
+end
+
+function api.nvim_get_current_buf()
+  return state.current.buf
+end
+
+function api.nvim_set_current_buf(id)
+  buffer(id)
+  api.nvim_win_set_buf(state.current.win, id)
+end
+
+function api.nvim_buf_is_valid(id)
+  return state.buffers[id] ~= nil
+end
+
+function api.nvim_buf_is_loaded(id)
+  local buf = state.buffers[id]
+  return buf ~= nil and buf.loaded
+end
+
+function api.nvim_buf_delete(id, opts)
+  local buf = buffer(id)
+  opts = opts or {}
+  if buf.opts.modified and not opts.force then
+    error("Failed to unload buffer.", 2)
+  end
+  fire("BufDelete", buf.id)
+  fire("BufWipeout", buf.id)
+  for _, attached in ipairs(copy(buf.attached)) do
+    api.nvim_buf_detach(buf.id, attached)
+  end
+  if opts.unload then
+    buf.loaded = false
+    return
+  end
+  state.buffers[buf.id] = nil
+  -- Windows showing the buffer switch to another one, like `:bwipeout`
+  for _, win in pairs(state.windows) do
+    if win.buf == buf.id then
+      local other = sorted_keys(state.buffers)[1] or new_buffer(true, false)
+      win.buf = other
+      win.cursor = { 1, 0 }
+    end
+  end
+  if state.current.buf == buf.id then
+    state.current.buf = state.windows[state.current.win].buf
+  end
+end
+
+function api.nvim_buf_get_name(id)
+  return buffer(id).name
+end
+
+function api.nvim_buf_set_name(id, name)
+  local buf = buffer(id)
+  if name ~= "" and name:sub(1, 1) ~= "/" and not name:match("^%a+://") then
+    name = state.cwd .. "/" .. name
+  end
+  buf.name = name
+end
+
+function api.nvim_buf_line_count(id)
+  return #buffer(id).lines
+end
+
+function api.nvim_buf_get_changedtick(id)
+  return buffer(id).changedtick
+end
+
+function api.nvim_buf_get_lines(id, start, stop, strict)
+  local buf = buffer(id)
+  start = line_index(buf, start, strict, 2)
+  stop = line_index(buf, stop, strict, 2)
+  local lines = {}
+  for i = start + 1, stop do
+    table.insert(lines, buf.lines[i])
+  end
+  return lines
+end
+
+function api.nvim_buf_set_lines(id, start, stop, strict, replacement)
+  local buf = buffer(id)
+  if buf.opts.modifiable == false then
+    error("Buffer is not 'modifiable'", 2)
+  end
+  start = line_index(buf, start, strict, 2)
+  stop = line_index(buf, stop, strict, 2)
+  if start > stop then
+    error("'start' is higher than 'end'", 2)
+  end
+  replace_lines(buf, start, stop, replacement)
+end
+
+function api.nvim_buf_get_text(id, start_row, start_col, end_row, end_col, _)
+  local buf = buffer(id)
+  start_row = line_index(buf, start_row, true, 2)
+  end_row = line_index(buf, end_row, true, 2)
+  local lines = {}
+  for row = start_row, end_row do
+    local line = buf.lines[row + 1] or ""
+    local first = row == start_row and start_col + 1 or 1
+    local last = row == end_row and end_col or #line
+    table.insert(lines, line:sub(first, last))
+  end
+  return lines
+end
+
+function api.nvim_buf_set_text(id, start_row, start_col, end_row, end_col, replacement)
+  local buf = buffer(id)
+  start_row = line_index(buf, start_row, true, 2)
+  end_row = line_index(buf, end_row, true, 2)
+  local first = buf.lines[start_row + 1] or ""
+  local last = buf.lines[end_row + 1] or ""
+  local lines = {}
+  for i, line in ipairs(replacement) do
+    lines[i] = line
+  end
+  if #lines == 0 then
+    lines = { "" }
+  end
+  lines[1] = first:sub(1, start_col) .. lines[1]
+  lines[#lines] = lines[#lines] .. last:sub(end_col + 1)
+  replace_lines(buf, start_row, end_row + 1, lines)
+end
+
+function api.nvim_get_current_line()
+  local win = window(0)
+  return state.buffers[win.buf].lines[win.cursor[1]]
+end
+
+function api.nvim_set_current_line(line)
+  local win = window(0)
+  replace_lines(state.buffers[win.buf], win.cursor[1] - 1, win.cursor[1], { line })
+end
+
+function api.nvim_del_current_line()
+  local win = window(0)
+  replace_lines(state.buffers[win.buf], win.cursor[1] - 1, win.cursor[1], {})
+end
+
+function api.nvim_buf_attach(id, _, opts)
+  local buf = buffer(id)
+  table.insert(buf.attached, opts or {})
+  return true
+end
+
+function api.nvim_buf_detach(id, attached)
+  local buf = buffer(id)
+  for i = #buf.attached, 1, -1 do
+    if attached == nil or buf.attached[i] == attached then
+      local removed = table.remove(buf.attached, i)
+      if removed.on_detach then
+        removed.on_detach("detach", buf.id)
+      end
+    end
+  end
+  return true
+end
+
+-- Marks ----------------------------------------------------------------------------------------------------------
+
+function api.nvim_buf_set_mark(id, name, line, col, _)
+  local buf = buffer(id)
+  if name:match("^%u$") then
+    state.marks[name] = { line, col, buf.id }
+  else
+    buf.marks[name] = { line, col }
+  end
+  return true
+end
+
+function api.nvim_buf_get_mark(id, name)
+  local buf = buffer(id)
+  local mark = name:match("^%u$") and state.marks[name] or buf.marks[name]
+  if mark and (mark[3] == nil or mark[3] == buf.id) then
+    return { mark[1], mark[2] }
+  end
+  return { 0, 0 }
+end
+
+function api.nvim_buf_del_mark(id, name)
+  local buf = buffer(id)
+  if buf.marks[name] then
+    buf.marks[name] = nil
+    return true
+  end
+  return false
+end
+
+function api.nvim_get_mark(name, _)
+  local mark = state.marks[name]
+  if not mark then
+    return { 0, 0, 0, "" }
+  end
+  return { mark[1], mark[2], mark[3], state.buffers[mark[3]] and state.buffers[mark[3]].name or "" }
+end
+
+function api.nvim_del_mark(name)
+  local found = state.marks[name] ~= nil
+  state.marks[name] = nil
+  return found
+end
+
+-- Windows and tabpages -------------------------------------------------------------------------------------------
+
+function api.nvim_list_wins()
+  return sorted_keys(state.windows)
+end
+
+function api.nvim_get_current_win()
+  return state.current.win
+end
+
+function api.nvim_set_current_win(id)
+  local win = window(id)
+  if win.id ~= state.current.win then
+    fire("WinLeave", state.current.buf)
+    fire("BufLeave", state.current.buf)
+    state.current.win = win.id
+    state.current.tab = win.tab
+    state.current.buf = win.buf
+    fire("WinEnter", win.buf)
+    fire("BufEnter", win.buf)
+  end
+end
+
+function api.nvim_open_win(buf, enter, config)
+  buffer(buf)
+  if buf == 0 then
+    buf = state.current.buf
+  end
+  local id = new_window(buf, state.current.tab, copy(config or {}))
+  fire("WinNew", buf)
+  if enter then
+    api.nvim_set_current_win(id)
+  end
+  return id
+end
+
+function api.nvim_win_is_valid(id)
+  return state.windows[id] ~= nil
+end
+
+function api.nvim_win_close(id, _)
+  local win = window(id)
+  local wins = state.tabpages[win.tab].wins
+  if #wins == 1 and #sorted_keys(state.tabpages) == 1 then
+    error("Vim:E444: Cannot close last window", 2)
+  end
+  fire("WinClosed", win.buf, { pattern = tostring(win.id) })
+  for i, w in ipairs(wins) do
+    if w == win.id then
+      table.remove(wins, i)
+    end
+  end
+  state.windows[win.id] = nil
+  if #wins == 0 then
+    state.tabpages[win.tab] = nil
+  end
+  if state.current.win == win.id then
+    local tab = state.tabpages[win.tab] and win.tab or sorted_keys(state.tabpages)[1]
+    local next_win = state.tabpages[tab].wins[1]
+    state.current.win = next_win
+    state.current.tab = tab
+    state.current.buf = state.windows[next_win].buf
+  end
+end
+
+api.nvim_win_hide = api.nvim_win_close
+
+function api.nvim_win_get_buf(id)
+  return window(id).buf
+end
+
+function api.nvim_win_set_buf(id, buf)
+  local win = window(id)
+  buffer(buf)
+  if buf == 0 then
+    buf = state.current.buf
+  end
+  if win.buf ~= buf then
+    local current = win.id == state.current.win
+    if current then
+      fire("BufLeave", win.buf)
+    end
+    win.buf = buf
+    win.cursor = { 1, 0 }
+    if current then
+      state.current.buf = buf
+      fire("BufEnter", buf)
+    end
+  end
+end
+
+function api.nvim_win_get_cursor(id)
+  local cursor = window(id).cursor
+  return { cursor[1], cursor[2] }
+end
+
+function api.nvim_win_set_cursor(id, pos)
+  local win = window(id)
+  local lines = state.buffers[win.buf].lines
+  local row, col = pos[1], pos[2]
+  if row < 1 or row > #lines then
+    error("Cursor position outside buffer", 2)
+  end
+  win.cursor = { row, math.max(0, math.min(col, math.max(#lines[row] - 1, 0))) }
+end
+
+function api.nvim_win_get_width(id)
+  return window(id).width
+end
+
+function api.nvim_win_set_width(id, width)
+  window(id).width = width
+end
+
+function api.nvim_win_get_height(id)
+  return window(id).height
+end
+
+function api.nvim_win_set_height(id, height)
+  window(id).height = height
+end
+
+function api.nvim_win_get_position(id)
+  local config = window(id).config
+  if config and config.relative and config.relative ~= "" then
+    return { config.row or 0, config.col or 0 }
+  end
+  return { 0, 0 }
+end
+
+function api.nvim_win_get_number(id)
+  local win = window(id)
+  for i, w in ipairs(state.tabpages[win.tab].wins) do
+    if w == win.id then
+      return i
+    end
+  end
+end
+
+function api.nvim_win_get_tabpage(id)
+  return window(id).tab
+end
+
+function api.nvim_win_get_config(id)
+  local win = window(id)
+  local config = copy(win.config or {})
+  config.relative = config.relative or ""
+  config.width = win.width
+  config.height = win.height
+  return config
+end
+
+function api.nvim_win_set_config(id, config)
+  local win = window(id)
+  win.config = win.config or {}
+  for k, v in pairs(config) do
+    win.config[k] = v
+  end
+  win.width = config.width or win.width
+  win.height = config.height or win.height
+end
+
+function api.nvim_win_set_hl_ns(id, ns)
+  window(id).hl_ns = ns
+end
+
+function api.nvim_list_tabpages()
+  return sorted_keys(state.tabpages)
+end
+
+function api.nvim_get_current_tabpage()
+  return state.current.tab
+end
+
+function api.nvim_set_current_tabpage(id)
+  local tab = tabpage(id)
+  api.nvim_set_current_win(tab.current or tab.wins[1])
+end
+
+function api.nvim_tabpage_is_valid(id)
+  return state.tabpages[id] ~= nil
+end
+
+function api.nvim_tabpage_list_wins(id)
+  return copy(tabpage(id).wins)
+end
+
+function api.nvim_tabpage_get_win(id)
+  local tab = tabpage(id)
+  if tab.id == state.current.tab then
+    return state.current.win
+  end
+  return tab.wins[1]
+end
+
+function api.nvim_tabpage_get_number(id)
+  local tab = tabpage(id)
+  for i, t in ipairs(sorted_keys(state.tabpages)) do
+    if t == tab.id then
+      return i
+    end
+  end
+end
+
+-- Variables ------------------------------------------------------------------------------------------------------
+
+local function get_var(vars, name)
+  local value = vars[name]
+  if value == nil then
+    error("Key not found: " .. name, 3)
+  end
+  return value
+end
+
+local function del_var(vars, name)
+  if vars[name] == nil then
+    error("Key not found: " .. name, 3)
+  end
+  vars[name] = nil
+end
+
+function api.nvim_get_var(name)
+  return get_var(state.g, name)
+end
+
+function api.nvim_set_var(name, value)
+  state.g[name] = value
+end
+
+function api.nvim_del_var(name)
+  del_var(state.g, name)
+end
+
+function api.nvim_buf_get_var(id, name)
+  return get_var(buffer(id).vars, name)
+end
+
+function api.nvim_buf_set_var(id, name, value)
+  buffer(id).vars[name] = value
+end
+
+function api.nvim_buf_del_var(id, name)
+  del_var(buffer(id).vars, name)
+end
+
+function api.nvim_win_get_var(id, name)
+  return get_var(window(id).vars, name)
+end
+
+function api.nvim_win_set_var(id, name, value)
+  window(id).vars[name] = value
+end
+
+function api.nvim_win_del_var(id, name)
+  del_var(window(id).vars, name)
+end
+
+function api.nvim_tabpage_get_var(id, name)
+  return get_var(tabpage(id).vars, name)
+end
+
+function api.nvim_tabpage_set_var(id, name, value)
+  tabpage(id).vars[name] = value
+end
+
+function api.nvim_tabpage_del_var(id, name)
+  del_var(tabpage(id).vars, name)
+end
+
+-- A table like `vim.b`, where fields are variables of the current object and indexes select an object
+local function scoped_vars(lookup)
+  return setmetatable({}, {
+    __index = function(_, key)
+      if type(key) == "number" then
+        return lookup(key).vars
+      end
+      return lookup(0).vars[key]
+    end,
+    __newindex = function(_, key, value)
+      lookup(0).vars[key] = value
+    end,
+  })
+end
+
+vim.g = state.g
+vim.b = scoped_vars(buffer)
+vim.w = scoped_vars(window)
+vim.t = scoped_vars(tabpage)
+vim.v = {
+  count = 0,
+  count1 = 1,
+  register = '"',
+  operator = "",
+  shell_error = 0,
+  hlsearch = 0,
+  progpath = "nvim",
+}
+
+-- Options --------------------------------------------------------------------------------------------------------
+
+local function option_value(name, opts, level)
+  name = option_name(name)
+  local def = option_defs[name]
+  opts = opts or {}
+  local scope = def[3]
+  if opts.buf and opts.buf ~= vim.NIL then
+    if scope ~= "buf" then
+      error("Option '" .. name .. "' is not buffer-local", level + 1)
+    end
+    return name, buffer(opts.buf, level).opts
+  elseif opts.win and opts.win ~= vim.NIL then
+    if scope ~= "win" then
+      error("Option '" .. name .. "' is not window-local", level + 1)
+    end
+    return name, window(opts.win, level).opts
+  elseif opts.scope == "global" or scope == "global" then
+    return name, state.options
+  elseif scope == "buf" then
+    return name, buffer(0, level).opts
+  else
+    return name, window(0, level).opts
+  end
+end
+
+local function get_option(name, opts)
+  local values
+  name, values = option_value(name, opts, 2)
+  local value = values[name]
+  if value == nil then
+    value = state.options[name]
+  end
+  return value
+end
+
+local function set_option(name, value, opts)
+  local values
+  name, values = option_value(name, opts, 2)
+  local def = option_defs[name]
+  if type(value) ~= def[2] then
+    error("Invalid value for option '" .. name .. "': expected " .. def[2] .. ", got " .. type(value), 3)
+  end
+  local old = get_option(name, opts)
+  values[name] = value
+  -- Setting a local value without a scope also sets the global value, like `:set`
+  if opts == nil or (opts.scope == nil and opts.buf == nil and opts.win == nil) then
+    state.options[name] = value
+  end
+  exec_autocmds("OptionSet", { pattern = name, data = { old = old, new = value } })
+end
+
+function api.nvim_get_option_value(name, opts)
+  return get_option(name, opts)
+end
+
+function api.nvim_set_option_value(name, value, opts)
+  set_option(name, value, opts)
+end
+
+function api.nvim_get_option_info2(name, _)
+  local full = option_name(name)
+  local def = option_defs[full]
+  return {
+    name = full,
+    shortname = def[1],
+    type = def[2],
+    scope = def[3],
+    global_local = false,
+    commalist = def[5] == true,
+    flaglist = def[6] == true,
+    was_set = state.options[full] ~= def[4],
+    default = def[4],
+    allows_duplicates = false,
+    last_set_sid = 0,
+    last_set_linenr = 0,
+    last_set_chan = 0,
+  }
+end
+
+function api.nvim_get_option_info(name)
+  return api.nvim_get_option_info2(name, {})
+end
+
+-- A table like `vim.bo`, where fields are options and indexes select a buffer or window
+local function scoped_options(key_name)
+  local function proxy(opts)
+    return setmetatable({}, {
+      __index = function(_, name)
+        if type(name) == "number" and key_name then
+          return proxy({ [key_name] = name })
+        end
+        return get_option(name, opts)
+      end,
+      __newindex = function(_, name, value)
+        set_option(name, value, opts)
+      end,
+    })
+  end
+  return proxy
+end
+
+vim.o = scoped_options(nil)(nil)
+vim.go = scoped_options(nil)({ scope = "global" })
+vim.bo = scoped_options("buf")({ buf = 0 })
+vim.wo = scoped_options("win")({ win = 0 })
+
+-- Keymaps --------------------------------------------------------------------------------------------------------
+
+local function flag(value)
+  return value and 1 or 0
+end
+
+local function keymap_set(modes, lhs, rhs, opts, buf)
+  opts = opts or {}
+  for _, mode in ipairs(list(modes)) do
+    if mode == "" then
+      mode = " "
+    end
+    local maps = buf and buffer(buf).keymaps or state.keymaps
+    if opts.unique and maps[mode .. lhs] then
+      error("E227: mapping already exists for " .. lhs, 3)
+    end
+    maps[mode .. lhs] = {
+      lhs = lhs,
+      lhsraw = lhs,
+      rhs = type(rhs) == "string" and rhs or nil,
+      callback = type(rhs) == "function" and rhs or opts.callback,
+      mode = mode,
+      buffer = buf and buffer(buf).id or 0,
+      silent = flag(opts.silent),
+      noremap = flag(opts.noremap ~= false and not opts.remap),
+      expr = flag(opts.expr),
+      nowait = flag(opts.nowait),
+      script = flag(opts.script),
+      replace_keycodes = flag(opts.replace_keycodes),
+      desc = opts.desc,
+      lnum = 0,
+      sid = -8,
+    }
+  end
+end
+
+local function keymap_del(modes, lhs, buf)
+  for _, mode in ipairs(list(modes)) do
+    if mode == "" then
+      mode = " "
+    end
+    local maps = buf and buffer(buf).keymaps or state.keymaps
+    if not maps[mode .. lhs] then
+      error("E31: No such mapping", 3)
+    end
+    maps[mode .. lhs] = nil
+  end
+end
+
+local function keymap_get(maps, mode)
+  local result = {}
+  for _, key in ipairs(sorted_keys(maps)) do
+    local map = maps[key]
+    if map.mode == mode or (mode == "" and map.mode == " ") then
+      table.insert(result, copy(map))
+    end
+  end
+  return result
+end
+
+local function keymap_buffer(opts)
+  local buf = opts and opts.buffer
+  if buf == true then
+    return 0
+  elseif buf == false then
+    return nil
+  end
+  return buf
+end
+
+vim.keymap = {}
+
+function vim.keymap.set(modes, lhs, rhs, opts)
+  opts = opts and copy(opts) or {}
+  local buf = keymap_buffer(opts)
+  opts.buffer = nil
+  if opts.expr and opts.replace_keycodes == nil then
+    opts.replace_keycodes = true
+  end
+  keymap_set(modes, lhs, rhs, opts, buf)
+end
+
+function vim.keymap.del(modes, lhs, opts)
+  keymap_del(modes, lhs, keymap_buffer(opts))
+end
+
+function api.nvim_set_keymap(mode, lhs, rhs, opts)
+  keymap_set(mode, lhs, rhs, opts)
+end
+
+function api.nvim_del_keymap(mode, lhs)
+  keymap_del(mode, lhs)
+end
+
+function api.nvim_buf_set_keymap(id, mode, lhs, rhs, opts)
+  keymap_set(mode, lhs, rhs, opts, id)
+end
+
+function api.nvim_buf_del_keymap(id, mode, lhs)
+  keymap_del(mode, lhs, id)
+end
+
+function api.nvim_get_keymap(mode)
+  return keymap_get(state.keymaps, mode)
+end
+
+function api.nvim_buf_get_keymap(id, mode)
+  return keymap_get(buffer(id).keymaps, mode)
+end
+
+local function find_mapping(mode, keys)
+  local short = mode:sub(1, 1)
+  local candidates = { short }
+  if short == "n" or short == "v" or short == "o" then
+    table.insert(candidates, " ")
+  end
+  if short == "v" or short == "V" or short == "\22" then
+    candidates = { "x", "v", " " }
+  end
+  for _, m in ipairs(candidates) do
+    local map = buffer(0).keymaps[m .. keys] or state.keymaps[m .. keys]
+    if map then
+      return map
+    end
+  end
+end
+
+-- Runs mappings that match the keys exactly, other keys only end up in the log
+function api.nvim_feedkeys(keys, _, _)
+  local map = find_mapping(state.mode, keys)
+  if not map then
+    table.insert(state.ex_commands, "normal " .. keys)
+    return
+  end
+  if map.expr == 1 then
+    local result = map.callback and map.callback() or keys
+    if result and result ~= "" and result ~= keys then
+      api.nvim_feedkeys(result, "m", false)
+    end
+  elseif map.callback then
+    map.callback()
+  elseif map.rhs and map.rhs ~= keys then
+    api.nvim_feedkeys(map.rhs, "m", false)
+  end
+end
+
+function api.nvim_replace_termcodes(str, _, _, _)
+  return str
+end
+
+-- User commands and Ex commands ----------------------------------------------------------------------------------
+
+local function create_command(commands, name, command, opts)
+  if not name:match("^%u[%w]*$") then
+    error("'name' must begin with an uppercase letter", 3)
+  end
+  commands[name] = { name = name, command = command, opts = opts or {} }
+end
+
+function api.nvim_create_user_command(name, command, opts)
+  create_command(state.commands, name, command, opts)
+end
+
+function api.nvim_del_user_command(name)
+  if not state.commands[name] then
+    error("Invalid command (not found): " .. name, 2)
+  end
+  state.commands[name] = nil
+end
+
+function api.nvim_buf_create_user_command(id, name, command, opts)
+  create_command(buffer(id).commands, name, command, opts)
+end
+
+function api.nvim_buf_del_user_command(id, name)
+  local commands = buffer(id).commands
+  if not commands[name] then
+    error("Invalid command (not found): " .. name, 2)
+  end
+  commands[name] = nil
+end
+
+function api.nvim_get_commands(_)
+  local result = {}
+  for name, cmd in pairs(state.commands) do
+    result[name] = {
+      name = name,
+      definition = type(cmd.command) == "string" and cmd.command or "",
+      nargs = tostring(cmd.opts.nargs or 0),
+      bang = cmd.opts.bang or false,
+    }
+  end
+  return result
+end
+
+local function run_ex(line)
+  line = line:gsub("^%s+", ""):gsub("%s+$", "")
+  if line == "" then
+    return
+  end
+  local name, bang, args = line:match("^(%u[%w]*)(!?)%s*(.*)$")
+  local cmd = name and (buffer(0).commands[name] or state.commands[name])
+  if not cmd then
+    table.insert(state.ex_commands, line)
+    return
+  end
+  if type(cmd.command) == "string" then
+    return run_ex(cmd.command)
+  end
+  local fargs = {}
+  for arg in args:gmatch("%S+") do
+    table.insert(fargs, arg)
+  end
+  local row = window(0).cursor[1]
+  cmd.command({
+    name = name,
+    args = args,
+    fargs = fargs,
+    bang = bang == "!",
+    line1 = row,
+    line2 = row,
+    range = 0,
+    count = -1,
+    reg = "",
+    mods = "",
+    smods = {},
+  })
+end
+
+vim.cmd = setmetatable({}, {
+  __call = function(_, command)
+    if type(command) == "table" then
+      command = command.cmd .. (command.bang and "!" or "") .. " " .. table.concat(command.args or {}, " ")
+    end
+    for line in (command .. "\n"):gmatch("(.-)\n") do
+      run_ex(line)
+    end
+  end,
+  __index = function(_, name)
+    return function(...)
+      vim.cmd(name .. " " .. table.concat({ ... }, " "))
+    end
+  end,
+})
+
+function api.nvim_exec(src, output)
+  vim.cmd(src)
+  return output and "" or nil
+end
+
+function api.nvim_exec2(src, opts)
+  vim.cmd(src)
+  return { output = (opts and opts.output) and "" or nil }
+end
+
+function api.nvim_command(command)
+  vim.cmd(command)
+end
+
+function api.nvim_exec_lua(code, args)
+  local chunk = assert(loadstring(code))
+  return chunk(unpack(args or {}))
+end
+
+-- Namespaces, extmarks and highlights ----------------------------------------------------------------------------
+
+function api.nvim_create_namespace(name)
+  if name ~= "" and state.namespaces[name] then
+    return state.namespaces[name]
+  end
+  local id = state.next_ns
+  state.next_ns = id + 1
+  if name ~= "" then
+    state.namespaces[name] = id
+  end
+  return id
+end
+
+function api.nvim_get_namespaces()
+  return copy(state.namespaces)
+end
+
+local function extmark_pos(pos, default)
+  if pos == 0 then
+    return { 0, 0 }
+  elseif pos == -1 or pos == nil then
+    return default
+  elseif type(pos) == "table" then
+    return pos
+  end
+  return pos
+end
+
+function api.nvim_buf_set_extmark(id, ns, row, col, opts)
+  local buf = buffer(id)
+  if row < 0 or row >= #buf.lines then
+    error("Invalid 'line': out of range", 2)
+  end
+  if col < 0 or col > #buf.lines[row + 1] then
+    error("Invalid 'col': out of range", 2)
+  end
+  opts = copy(opts or {})
+  buf.extmarks[ns] = buf.extmarks[ns] or {}
+  local marks = buf.extmarks[ns]
+  local mark_id = opts.id
+  if not mark_id then
+    mark_id = 1
+    while marks[mark_id] do
+      mark_id = mark_id + 1
+    end
+  end
+  opts.id = nil
+  opts.ns_id = ns
+  marks[mark_id] = { row = row, col = col, details = opts }
+  return mark_id
+end
+
+function api.nvim_buf_get_extmark_by_id(id, ns, mark_id, opts)
+  local marks = buffer(id).extmarks[ns] or {}
+  local mark = marks[mark_id]
+  if not mark then
+    return {}
+  end
+  if opts and opts.details then
+    return { mark.row, mark.col, copy(mark.details) }
+  end
+  return { mark.row, mark.col }
+end
+
+function api.nvim_buf_get_extmarks(id, ns, start, stop, opts)
+  local buf = buffer(id)
+  opts = opts or {}
+  start = extmark_pos(start, { 0, 0 })
+  stop = extmark_pos(stop, { math.huge, math.huge })
+  local result = {}
+  local namespaces = ns == -1 and sorted_keys(buf.extmarks) or { ns }
+  for _, n in ipairs(namespaces) do
+    for _, mark_id in ipairs(sorted_keys(buf.extmarks[n] or {})) do
+      local mark = buf.extmarks[n][mark_id]
+      local after_start = mark.row > start[1] or (mark.row == start[1] and mark.col >= start[2])
+      local before_stop = mark.row < stop[1] or (mark.row == stop[1] and mark.col <= stop[2])
+      if after_start and before_stop then
+        local item = { mark_id, mark.row, mark.col }
+        if opts.details then
+          item[4] = copy(mark.details)
+        end
+        table.insert(result, item)
+      end
+    end
+  end
+  table.sort(result, function(a, b)
+    return a[2] < b[2] or (a[2] == b[2] and (a[3] < b[3] or (a[3] == b[3] and a[1] < b[1])))
+  end)
+  if opts.limit then
+    while #result > opts.limit do
+      table.remove(result)
+    end
+  end
+  return result
+end
+
+function api.nvim_buf_del_extmark(id, ns, mark_id)
+  local marks = buffer(id).extmarks[ns] or {}
+  local found = marks[mark_id] ~= nil
+  marks[mark_id] = nil
+  return found
+end
+
+function api.nvim_buf_clear_namespace(id, ns, line_start, line_end)
+  local buf = buffer(id)
+  if line_end == nil or line_end < 0 then
+    line_end = math.huge
+  end
+  local namespaces = ns == -1 and sorted_keys(buf.extmarks) or { ns }
+  for _, n in ipairs(namespaces) do
+    for mark_id, mark in pairs(buf.extmarks[n] or {}) do
+      if mark.row >= line_start and mark.row < line_end then
+        buf.extmarks[n][mark_id] = nil
+      end
+    end
+  end
+end
+
+function api.nvim_buf_add_highlight(id, ns, hl_group, line, col_start, col_end)
+  if ns == 0 or ns == -1 then
+    ns = api.nvim_create_namespace("")
+  end
+  local buf = buffer(id)
+  if col_end < 0 then
+    col_end = #buf.lines[line + 1]
+  end
+  api.nvim_buf_set_extmark(id, ns, line, col_start, { end_col = col_end, hl_group = hl_group })
+  return ns
+end
+
+function api.nvim_get_hl_id_by_name(name)
+  local id = state.hl_ids[name]
+  if not id then
+    id = state.next_hl
+    state.next_hl = id + 1
+    state.hl_ids[name] = id
+  end
+  return id
+end
+
+function api.nvim_set_hl(ns, name, val)
+  api.nvim_get_hl_id_by_name(name)
+  state.highlights[ns] = state.highlights[ns] or {}
+  state.highlights[ns][name] = copy(val)
+end
+
+local function hl_name(id)
+  for name, hl_id in pairs(state.hl_ids) do
+    if hl_id == id then
+      return name
+    end
+  end
+end
+
+function api.nvim_get_hl(ns, opts)
+  opts = opts or {}
+  local groups = state.highlights[ns] or {}
+  local name = opts.name or (opts.id and hl_name(opts.id))
+  if name then
+    local val = groups[name]
+    if not val then
+      return {}
+    end
+    if opts.link == false then
+      while val.link and groups[val.link] do
+        val = groups[val.link]
+      end
+    end
+    return copy(val)
+  end
+  local result = {}
+  for group, val in pairs(groups) do
+    result[group] = copy(val)
+  end
+  return result
+end
+
+function api.nvim_get_hl_by_name(name, _)
+  if not state.hl_ids[name] then
+    error("Invalid highlight name: '" .. name .. "'", 2)
+  end
+  return api.nvim_get_hl(0, { name = name, link = false })
+end
+
+function api.nvim_get_hl_by_id(id, rgb)
+  local name = hl_name(id)
+  if not name then
+    error("Invalid highlight id: " .. tostring(id), 2)
+  end
+  return api.nvim_get_hl_by_name(name, rgb)
+end
+
+function api.nvim_set_hl_ns(ns)
+  state.hl_ns = ns
+end
+
+local colors = {
+  Black = 0x000000,
+  Blue = 0x0000ff,
+  Cyan = 0x00ffff,
+  Gray = 0xbebebe,
+  Green = 0x00ff00,
+  Magenta = 0xff00ff,
+  Red = 0xff0000,
+  White = 0xffffff,
+  Yellow = 0xffff00,
+}
+
+function api.nvim_get_color_map()
+  return copy(colors)
+end
+
+function api.nvim_get_color_by_name(name)
+  local hex = name:match("^#(%x%x%x%x%x%x)$")
+  if hex then
+    return tonumber(hex, 16)
+  end
+  for color, value in pairs(colors) do
+    if color:lower() == name:lower() then
+      return value
+    end
+  end
+  return -1
+end
+
+-- Messages -------------------------------------------------------------------------------------------------------
+
+vim.log = { levels = { TRACE = 0, DEBUG = 1, INFO = 2, WARN = 3, ERROR = 4, OFF = 5 } }
+
+function vim.notify(msg, level, _)
+  table.insert(state.notifications, { message = msg, level = level or vim.log.levels.INFO })
+end
+
+local notified = {}
+function vim.notify_once(msg, level, opts)
+  if notified[msg] then
+    return false
+  end
+  notified[msg] = true
+  vim.notify(msg, level, opts)
+  return true
+end
+
+function api.nvim_echo(chunks, _, _)
+  local parts = {}
+  for _, chunk in ipairs(chunks) do
+    table.insert(parts, chunk[1])
+  end
+  table.insert(state.messages, table.concat(parts))
+end
+
+function api.nvim_err_write(msg)
+  table.insert(state.messages, msg)
+end
+
+function api.nvim_err_writeln(msg)
+  table.insert(state.messages, msg)
+end
+
+function api.nvim_out_write(msg)
+  table.insert(state.messages, msg)
+end
+
+function api.nvim_get_mode()
+  return { mode = state.mode, blocking = false }
+end
+
+local function health(kind)
+  return function(msg, ...)
+    table.insert(state.health, { kind = kind, message = msg, advice = ... })
+  end
+end
+
+vim.health = {
+  start = health("start"),
+  ok = health("ok"),
+  info = health("info"),
+  warn = health("warn"),
+  error = health("error"),
+}
+
+-- vim.fn ---------------------------------------------------------------------------------------------------------
+
+local function cursor_pos()
+  local cursor = window(0).cursor
+  return cursor[1], cursor[2]
+end
+
+-- Resolves the position expressions used by `line()`, `getpos()` and `setpos()` to a 1-based line and 0-based col
+local function position(expr)
+  if expr == "." then
+    return cursor_pos()
+  elseif expr == "$" then
+    return #buffer(0).lines, 0
+  elseif expr == "v" then
+    if state.visual_start and state.mode:match("^[vV\22sS\19]") then
+      return state.visual_start[1], state.visual_start[2]
+    end
+    return cursor_pos()
+  elseif expr:sub(1, 1) == "'" then
+    local mark = api.nvim_buf_get_mark(0, expr:sub(2))
+    return mark[1], mark[2]
+  end
+  return 0, 0
+end
+
+function fn.line(expr)
+  local line = position(expr)
+  return line
+end
+
+function fn.col(expr)
+  local _, col = position(expr)
+  return col + 1
+end
+
+function fn.getpos(expr)
+  local line, col = position(expr)
+  if line == 0 then
+    return { 0, 0, 0, 0 }
+  end
+  return { 0, line, col + 1, 0 }
+end
+
+function fn.setpos(expr, pos)
+  local line, col = pos[2], math.max(pos[3] - 1, 0)
+  if expr == "." then
+    api.nvim_win_set_cursor(0, { line, col })
+  elseif expr:sub(1, 1) == "'" then
+    api.nvim_buf_set_mark(0, expr:sub(2), line, col, {})
+  else
+    return -1
+  end
+  return 0
+end
+
+function fn.getline(lnum, stop)
+  local buf = buffer(0)
+  if type(lnum) == "string" then
+    lnum = fn.line(lnum)
+  end
+  if stop == nil then
+    return buf.lines[lnum] or ""
+  end
+  if type(stop) == "string" then
+    stop = fn.line(stop)
+  end
+  local lines = {}
+  for i = lnum, stop do
+    if buf.lines[i] then
+      table.insert(lines, buf.lines[i])
+    end
+  end
+  return lines
+end
+
+function fn.setline(lnum, text)
+  local buf = buffer(0)
+  if type(lnum) == "string" then
+    lnum = fn.line(lnum)
+  end
+  if lnum < 1 or lnum > #buf.lines + 1 then
+    return 1
+  end
+  local lines = type(text) == "table" and text or { text }
+  for i, line in ipairs(lines) do
+    local row = lnum + i - 1
+    if row <= #buf.lines then
+      replace_lines(buf, row - 1, row, { line })
+    else
+      replace_lines(buf, row - 1, row - 1, { line })
+    end
+  end
+  return 0
+end
+
+function fn.indent(lnum)
+  local line = buffer(0).lines[lnum]
+  if not line then
+    return -1
+  end
+  local tabstop = get_option("tabstop")
+  local width = 0
+  for char in line:gmatch(".") do
+    if char == " " then
+      width = width + 1
+    elseif char == "\t" then
+      width = width + tabstop - width % tabstop
+    else
+      break
+    end
+  end
+  return width
+end
+
+function fn.shiftwidth()
+  local sw = get_option("shiftwidth")
+  return sw > 0 and sw or get_option("tabstop")
+end
+
+function fn.mode(_)
+  return state.mode
+end
+
+function fn.visualmode(_)
+  return state.visualmode
+end
+
+function fn.winsaveview()
+  local row, col = cursor_pos()
+  return { lnum = row, col = col, curswant = state.curswant or col, topline = 1, leftcol = 0, skipcol = 0 }
+end
+
+function fn.foldclosed(_)
+  return -1
+end
+
+function fn.foldclosedend(_)
+  return -1
+end
+
+function fn.bufnr(expr)
+  if expr == nil or expr == "%" or expr == "" then
+    return state.current.buf
+  end
+  for id, buf in pairs(state.buffers) do
+    if buf.name == expr then
+      return id
+    end
+  end
+  return -1
+end
+
+function fn.getcwd(_, _)
+  return state.cwd
+end
+
+function fn.stdpath(what)
+  local root = (os.getenv("TMPDIR") or "/tmp") .. "/nvim-mock"
+  if what == "config_dirs" or what == "data_dirs" then
+    return {}
+  end
+  return root .. "/" .. what
+end
+
+function fn.tmpname()
+  return os.tmpname()
+end
+
+function fn.has(feature)
+  if feature == "nvim" or feature:match("^nvim%-0%.") then
+    local minor = tonumber(feature:match("^nvim%-0%.(%d+)") or 0)
+    return minor <= 10 and 1 or 0
+  end
+  return 0
+end
+
+function fn.exists(expr)
+  local kind, name = expr:sub(1, 1), expr:sub(2)
+  if kind == "+" or kind == "&" then
+    return (option_defs[name] or option_aliases[name]) and 1 or 0
+  elseif kind == ":" then
+    return (state.commands[name] or buffer(0).commands[name]) and 2 or 0
+  elseif kind == "*" then
+    return fn[name] and 1 or 0
+  elseif expr:match("^g:") then
+    return state.g[expr:sub(3)] ~= nil and 1 or 0
+  elseif expr:match("^b:") then
+    return buffer(0).vars[expr:sub(3)] ~= nil and 1 or 0
+  elseif expr:match("^w:") then
+    return window(0).vars[expr:sub(3)] ~= nil and 1 or 0
+  elseif expr:match("^t:") then
+    return tabpage(0).vars[expr:sub(3)] ~= nil and 1 or 0
+  end
+  return 0
+end
+
+function fn.expand(expr)
+  if expr == "%" or expr == "%:p" then
+    return buffer(0).name
+  end
+  return expr
+end
+
+-- Every character is one cell wide, except for tabs
+function fn.strdisplaywidth(str, col)
+  col = col or 0
+  local tabstop = get_option("tabstop")
+  local width = 0
+  for char in str:gmatch("[^\128-\191][\128-\191]*") do
+    if char == "\t" then
+      width = width + tabstop - (col + width) % tabstop
+    else
+      width = width + 1
+    end
+  end
+  return width
+end
+
+function fn.strchars(str)
+  local _, count = str:gsub("[^\128-\191]", "")
+  return count
+end
+
+-- vim.uv ---------------------------------------------------------------------------------------------------------
+
+local handle_methods = {}
+local handle_meta = { __index = handle_methods }
+
+local function new_handle(kind, fields)
+  local handle = fields or {}
+  handle.kind = kind
+  handle.active = false
+  handle.closing = false
+  return setmetatable(handle, handle_meta)
+end
+
+function uv.now()
+  return state.now
+end
+
+function uv.hrtime()
+  return state.now * 1000000
+end
+
+function uv.update_time() end
+
+function uv.is_active(handle)
+  return handle.active
+end
+
+function uv.is_closing(handle)
+  return handle.closing
+end
+
+function uv.close(handle, callback)
+  handle.active = false
+  handle.closing = true
+  if callback then
+    vim.schedule(callback)
+  end
+end
+
+function uv.ref(_) end
+
+function uv.unref(_) end
+
+function uv.has_ref(_)
+  return true
+end
+
+function uv.new_timer()
+  return new_handle("timer", { due = 0, ["repeat"] = 0 })
+end
+
+function uv.timer_start(timer, timeout, repeat_ms, callback)
+  if timer.closing then
+    return nil, "EINVAL: invalid argument"
+  end
+  timer.due = state.now + timeout
+  timer["repeat"] = repeat_ms
+  timer.callback = callback
+  timer.active = true
+  state.timers[timer] = true
+  return 0
+end
+
+function uv.timer_stop(timer)
+  timer.active = false
+  state.timers[timer] = nil
+  return 0
+end
+
+function uv.timer_again(timer)
+  if not timer.callback then
+    return nil, "EINVAL: invalid argument"
+  end
+  if timer["repeat"] > 0 then
+    timer.due = state.now + timer["repeat"]
+    timer.active = true
+    state.timers[timer] = true
+  end
+  return 0
+end
+
+function uv.timer_set_repeat(timer, repeat_ms)
+  timer["repeat"] = repeat_ms
+end
+
+function uv.timer_get_repeat(timer)
+  return timer["repeat"]
+end
+
+function uv.timer_get_due_in(timer)
+  if not timer.active then
+    return 0
+  end
+  return math.max(timer.due - state.now, 0)
+end
+
+function uv.new_check()
+  return new_handle("check")
+end
+
+function uv.check_start(check, callback)
+  check.callback = callback
+  check.active = true
+  state.checks[check] = true
+  return 0
+end
+
+function uv.check_stop(check)
+  check.active = false
+  state.checks[check] = nil
+  return 0
+end
+
+function uv.new_async(callback)
+  return new_handle("async", { callback = callback, pending = false })
+end
+
+function uv.async_send(async)
+  if async.closing then
+    return nil, "EINVAL: invalid argument"
+  end
+  async.pending = true
+  state.asyncs[async] = true
+  return 0
+end
+
+-- Watchers can be started, but never see any changes
+local function watcher(kind)
+  uv["new_" .. kind] = function()
+    return new_handle(kind)
+  end
+  uv[kind .. "_start"] = function(handle, ...)
+    handle.active = true
+    handle.args = { ... }
+    return 0
+  end
+  uv[kind .. "_stop"] = function(handle)
+    handle.active = false
+    return 0
+  end
+end
+watcher("fs_event")
+watcher("fs_poll")
+
+function uv.new_pipe(_)
+  return new_handle("pipe")
+end
+
+-- Records the arguments, so tests can check what would have been spawned
+function uv.spawn(file, options, _)
+  table.insert(state.spawned, { file = file, options = options })
+  return nil, "ENOSYS: processes can't be spawned in the mock"
+end
+
+for name, f in pairs(uv) do
+  local method = name:match("^timer_(.+)$") or name:match("^check_(.+)$") or name:match("^async_(.+)$")
+  handle_methods[method or name] = f
+end
+
+-- Runs everything that's ready at the current time: async handles, scheduled callbacks and check handles
+function state.tick()
+  for _ = 1, 1000 do
+    local did_work = false
+    for async in pairs(state.asyncs) do
+      state.asyncs[async] = nil
+      if async.pending and not async.closing then
+        async.pending = false
+        did_work = true
+        async.callback()
+      end
+    end
+    local scheduled = state.scheduled
+    state.scheduled = {}
+    for _, callback in ipairs(scheduled) do
+      did_work = true
+      callback()
+    end
+    if did_work then
+      for check in pairs(copy(state.checks)) do
+        if check.active then
+          check.callback()
+        end
+      end
+    else
+      return
+    end
+  end
+  error("scheduled callbacks keep scheduling more callbacks", 2)
+end
+
+local function next_timer(deadline)
+  local next
+  for timer in pairs(state.timers) do
+    if timer.active and timer.due <= deadline and (next == nil or timer.due < next.due) then
+      next = timer
+    end
+  end
+  return next
+end
+
+-- Moves the virtual clock forward, firing timers in order
+function state.advance(ms)
+  local deadline = state.now + ms
+  state.tick()
+  while true do
+    local timer = next_timer(deadline)
+    if not timer then
+      break
+    end
+    state.now = math.max(state.now, timer.due)
+    if timer["repeat"] > 0 then
+      timer.due = timer.due + timer["repeat"]
+    else
+      timer.active = false
+      state.timers[timer] = nil
+    end
+    timer.callback()
+    state.tick()
+  end
+  state.now = deadline
+end
+
+vim.uv = uv
+vim.loop = uv
+
+function vim.schedule(callback)
+  table.insert(state.scheduled, callback)
+end
+
+function vim.schedule_wrap(callback)
+  return function(...)
+    local args = { n = select("#", ...), ... }
+    vim.schedule(function()
+      callback(unpack(args, 1, args.n))
+    end)
+  end
+end
+
+function vim.defer_fn(callback, timeout)
+  local timer = uv.new_timer()
+  uv.timer_start(timer, timeout, 0, function()
+    uv.close(timer)
+    vim.schedule(callback)
+  end)
+  return timer
+end
+
+function vim.in_fast_event()
+  return false
+end
+
+-- Advances the virtual clock until the condition is true
+function vim.wait(timeout, condition, interval, _)
+  interval = math.max(interval or 200, 1)
+  local waited = 0
+  state.tick()
+  while true do
+    if condition == nil or condition() then
+      if condition ~= nil then
+        return true
+      end
+    end
+    if waited >= timeout then
+      return false, -1
+    end
+    local step = math.min(interval, timeout - waited)
+    state.advance(step)
+    waited = waited + step
+  end
+end
+
+-- Utilities ------------------------------------------------------------------------------------------------------
+
+vim.NIL = setmetatable({}, {
+  __tostring = function()
+    return "vim.NIL"
+  end,
+})
+
+function vim.empty_dict()
+  return {}
+end
+
+function vim.version()
+  return { major = 0, minor = 10, patch = 0, api_level = 12, api_compatible = 0, prerelease = false }
+end
+
+function vim.inspect(value, _)
+  local seen = {}
+  local function inspect(v, indent)
+    if type(v) == "string" then
+      return string.format("%q", v)
+    elseif type(v) ~= "table" then
+      return tostring(v)
+    elseif seen[v] then
+      return "<cycle>"
+    end
+    seen[v] = true
+    local keys = {}
+    for k in pairs(v) do
+      table.insert(keys, k)
+    end
+    if #keys == 0 then
+      return "{}"
+    end
+    table.sort(keys, function(a, b)
+      if type(a) == type(b) and (type(a) == "number" or type(a) == "string") then
+        return a < b
+      end
+      return type(a) < type(b)
+    end)
+    local parts = {}
+    local inner = indent .. "  "
+    for _, k in ipairs(keys) do
+      local key
+      if type(k) == "number" and k >= 1 and k <= #v then
+        key = ""
+      elseif type(k) == "string" and k:match("^[%a_][%w_]*$") then
+        key = k .. " = "
+      else
+        key = "[" .. inspect(k, inner) .. "] = "
+      end
+      table.insert(parts, inner .. key .. inspect(v[k], inner))
+    end
+    seen[v] = nil
+    return "{\n" .. table.concat(parts, ",\n") .. "\n" .. indent .. "}"
+  end
+  return inspect(value, "")
+end
+
+function vim.deepcopy(value)
+  if type(value) ~= "table" then
+    return value
+  end
+  local new = {}
+  for k, v in pairs(value) do
+    new[vim.deepcopy(k)] = vim.deepcopy(v)
+  end
+  return setmetatable(new, getmetatable(value))
+end
+
+function vim.split(s, sep, opts)
+  local plain = opts and opts.plain
+  local result = {}
+  local start = 1
+  while true do
+    local first, last = s:find(sep, start, plain)
+    if not first or last < first then
+      break
+    end
+    table.insert(result, s:sub(start, first - 1))
+    start = last + 1
+  end
+  table.insert(result, s:sub(start))
+  if opts and opts.trimempty then
+    while result[1] == "" do
+      table.remove(result, 1)
+    end
+    while result[#result] == "" do
+      table.remove(result)
+    end
+  end
+  return result
+end
+
+function vim.trim(s)
+  return (s:gsub("^%s+", ""):gsub("%s+$", ""))
+end
+
+function vim.startswith(s, prefix)
+  return s:sub(1, #prefix) == prefix
+end
+
+function vim.endswith(s, suffix)
+  return suffix == "" or s:sub(-#suffix) == suffix
+end
+
+function vim.tbl_keys(t)
+  local keys = {}
+  for k in pairs(t) do
+    table.insert(keys, k)
+  end
+  return keys
+end
+
+function vim.tbl_values(t)
+  local values = {}
+  for _, v in pairs(t) do
+    table.insert(values, v)
+  end
+  return values
+end
+
+function vim.tbl_contains(t, value)
+  for _, v in pairs(t) do
+    if v == value then
+      return true
+    end
+  end
+  return false
+end
+
+function vim.tbl_isempty(t)
+  return next(t) == nil
+end
+
+function vim.tbl_count(t)
+  local count = 0
+  for _ in pairs(t) do
+    count = count + 1
+  end
+  return count
+end
+
+function vim.tbl_map(f, t)
+  local result = {}
+  for k, v in pairs(t) do
+    result[k] = f(v)
+  end
+  return result
+end
+
+function vim.tbl_filter(f, t)
+  local result = {}
+  for _, v in pairs(t) do
+    if f(v) then
+      table.insert(result, v)
+    end
+  end
+  return result
+end
+
+function vim.list_extend(dst, src, first, last)
+  for i = first or 1, last or #src do
+    table.insert(dst, src[i])
+  end
+  return dst
+end
+
+local function extend(deep, behavior, ...)
+  local result = {}
+  for i = 1, select("#", ...) do
+    for k, v in pairs(select(i, ...) or {}) do
+      if deep and type(v) == "table" and type(result[k]) == "table" then
+        result[k] = extend(true, behavior, result[k], v)
+      elseif behavior == "error" and result[k] ~= nil then
+        error("key found in more than one map: " .. tostring(k), 3)
+      elseif behavior == "keep" and result[k] ~= nil then
+        -- Keep the first value
+      else
+        result[k] = deep and vim.deepcopy(v) or v
+      end
+    end
+  end
+  return result
+end
+
+function vim.tbl_extend(behavior, ...)
+  return extend(false, behavior, ...)
+end
+
+function vim.tbl_deep_extend(behavior, ...)
+  return extend(true, behavior, ...)
+end
+
+function vim.validate(_) end
+
+vim.api = api
+vim.fn = fn
+
+-- The initial layout: one tabpage with one window showing an empty buffer
+state.current.tab = new_tabpage()
+state.current.buf = new_buffer(true, false)
+state.current.win = new_window(state.current.buf, state.current.tab)
+
+return vim
@@ -0,0 +1,480 @@
+//! A harness for testing compiled plugins inside headless Neovim
+//!
+//! [`Harness`] builds a runtimepath containing the plugin's cdylib, then runs each test case in its own
+//! `nvim --headless --clean` process with temporary `XDG_*` directories, so cases can't see each other's state
+//! or the user's config. Test bodies are Lua code, or functions exported by the plugin itself.
+//! Results are sent back over stdout as JSON, and every case is reported on its own.
+//!
+//! Neovim is expected to be on the `PATH`, unless another binary is set with [`Harness::with_nvim`].
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::testing::Harness;
+//!
+//! // Called from a `#[test]` in the plugin's crate
+//! fn plugin_tests() -> Result<(), nvim_utils::testing::TestError> {
+//!     Harness::new()
+//!         .with_plugin("my_plugin")?
+//!         .with_lua_test("loads", "assert(require('my_plugin'))")
+//!         .with_rust_test("runs self test", "my_plugin", "self_test")
+//!         .run()?
+//!         .assert_passed();
+//!     Ok(())
+//! }
+//! ```
+
+use serde::Deserialize;
+use std::{
+    env, fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const RUNNER: &str = include_str!("testing/runner.lua");
+
+/// Prefixes the line of JSON the runner writes the result to
+const MARKER: &str = "__NVIM_UTILS_TEST_RESULT__";
+
+/// The extension Neovim's Lua loader looks for on the runtimepath
+const MODULE_EXT: &str = if cfg!(target_os = "windows") {
+    "dll"
+} else {
+    "so"
+};
+
+/// An error that stopped the harness from running a test case.
+/// Failing test cases are reported in [`TestResult`] instead.
+#[derive(Debug)]
+pub enum TestError {
+    /// A plugin's compiled library could not be found
+    PluginNotFound {
+        name: String,
+        searched: Vec<PathBuf>,
+    },
+    /// Neovim could not be started
+    Spawn {
+        nvim: PathBuf,
+        error: io::Error,
+    },
+    /// The runner didn't report a result, usually because Neovim exited early
+    NoResult {
+        name: String,
+        stderr: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestError::PluginNotFound { name, searched } => write!(
+                f,
+                "could not find the compiled library for plugin {}, searched {:?}",
+                name, searched
+            ),
+            TestError::Spawn { nvim, error } => {
+                write!(f, "could not start {}: {}", nvim.display(), error)
+            }
+            TestError::NoResult { name, stderr } => write!(
+                f,
+                "test {} did not report a result, stderr:\n{}",
+                name, stderr
+            ),
+            TestError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+impl From<io::Error> for TestError {
+    fn from(err: io::Error) -> Self {
+        TestError::Io(err)
+    }
+}
+
+/// The body of a test case
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Body {
+    /// A Lua chunk, which fails if it raises an error
+    Lua(String),
+    /// A function exported by a module, which fails if it returns an error
+    Function { module: String, function: String },
+}
+
+impl Body {
+    fn to_lua(&self) -> String {
+        match self {
+            Body::Lua(code) => code.clone(),
+            Body::Function { module, function } => {
+                format!("require({:?})[{:?}]()", module, function)
+            }
+        }
+    }
+}
+
+/// A named test case, added with [`Harness::with_lua_test`] or [`Harness::with_rust_test`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    body: Body,
+}
+
+/// The outcome of a single test case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// The error and traceback of a failed case, or the reason it didn't finish
+    pub error: Option<String>,
+    pub duration: Duration,
+    /// Everything Neovim wrote to stderr, including `print` and `vim.notify` output
+    pub stderr: String,
+}
+
+/// The results of every test case run by [`Harness::run`], in the order they were added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub results: Vec<TestResult>,
+}
+
+impl Report {
+    /// Returns the cases that passed
+    pub fn passed(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|result| result.passed)
+    }
+
+    /// Returns the cases that failed
+    pub fn failed(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+
+    /// Prints a line for every case, then panics with the errors of the failed cases if there are any
+    pub fn assert_passed(&self) {
+        for result in &self.results {
+            let status = if result.passed { "ok" } else { "FAILED" };
+            println!(
+                "test {} ... {} ({:?})",
+                result.name, status, result.duration
+            );
+        }
+        let failures = self
+            .failed()
+            .map(|result| {
+                format!(
+                    "---- {} ----\n{}\nstderr:\n{}",
+                    result.name,
+                    result.error.as_deref().unwrap_or_default(),
+                    result.stderr
+                )
+            })
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            panic!(
+                "{} of {} Neovim tests failed\n\n{}",
+                failures.len(),
+                self.results.len(),
+                failures.join("\n\n")
+            );
+        }
+    }
+}
+
+/// Builder for running test cases in headless Neovim
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug, Clone)]
+pub struct Harness {
+    nvim: PathBuf,
+    plugins: Vec<(String, PathBuf)>,
+    runtimepath: Vec<PathBuf>,
+    setup: Option<String>,
+    timeout: Duration,
+    cases: Vec<TestCase>,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    /// Creates a harness using `nvim` from the `PATH`, with a timeout of 30 seconds per case
+    pub fn new() -> Self {
+        Self {
+            nvim: PathBuf::from("nvim"),
+            plugins: Vec::new(),
+            runtimepath: Vec::new(),
+            setup: None,
+            timeout: Duration::from_secs(30),
+            cases: Vec::new(),
+        }
+    }
+
+    /// Sets the Neovim binary to run
+    pub fn with_nvim(mut self, nvim: impl Into<PathBuf>) -> Self {
+        self.nvim = nvim.into();
+        self
+    }
+
+    /// Adds the cdylib built for a crate, found with [`find_plugin`].
+    /// It can be loaded with `require` using the crate name, with dashes replaced by underscores.
+    pub fn with_plugin(self, crate_name: &str) -> Result<Self, TestError> {
+        let path = find_plugin(crate_name)?;
+        Ok(self.with_plugin_path(&crate_name.replace('-', "_"), path))
+    }
+
+    /// Adds a compiled library, loaded with `require(module)`
+    pub fn with_plugin_path(mut self, module: &str, path: impl Into<PathBuf>) -> Self {
+        self.plugins.push((module.to_owned(), path.into()));
+        self
+    }
+
+    /// Adds a directory to the runtimepath, like a plugin's source directory containing `lua/` and `plugin/`
+    pub fn with_runtimepath(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.runtimepath.push(dir.into());
+        self
+    }
+
+    /// Sets Lua code to run before each test case
+    pub fn with_setup(mut self, lua: impl Into<String>) -> Self {
+        self.setup = Some(lua.into());
+        self
+    }
+
+    /// Sets how long a test case can run before Neovim is killed and the case fails
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a test case running Lua code, which fails if the code raises an error
+    pub fn with_lua_test(mut self, name: impl Into<String>, lua: impl Into<String>) -> Self {
+        self.cases.push(TestCase {
+            name: name.into(),
+            body: Body::Lua(lua.into()),
+        });
+        self
+    }
+
+    /// Adds a test case calling `require(module)[function]()`, for test bodies written in Rust and exported by a plugin.
+    /// The case fails if the function returns an error.
+    pub fn with_rust_test(
+        mut self,
+        name: impl Into<String>,
+        module: impl Into<String>,
+        function: impl Into<String>,
+    ) -> Self {
+        self.cases.push(TestCase {
+            name: name.into(),
+            body: Body::Function {
+                module: module.into(),
+                function: function.into(),
+            },
+        });
+        self
+    }
+
+    /// Returns the test cases added so far
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Runs every test case, each in its own Neovim process
+    pub fn run(&self) -> Result<Report, TestError> {
+        let results = self
+            .cases
+            .iter()
+            .map(|case| self.run_case(case))
+            .collect::<Result<_, _>>()?;
+        Ok(Report { results })
+    }
+
+    /// Runs a single test case in its own Neovim process
+    pub fn run_case(&self, case: &TestCase) -> Result<TestResult, TestError> {
+        let dir = TempDir::new()?;
+        let rtp = dir.path().join("rtp");
+        fs::create_dir_all(rtp.join("lua"))?;
+        for (module, path) in &self.plugins {
+            fs::copy(
+                path,
+                rtp.join("lua").join(format!("{}.{}", module, MODULE_EXT)),
+            )?;
+        }
+
+        let runner = dir.path().join("runner.lua");
+        let body = dir.path().join("case.lua");
+        let setup = dir.path().join("setup.lua");
+        fs::write(&runner, RUNNER)?;
+        fs::write(&body, case.body.to_lua())?;
+        fs::write(&setup, self.setup.as_deref().unwrap_or_default())?;
+
+        let mut command = Command::new(&self.nvim);
+        command
+            .args(["--headless", "--clean", "-n", "-i", "NONE"])
+            .arg("--cmd")
+            .arg(runtimepath_cmd(
+                std::iter::once(&rtp).chain(&self.runtimepath),
+            ))
+            .arg("-u")
+            .arg(&runner)
+            .env("NVIM_UTILS_TEST_MARKER", MARKER)
+            .env("NVIM_UTILS_TEST_CASE", &body)
+            .env("NVIM_UTILS_TEST_SETUP", &setup)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for (var, name) in [
+            ("XDG_CONFIG_HOME", "config"),
+            ("XDG_DATA_HOME", "data"),
+            ("XDG_STATE_HOME", "state"),
+            ("XDG_CACHE_HOME", "cache"),
+            ("XDG_RUNTIME_DIR", "runtime"),
+        ] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(&path)?;
+            command.env(var, path);
+        }
+
+        let start = Instant::now();
+        let mut child = command.spawn().map_err(|error| TestError::Spawn {
+            nvim: self.nvim.clone(),
+            error,
+        })?;
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+
+        let mut timed_out = false;
+        while child.try_wait()?.is_none() {
+            if start.elapsed() > self.timeout {
+                child.kill()?;
+                child.wait()?;
+                timed_out = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let duration = start.elapsed();
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default().replace('\r', "");
+
+        if timed_out {
+            return Ok(TestResult {
+                name: case.name.clone(),
+                passed: false,
+                error: Some(format!("timed out after {:?}", self.timeout)),
+                duration,
+                stderr,
+            });
+        }
+
+        #[derive(Deserialize)]
+        struct Outcome {
+            passed: bool,
+            error: Option<String>,
+        }
+        let outcome = stdout
+            .lines()
+            .rev()
+            .find_map(|line| line.strip_prefix(MARKER))
+            .and_then(|json| serde_json::from_str::<Outcome>(json).ok());
+        match outcome {
+            Some(outcome) => Ok(TestResult {
+                name: case.name.clone(),
+                passed: outcome.passed,
+                error: outcome.error,
+                duration,
+                stderr,
+            }),
+            None => Err(TestError::NoResult {
+                name: case.name.clone(),
+                stderr,
+            }),
+        }
+    }
+}
+
+/// Finds the cdylib cargo built for a crate, next to the running test executable.
+///
+/// Both `target/<profile>` and `target/<profile>/deps` are searched, and the most recently built match is returned.
+/// The plugin must already be built, for example as a `[dev-dependencies]` entry or with `cargo build` before testing.
+pub fn find_plugin(crate_name: &str) -> Result<PathBuf, TestError> {
+    let name = crate_name.replace('-', "_");
+    let file_name = if cfg!(target_os = "windows") {
+        format!("{}.dll", name)
+    } else if cfg!(target_os = "macos") {
+        format!("lib{}.dylib", name)
+    } else {
+        format!("lib{}.so", name)
+    };
+
+    let exe = env::current_exe()?;
+    let searched = exe
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(&file_name))
+        .collect::<Vec<_>>();
+    searched
+        .iter()
+        .filter_map(|path| Some((fs::metadata(path).ok()?.modified().ok()?, path)))
+        .max()
+        .map(|(_, path)| path.clone())
+        .ok_or_else(|| TestError::PluginNotFound {
+            name: crate_name.to_owned(),
+            searched,
+        })
+}
+
+/// Builds the `--cmd` that prepends directories to the runtimepath
+fn runtimepath_cmd<'a>(dirs: impl Iterator<Item = &'a PathBuf>) -> String {
+    dirs.map(|dir| {
+        format!(
+            "let &runtimepath = escape('{}', '\\,') . ',' . &runtimepath",
+            dir.to_string_lossy().replace('\'', "''")
+        )
+    })
+    .collect::<Vec<_>>()
+    .join(" | ")
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// A directory under the system's temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "nvim-utils-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
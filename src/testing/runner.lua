@@ -0,0 +1,27 @@
+-- Runs a single test case inside headless Neovim, started by `nvim_utils::testing::Harness`
+--
+-- The case and the optional setup script are passed as file paths in the environment. The result is written to stdout
+-- as one line of JSON after a marker, so output from the test itself can't be mistaken for it.
+
+local marker = os.getenv("NVIM_UTILS_TEST_MARKER")
+local setup = os.getenv("NVIM_UTILS_TEST_SETUP")
+local case = os.getenv("NVIM_UTILS_TEST_CASE")
+
+local function report(result)
+  io.stdout:write("\n", marker, vim.json.encode(result), "\n")
+  io.stdout:flush()
+  vim.cmd("qall!")
+end
+
+local ok, err = xpcall(function()
+  if setup and setup ~= "" then
+    dofile(setup)
+  end
+  dofile(case)
+end, debug.traceback)
+
+if ok then
+  report({ passed = true })
+else
+  report({ passed = false, error = tostring(err) })
+end
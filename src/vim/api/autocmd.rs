@@ -0,0 +1,518 @@
+//! Autocommands, corresponding to `nvim_create_autocmd` and related functions
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::api::autocmd::{self, AutocmdBuilder, Event};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let group = autocmd::nvim_create_augroup(lua, "MyPlugin", true)?;
+//!     AutocmdBuilder::new([Event::BufWritePost])
+//!         .with_group(group)
+//!         .with_pattern("*.rs")
+//!         .with_callback(|lua, args| {
+//!             log::info(lua, &format!("wrote {}", args.file))?;
+//!             // Returning true deletes the autocommand
+//!             Ok(false)
+//!         })
+//!         .create(lua)?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use serde::de::DeserializeOwned;
+
+use crate::{prelude::*, MaybeSend};
+use vim::api::Buffer;
+
+macro_rules! events {
+    ($($(#[$doc:meta])* $name:ident),* $(,)?) => {
+        /// An autocommand event, see `:h autocmd-events`
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum Event {
+            $($(#[$doc])* $name,)*
+            /// Any event not covered by the other variants
+            Other(String),
+        }
+
+        impl Event {
+            /// The name Neovim uses for the event
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Event::$name => stringify!($name),)*
+                    Event::Other(name) => name,
+                }
+            }
+        }
+
+        impl FromStr for Event {
+            type Err = std::convert::Infallible;
+
+            /// Parses an event name case-insensitively, like Neovim does
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(if s.eq_ignore_ascii_case(stringify!($name)) {
+                    return Ok(Event::$name);
+                })*
+                Ok(Event::Other(s.to_owned()))
+            }
+        }
+    };
+}
+
+events! {
+    BufAdd,
+    BufDelete,
+    BufEnter,
+    BufFilePost,
+    BufFilePre,
+    BufHidden,
+    BufLeave,
+    BufModifiedSet,
+    BufNew,
+    BufNewFile,
+    BufRead,
+    BufReadCmd,
+    BufReadPost,
+    BufReadPre,
+    BufUnload,
+    BufWinEnter,
+    BufWinLeave,
+    BufWipeout,
+    BufWrite,
+    BufWriteCmd,
+    BufWritePost,
+    BufWritePre,
+    ChanInfo,
+    ChanOpen,
+    CmdUndefined,
+    CmdlineChanged,
+    CmdlineEnter,
+    CmdlineLeave,
+    CmdwinEnter,
+    CmdwinLeave,
+    ColorScheme,
+    ColorSchemePre,
+    CompleteChanged,
+    CompleteDone,
+    CompleteDonePre,
+    CursorHold,
+    CursorHoldI,
+    CursorMoved,
+    CursorMovedI,
+    DiagnosticChanged,
+    DiffUpdated,
+    DirChanged,
+    DirChangedPre,
+    ExitPre,
+    FileAppendCmd,
+    FileAppendPost,
+    FileAppendPre,
+    FileChangedRO,
+    FileChangedShell,
+    FileChangedShellPost,
+    FileReadCmd,
+    FileReadPost,
+    FileReadPre,
+    FileType,
+    FileWriteCmd,
+    FileWritePost,
+    FileWritePre,
+    FilterReadPost,
+    FilterReadPre,
+    FilterWritePost,
+    FilterWritePre,
+    FocusGained,
+    FocusLost,
+    FuncUndefined,
+    InsertChange,
+    InsertCharPre,
+    InsertEnter,
+    InsertLeave,
+    InsertLeavePre,
+    LspAttach,
+    LspDetach,
+    MenuPopup,
+    ModeChanged,
+    OptionSet,
+    QuickFixCmdPost,
+    QuickFixCmdPre,
+    QuitPre,
+    RecordingEnter,
+    RecordingLeave,
+    RemoteReply,
+    SearchWrapped,
+    SessionLoadPost,
+    ShellCmdPost,
+    ShellFilterPost,
+    Signal,
+    SourceCmd,
+    SourcePost,
+    SourcePre,
+    SpellFileMissing,
+    StdinReadPost,
+    StdinReadPre,
+    SwapExists,
+    Syntax,
+    TabClosed,
+    TabEnter,
+    TabLeave,
+    TabNew,
+    TabNewEntered,
+    TermClose,
+    TermEnter,
+    TermLeave,
+    TermOpen,
+    TermResponse,
+    TextChanged,
+    TextChangedI,
+    TextChangedP,
+    TextChangedT,
+    TextYankPost,
+    UIEnter,
+    UILeave,
+    /// User-defined event, the name of the event is given by the pattern
+    User,
+    VimEnter,
+    VimLeave,
+    VimLeavePre,
+    VimResized,
+    VimResume,
+    VimSuspend,
+    WinClosed,
+    WinEnter,
+    WinLeave,
+    WinNew,
+    WinResized,
+    WinScrolled,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'a> ToLua<'a> for Event {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.as_str().to_lua(lua)
+    }
+}
+
+impl<'a> FromLua<'a> for Event {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        let name = String::from_lua(lua_value, lua)?;
+        Ok(Event::from_str(&name).unwrap_or_else(|never| match never {}))
+    }
+}
+
+/// An autocommand group, referred to either by id or by name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Augroup {
+    Id(LuaInteger),
+    Name(String),
+}
+
+impl From<LuaInteger> for Augroup {
+    fn from(id: LuaInteger) -> Self {
+        Augroup::Id(id)
+    }
+}
+
+impl From<&str> for Augroup {
+    fn from(name: &str) -> Self {
+        Augroup::Name(name.to_owned())
+    }
+}
+
+impl From<String> for Augroup {
+    fn from(name: String) -> Self {
+        Augroup::Name(name)
+    }
+}
+
+impl<'a> ToLua<'a> for Augroup {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        match self {
+            Augroup::Id(id) => id.to_lua(lua),
+            Augroup::Name(name) => name.to_lua(lua),
+        }
+    }
+}
+
+/// The argument passed to autocommand callbacks, see `:h nvim_create_autocmd()`
+#[derive(Debug, Clone)]
+pub struct AutocmdArgs<'a> {
+    /// The autocommand id
+    pub id: LuaInteger,
+    /// The event that triggered the autocommand
+    pub event: Event,
+    /// The autocommand group id, if any
+    pub group: Option<LuaInteger>,
+    /// Expanded value of `<amatch>`, called `match` in Lua (renamed, since `match` is a keyword)
+    pub matched: String,
+    /// Expanded value of `<abuf>`
+    pub buf: Buffer,
+    /// Expanded value of `<afile>`
+    pub file: String,
+    /// Arbitrary data passed from `nvim_exec_autocmds`
+    pub data: LuaValue<'a>,
+}
+
+impl<'a> AutocmdArgs<'a> {
+    /// Deserializes the `data` field into `T`
+    pub fn data_as<T: DeserializeOwned>(&self, lua: &'a Lua) -> LuaResult<T> {
+        lua.from_value(self.data.clone())
+    }
+}
+
+impl<'a> FromLua<'a> for AutocmdArgs<'a> {
+    fn from_lua(lua_value: LuaValue<'a>, _lua: &'a Lua) -> LuaResult<Self> {
+        match lua_value {
+            LuaValue::Table(table) => Ok(AutocmdArgs {
+                id: table.get("id")?,
+                event: table.get("event")?,
+                group: table.get("group")?,
+                matched: table.get("match")?,
+                buf: table.get("buf")?,
+                file: table.get("file")?,
+                data: table.get("data")?,
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "LuaValue",
+                to: "AutocmdArgs",
+                message: Some("Expected LuaValue::Table".to_string()),
+            }),
+        }
+    }
+}
+
+/// The signature of an autocommand callback. Returning `true` deletes the autocommand.
+pub type AutocmdCallback =
+    maybe_send_box!(dyn for<'a> Fn(&'a Lua, AutocmdArgs<'a>) -> LuaResult<bool>);
+
+enum AutocmdAction {
+    Callback(AutocmdCallback),
+    Command(String),
+}
+
+/// Builder for autocommands, passed to [`nvim_create_autocmd`]
+pub struct AutocmdBuilder {
+    events: Vec<Event>,
+    pattern: Vec<String>,
+    buffer: Option<Buffer>,
+    group: Option<Augroup>,
+    desc: Option<String>,
+    once: bool,
+    nested: bool,
+    action: Option<AutocmdAction>,
+}
+
+impl fmt::Debug for AutocmdBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutocmdBuilder")
+            .field("events", &self.events)
+            .field("pattern", &self.pattern)
+            .field("buffer", &self.buffer)
+            .field("group", &self.group)
+            .field("desc", &self.desc)
+            .field("once", &self.once)
+            .field("nested", &self.nested)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AutocmdBuilder {
+    /// Creates a new autocommand builder for the given events
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            pattern: Vec::new(),
+            buffer: None,
+            group: None,
+            desc: None,
+            once: false,
+            nested: false,
+            action: None,
+        }
+    }
+
+    /// Adds a pattern to match against, can be called multiple times.<br>
+    /// Cannot be combined with [`AutocmdBuilder::with_buffer`].
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern.push(pattern.into());
+        self
+    }
+
+    /// Makes the autocommand buffer-local.<br>
+    /// Cannot be combined with [`AutocmdBuilder::with_pattern`].
+    pub fn with_buffer(mut self, buffer: Buffer) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Adds the autocommand to a group
+    pub fn with_group(mut self, group: impl Into<Augroup>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Sets the description of the autocommand
+    pub fn with_desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = Some(desc.into());
+        self
+    }
+
+    /// Runs the autocommand only once
+    pub fn with_once(mut self, once: bool) -> Self {
+        self.once = once;
+        self
+    }
+
+    /// Allows the autocommand to trigger other autocommands
+    pub fn with_nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+
+    /// Sets the callback to run. Returning `true` from the callback deletes the autocommand.
+    pub fn with_callback<F>(mut self, callback: F) -> Self
+    where
+        F: for<'a> Fn(&'a Lua, AutocmdArgs<'a>) -> LuaResult<bool> + MaybeSend + 'static,
+    {
+        self.action = Some(AutocmdAction::Callback(Box::new(callback)));
+        self
+    }
+
+    /// Sets an Ex command to run instead of a callback
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.action = Some(AutocmdAction::Command(command.into()));
+        self
+    }
+
+    /// Creates the autocommand, returning its id
+    pub fn create(self, lua: &Lua) -> LuaResult<LuaInteger> {
+        nvim_create_autocmd(lua, self)
+    }
+
+    fn into_opts(self, lua: &Lua) -> LuaResult<(Vec<Event>, LuaTable<'_>)> {
+        let opts = lua.create_table()?;
+        if !self.pattern.is_empty() {
+            opts.set("pattern", self.pattern)?;
+        }
+        opts.set("buffer", self.buffer)?;
+        opts.set("group", self.group)?;
+        opts.set("desc", self.desc)?;
+        opts.set("once", self.once)?;
+        opts.set("nested", self.nested)?;
+        match self.action {
+            Some(AutocmdAction::Callback(callback)) => {
+                opts.set(
+                    "callback",
+                    lua.create_function(move |lua, args: AutocmdArgs| callback(lua, args))?,
+                )?;
+            }
+            Some(AutocmdAction::Command(command)) => opts.set("command", command)?,
+            None => {}
+        }
+        Ok((self.events, opts))
+    }
+}
+
+/// Corresponds to `vim.api.nvim_create_augroup`
+/// Returns the id of the group. If `clear` is true, existing autocommands in the group are removed.
+pub fn nvim_create_augroup(lua: &Lua, name: &str, clear: bool) -> LuaResult<LuaInteger> {
+    let opts = lua.create_table()?;
+    opts.set("clear", clear)?;
+    vim::api::get(lua)?.call_function("nvim_create_augroup", (name, opts))
+}
+
+/// Corresponds to `vim.api.nvim_del_augroup_by_id`
+pub fn nvim_del_augroup_by_id(lua: &Lua, id: LuaInteger) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_del_augroup_by_id", id)
+}
+
+/// Corresponds to `vim.api.nvim_del_augroup_by_name`
+pub fn nvim_del_augroup_by_name(lua: &Lua, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_del_augroup_by_name", name)
+}
+
+/// Corresponds to `vim.api.nvim_create_autocmd`
+/// Returns the id of the autocommand.
+pub fn nvim_create_autocmd(lua: &Lua, autocmd: AutocmdBuilder) -> LuaResult<LuaInteger> {
+    let (events, opts) = autocmd.into_opts(lua)?;
+    vim::api::get(lua)?.call_function("nvim_create_autocmd", (events, opts))
+}
+
+/// Corresponds to `vim.api.nvim_del_autocmd`
+pub fn nvim_del_autocmd(lua: &Lua, id: LuaInteger) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_del_autocmd", id)
+}
+
+/// Options for [`nvim_clear_autocmds`]
+#[derive(Debug, Clone, Default)]
+pub struct ClearAutocmdsOpts {
+    pub events: Vec<Event>,
+    pub pattern: Vec<String>,
+    pub buffer: Option<Buffer>,
+    pub group: Option<Augroup>,
+}
+
+impl<'a> ToLua<'a> for ClearAutocmdsOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        if !self.events.is_empty() {
+            table.set("event", self.events)?;
+        }
+        if !self.pattern.is_empty() {
+            table.set("pattern", self.pattern)?;
+        }
+        table.set("buffer", self.buffer)?;
+        table.set("group", self.group)?;
+        lua.pack(table)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_clear_autocmds`
+pub fn nvim_clear_autocmds(lua: &Lua, opts: ClearAutocmdsOpts) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_clear_autocmds", opts)
+}
+
+/// Options for [`nvim_exec_autocmds`]
+#[derive(Debug, Clone, Default)]
+pub struct ExecAutocmdsOpts<'a> {
+    pub pattern: Vec<String>,
+    pub buffer: Option<Buffer>,
+    pub group: Option<Augroup>,
+    /// Process the modeline after the autocommands
+    pub modeline: Option<bool>,
+    /// Arbitrary data, passed to callbacks as [`AutocmdArgs::data`]
+    pub data: Option<LuaValue<'a>>,
+}
+
+impl<'a> ToLua<'a> for ExecAutocmdsOpts<'a> {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        if !self.pattern.is_empty() {
+            table.set("pattern", self.pattern)?;
+        }
+        table.set("buffer", self.buffer)?;
+        table.set("group", self.group)?;
+        table.set("modeline", self.modeline)?;
+        table.set("data", self.data)?;
+        lua.pack(table)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_exec_autocmds`
+pub fn nvim_exec_autocmds<'a>(
+    lua: &'a Lua,
+    events: impl IntoIterator<Item = Event>,
+    opts: ExecAutocmdsOpts<'a>,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function(
+        "nvim_exec_autocmds",
+        (events.into_iter().collect::<Vec<_>>(), opts),
+    )
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::{prelude::*, MaybeSend};
+use vim::api::Buffer;
+
+/// Arguments of the `on_lines` callback, see `:h nvim_buf_attach()`
+///
+/// Lines `first..last_old` were replaced by `first..last_new`, all 0-based and end-exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinesEvent {
+    pub buf: Buffer,
+    /// `None` if the change didn't increment the changedtick, like undo to an unmodified state
+    pub changedtick: Option<u64>,
+    pub first: u64,
+    pub last_old: u64,
+    pub last_new: u64,
+    /// The size in bytes of the replaced region, including the final newline
+    pub byte_count: u64,
+    /// The number of deleted codepoints, only set with [`BufAttachOpts::with_utf_sizes`]
+    pub deleted_codepoints: Option<u64>,
+    /// The number of deleted UTF-16 code units, only set with [`BufAttachOpts::with_utf_sizes`]
+    pub deleted_codeunits: Option<u64>,
+}
+
+/// Arguments of the `on_bytes` callback, see `:h nvim_buf_attach()`
+///
+/// The text starting at `(start_row, start_col)` spanning `(old_end_row, old_end_col)` was replaced
+/// by text spanning `(new_end_row, new_end_col)`. Rows and columns are 0-based, columns and offsets are in bytes,
+/// and the end positions are relative to the start, so `old_end_col` is only an absolute column when `old_end_row` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BytesEvent {
+    pub buf: Buffer,
+    pub changedtick: u64,
+    pub start_row: u64,
+    pub start_col: u64,
+    /// The byte offset of the start of the change in the buffer
+    pub start_byte: u64,
+    pub old_end_row: u64,
+    pub old_end_col: u64,
+    /// The length in bytes of the replaced text
+    pub old_end_byte: u64,
+    pub new_end_row: u64,
+    pub new_end_col: u64,
+    /// The length in bytes of the new text
+    pub new_end_byte: u64,
+}
+
+/// The signature of an `on_lines` callback. Returning `true` detaches it.
+pub type LinesCallback = maybe_send_box!(dyn Fn(&Lua, LinesEvent) -> LuaResult<bool>);
+
+/// The signature of an `on_bytes` callback. Returning `true` detaches it.
+pub type BytesCallback = maybe_send_box!(dyn Fn(&Lua, BytesEvent) -> LuaResult<bool>);
+
+/// The signature of an `on_changedtick` callback, which receives the buffer and its new changedtick.
+/// Returning `true` detaches it.
+pub type ChangedtickCallback = maybe_send_box!(dyn Fn(&Lua, Buffer, u64) -> LuaResult<bool>);
+
+/// The signature of `on_detach` and `on_reload` callbacks
+pub type BufCallback = maybe_send_box!(dyn Fn(&Lua, Buffer) -> LuaResult<()>);
+
+/// Builder for the callbacks passed to [`nvim_buf_attach`]
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{BufAttachOpts, Buffer};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let opts = BufAttachOpts::new()
+///         .with_on_bytes(|lua, event| {
+///             log::info(lua, &format!("{} bytes changed", event.old_end_byte))?;
+///             Ok(false)
+///         })
+///         .with_on_detach(|lua, buf| log::info(lua, &format!("detached from {}", buf.id())));
+///     Buffer::current(lua)?.attach(lua, false, opts)?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct BufAttachOpts {
+    on_lines: Option<LinesCallback>,
+    on_bytes: Option<BytesCallback>,
+    on_changedtick: Option<ChangedtickCallback>,
+    on_detach: Option<BufCallback>,
+    on_reload: Option<BufCallback>,
+    utf_sizes: bool,
+    preview: bool,
+}
+
+impl fmt::Debug for BufAttachOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufAttachOpts")
+            .field("utf_sizes", &self.utf_sizes)
+            .field("preview", &self.preview)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BufAttachOpts {
+    /// Creates a builder with no callbacks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called on every change to the lines of the buffer
+    pub fn with_on_lines<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, LinesEvent) -> LuaResult<bool> + MaybeSend + 'static,
+    {
+        self.on_lines = Some(Box::new(callback));
+        self
+    }
+
+    /// Called on every change to the buffer, with byte-level precision
+    pub fn with_on_bytes<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, BytesEvent) -> LuaResult<bool> + MaybeSend + 'static,
+    {
+        self.on_bytes = Some(Box::new(callback));
+        self
+    }
+
+    /// Called when the changedtick increments without the text changing
+    pub fn with_on_changedtick<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, Buffer, u64) -> LuaResult<bool> + MaybeSend + 'static,
+    {
+        self.on_changedtick = Some(Box::new(callback));
+        self
+    }
+
+    /// Called when the callbacks are detached, either by returning `true` or because the buffer was unloaded
+    pub fn with_on_detach<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, Buffer) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.on_detach = Some(Box::new(callback));
+        self
+    }
+
+    /// Called when the whole buffer is reloaded from disk, which doesn't trigger `on_lines` or `on_bytes`
+    pub fn with_on_reload<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, Buffer) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.on_reload = Some(Box::new(callback));
+        self
+    }
+
+    /// Include the deleted codepoints and UTF-16 code units in [`LinesEvent`]
+    pub fn with_utf_sizes(mut self, utf_sizes: bool) -> Self {
+        self.utf_sizes = utf_sizes;
+        self
+    }
+
+    /// Also send changes made by `'inccommand'` previews
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+}
+
+impl<'a> FromLuaMulti<'a> for LinesEvent {
+    fn from_lua_multi(values: LuaMultiValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        type Args = (
+            String,
+            Buffer,
+            Option<u64>,
+            u64,
+            u64,
+            u64,
+            u64,
+            Option<u64>,
+            Option<u64>,
+        );
+        let (_, buf, changedtick, first, last_old, last_new, byte_count, codepoints, codeunits) =
+            Args::from_lua_multi(values, lua)?;
+        Ok(LinesEvent {
+            buf,
+            changedtick,
+            first,
+            last_old,
+            last_new,
+            byte_count,
+            deleted_codepoints: codepoints,
+            deleted_codeunits: codeunits,
+        })
+    }
+}
+
+impl<'a> FromLuaMulti<'a> for BytesEvent {
+    fn from_lua_multi(values: LuaMultiValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        type Args = (
+            String,
+            Buffer,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+            u64,
+        );
+        let (
+            _,
+            buf,
+            changedtick,
+            start_row,
+            start_col,
+            start_byte,
+            old_end_row,
+            old_end_col,
+            old_end_byte,
+            new_end_row,
+            new_end_col,
+            new_end_byte,
+        ) = Args::from_lua_multi(values, lua)?;
+        Ok(BytesEvent {
+            buf,
+            changedtick,
+            start_row,
+            start_col,
+            start_byte,
+            old_end_row,
+            old_end_col,
+            old_end_byte,
+            new_end_row,
+            new_end_col,
+            new_end_byte,
+        })
+    }
+}
+
+impl<'a> ToLua<'a> for BufAttachOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        if let Some(callback) = self.on_lines {
+            let f = lua.create_function(move |lua, event: LinesEvent| callback(lua, event))?;
+            table.set("on_lines", f)?;
+        }
+        if let Some(callback) = self.on_bytes {
+            let f = lua.create_function(move |lua, event: BytesEvent| callback(lua, event))?;
+            table.set("on_bytes", f)?;
+        }
+        if let Some(callback) = self.on_changedtick {
+            let f =
+                lua.create_function(move |lua, (_, buf, changedtick): (String, Buffer, u64)| {
+                    callback(lua, buf, changedtick)
+                })?;
+            table.set("on_changedtick", f)?;
+        }
+        if let Some(callback) = self.on_detach {
+            let f =
+                lua.create_function(move |lua, (_, buf): (String, Buffer)| callback(lua, buf))?;
+            table.set("on_detach", f)?;
+        }
+        if let Some(callback) = self.on_reload {
+            let f =
+                lua.create_function(move |lua, (_, buf): (String, Buffer)| callback(lua, buf))?;
+            table.set("on_reload", f)?;
+        }
+        table.set("utf_sizes", self.utf_sizes)?;
+        table.set("preview", self.preview)?;
+        lua.pack(table)
+    }
+}
+
+impl Buffer {
+    /// Attaches callbacks to buffer changes, using `vim.api.nvim_buf_attach`
+    pub fn attach(&self, lua: &Lua, send_buffer: bool, opts: BufAttachOpts) -> LuaResult<bool> {
+        nvim_buf_attach(lua, *self, send_buffer, opts)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_buf_attach`
+/// Returns `false` if attaching failed, for example because the buffer isn't loaded.
+pub fn nvim_buf_attach(
+    lua: &Lua,
+    buffer: Buffer,
+    send_buffer: bool,
+    opts: BufAttachOpts,
+) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_buf_attach", (buffer, send_buffer, opts))
+}
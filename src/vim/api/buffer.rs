@@ -1,29 +1,212 @@
+use std::ops::RangeBounds;
+
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
+use vim::api::{line_range, Position, Range};
 
-/// Corresponds to `vim.api.nvim_buf_attach`
-// TODO: Change opts into a struct that implements `ToLua`
-pub fn nvim_buf_attach(lua: &Lua, buffer: u64, send_buffer: bool, opts: LuaTable) -> LuaResult<()> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_buf_attach")?
-        .call((buffer, send_buffer, opts))
+/// A handle to a Neovim buffer
+///
+/// Buffer `0` always refers to the current buffer, see [`Buffer::CURRENT`].
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::Buffer;
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let buf = Buffer::current(lua)?;
+///     let lines = buf.get_lines(lua, .., false)?;
+///     buf.set_lines(lua, lines.len() as u64.., false, vec![format!("{} lines", lines.len())])?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Buffer(LuaInteger);
+
+impl Buffer {
+    /// The handle Neovim uses to refer to the current buffer
+    pub const CURRENT: Buffer = Buffer(0);
+
+    /// Creates a buffer handle from a raw buffer id
+    pub const fn new(id: LuaInteger) -> Self {
+        Self(id)
+    }
+
+    /// Returns the raw buffer id
+    pub const fn id(&self) -> LuaInteger {
+        self.0
+    }
+
+    /// Gets the current buffer, using `vim.api.nvim_get_current_buf`
+    pub fn current(lua: &Lua) -> LuaResult<Self> {
+        vim::api::nvim_get_current_buf(lua)
+    }
+
+    /// Gets all buffers, using `vim.api.nvim_list_bufs`
+    pub fn list(lua: &Lua) -> LuaResult<Vec<Self>> {
+        vim::api::nvim_list_bufs(lua)
+    }
+
+    /// Creates a new buffer, using `vim.api.nvim_create_buf`
+    pub fn create(lua: &Lua, listed: bool, scratch: bool) -> LuaResult<Self> {
+        vim::api::get(lua)?.call_function("nvim_create_buf", (listed, scratch))
+    }
+
+    /// Gets a range of 0-based rows from the buffer, using `vim.api.nvim_buf_get_lines`
+    pub fn get_lines(
+        &self,
+        lua: &Lua,
+        rows: impl RangeBounds<u64>,
+        strict_indexing: bool,
+    ) -> LuaResult<Vec<String>> {
+        nvim_buf_get_lines(lua, *self, rows, strict_indexing)
+    }
+
+    /// Replaces a range of 0-based rows in the buffer, using `vim.api.nvim_buf_set_lines`
+    pub fn set_lines<S: Into<String>>(
+        &self,
+        lua: &Lua,
+        rows: impl RangeBounds<u64>,
+        strict_indexing: bool,
+        lines: Vec<S>,
+    ) -> LuaResult<()> {
+        nvim_buf_set_lines(lua, *self, rows, strict_indexing, lines)
+    }
+
+    /// Gets a range of text from the buffer, using `vim.api.nvim_buf_get_text`
+    pub fn get_text(&self, lua: &Lua, range: Range) -> LuaResult<Vec<String>> {
+        nvim_buf_get_text(lua, *self, range)
+    }
+
+    /// Replaces a range of text in the buffer, using `vim.api.nvim_buf_set_text`
+    pub fn set_text<S: Into<String>>(
+        &self,
+        lua: &Lua,
+        range: Range,
+        replacement: Vec<S>,
+    ) -> LuaResult<()> {
+        nvim_buf_set_text(lua, *self, range, replacement)
+    }
+
+    /// Gets the number of lines in the buffer, using `vim.api.nvim_buf_line_count`
+    pub fn line_count(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_buf_line_count(lua, *self)
+    }
+
+    /// Gets the `b:changedtick` of the buffer, using `vim.api.nvim_buf_get_changedtick`
+    pub fn get_changedtick(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_buf_get_changedtick(lua, *self)
+    }
+
+    /// Gets the full file name of the buffer, using `vim.api.nvim_buf_get_name`
+    pub fn get_name(&self, lua: &Lua) -> LuaResult<String> {
+        nvim_buf_get_name(lua, *self)
+    }
+
+    /// Sets the full file name of the buffer, using `vim.api.nvim_buf_set_name`
+    pub fn set_name(&self, lua: &Lua, name: &str) -> LuaResult<()> {
+        nvim_buf_set_name(lua, *self, name)
+    }
+
+    /// Gets a buffer-local option, using `vim.api.nvim_get_option_value`
+    pub fn get_option<'a, T: FromLua<'a>>(&self, lua: &'a Lua, name: &str) -> LuaResult<T> {
+        vim::api::nvim_get_option_value(lua, name, vim::api::OptionValueOpts::buf(*self))
+    }
+
+    /// Sets a buffer-local option, using `vim.api.nvim_set_option_value`
+    pub fn set_option<'a>(&self, lua: &'a Lua, name: &str, value: impl ToLua<'a>) -> LuaResult<()> {
+        vim::api::nvim_set_option_value(lua, name, value, vim::api::OptionValueOpts::buf(*self))
+    }
+
+    /// Gets a buffer-scoped (`b:`) variable, using `vim.api.nvim_buf_get_var`
+    pub fn get_var<'a, T: FromLua<'a>>(&self, lua: &'a Lua, name: &str) -> LuaResult<T> {
+        nvim_buf_get_var(lua, *self, name)
+    }
+
+    /// Sets a buffer-scoped (`b:`) variable, using `vim.api.nvim_buf_set_var`
+    pub fn set_var<'a>(&self, lua: &'a Lua, name: &str, value: impl ToLua<'a>) -> LuaResult<()> {
+        nvim_buf_set_var(lua, *self, name, value)
+    }
+
+    /// Removes a buffer-scoped (`b:`) variable, using `vim.api.nvim_buf_del_var`
+    pub fn del_var(&self, lua: &Lua, name: &str) -> LuaResult<()> {
+        nvim_buf_del_var(lua, *self, name)
+    }
+
+    /// Gets the position of a mark, using `vim.api.nvim_buf_get_mark`
+    pub fn get_mark(&self, lua: &Lua, name: &str) -> LuaResult<Option<Position>> {
+        nvim_buf_get_mark(lua, *self, name)
+    }
+
+    /// Sets a mark, using `vim.api.nvim_buf_set_mark`
+    pub fn set_mark(&self, lua: &Lua, name: &str, pos: Position) -> LuaResult<bool> {
+        nvim_buf_set_mark(lua, *self, name, pos)
+    }
+
+    /// Deletes a mark, using `vim.api.nvim_buf_del_mark`
+    pub fn del_mark(&self, lua: &Lua, name: &str) -> LuaResult<bool> {
+        nvim_buf_del_mark(lua, *self, name)
+    }
+
+    /// Checks if the buffer handle is valid, using `vim.api.nvim_buf_is_valid`
+    pub fn is_valid(&self, lua: &Lua) -> LuaResult<bool> {
+        nvim_buf_is_valid(lua, *self)
+    }
+
+    /// Checks if the buffer is loaded, using `vim.api.nvim_buf_is_loaded`
+    pub fn is_loaded(&self, lua: &Lua) -> LuaResult<bool> {
+        nvim_buf_is_loaded(lua, *self)
+    }
+
+    /// Deletes the buffer, using `vim.api.nvim_buf_delete`
+    pub fn delete(&self, lua: &Lua, force: bool, unload: bool) -> LuaResult<()> {
+        nvim_buf_delete(lua, *self, force, unload)
+    }
+}
+
+impl From<LuaInteger> for Buffer {
+    fn from(id: LuaInteger) -> Self {
+        Self(id)
+    }
+}
+
+impl From<Buffer> for LuaInteger {
+    fn from(buf: Buffer) -> Self {
+        buf.0
+    }
+}
+
+impl<'a> FromLua<'a> for Buffer {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        LuaInteger::from_lua(lua_value, lua).map(Self)
+    }
+}
+
+impl<'a> ToLua<'a> for Buffer {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.0.to_lua(lua)
+    }
 }
 
 /// Corresponds to `vim.api.nvim_buf_detach`
-pub fn nvim_buf_detach(lua: &Lua, buffer: u64) -> LuaResult<()> {
+pub fn nvim_buf_detach(lua: &Lua, buffer: Buffer) -> LuaResult<bool> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_buf_detach")?
         .call(buffer)
 }
 
 /// Corresponds to `vim.api.nvim_buf_set_lines`
+/// `rows` are 0-based, an unbounded end means the end of the buffer.
 pub fn nvim_buf_set_lines<S: Into<String>>(
     lua: &Lua,
-    buffer: u64,
-    start: u64,
-    end: u64,
+    buffer: Buffer,
+    rows: impl RangeBounds<u64>,
     strict_indexing: bool,
     lines: Vec<S>,
 ) -> LuaResult<()> {
+    let (start, end) = line_range(rows);
     vim::api::get(lua)?.call_function(
         "nvim_buf_set_lines",
         (
@@ -37,12 +220,137 @@ pub fn nvim_buf_set_lines<S: Into<String>>(
 }
 
 /// Corresponds to `vim.api.nvim_buf_get_lines`
+/// `rows` are 0-based, an unbounded end means the end of the buffer.
 pub fn nvim_buf_get_lines(
     lua: &Lua,
-    buffer: u64,
-    start: u64,
-    end: u64,
+    buffer: Buffer,
+    rows: impl RangeBounds<u64>,
     strict_indexing: bool,
 ) -> LuaResult<Vec<String>> {
+    let (start, end) = line_range(rows);
     vim::api::get(lua)?.call_function("nvim_buf_get_lines", (buffer, start, end, strict_indexing))
 }
+
+/// Corresponds to `vim.api.nvim_buf_set_text`
+pub fn nvim_buf_set_text<S: Into<String>>(
+    lua: &Lua,
+    buffer: Buffer,
+    range: Range,
+    replacement: Vec<S>,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_set_text",
+        (
+            buffer,
+            range.start.row,
+            range.start.col,
+            range.end.row,
+            range.end.col,
+            replacement
+                .into_iter()
+                .map(|s| s.into())
+                .collect::<Vec<_>>(),
+        ),
+    )
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_text`
+pub fn nvim_buf_get_text(lua: &Lua, buffer: Buffer, range: Range) -> LuaResult<Vec<String>> {
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_get_text",
+        (
+            buffer,
+            range.start.row,
+            range.start.col,
+            range.end.row,
+            range.end.col,
+            lua.create_table()?,
+        ),
+    )
+}
+
+/// Corresponds to `vim.api.nvim_buf_line_count`
+pub fn nvim_buf_line_count(lua: &Lua, buffer: Buffer) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_buf_line_count", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_changedtick`
+pub fn nvim_buf_get_changedtick(lua: &Lua, buffer: Buffer) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_buf_get_changedtick", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_name`
+pub fn nvim_buf_get_name(lua: &Lua, buffer: Buffer) -> LuaResult<String> {
+    vim::api::get(lua)?.call_function("nvim_buf_get_name", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_buf_set_name`
+pub fn nvim_buf_set_name(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_buf_set_name", (buffer, name))
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_var`
+pub fn nvim_buf_get_var<'a, T: FromLua<'a>>(
+    lua: &'a Lua,
+    buffer: Buffer,
+    name: &str,
+) -> LuaResult<T> {
+    vim::api::get(lua)?.call_function("nvim_buf_get_var", (buffer, name))
+}
+
+/// Corresponds to `vim.api.nvim_buf_set_var`
+pub fn nvim_buf_set_var<'a>(
+    lua: &'a Lua,
+    buffer: Buffer,
+    name: &str,
+    value: impl ToLua<'a>,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_buf_set_var", (buffer, name, value))
+}
+
+/// Corresponds to `vim.api.nvim_buf_del_var`
+pub fn nvim_buf_del_var(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_buf_del_var", (buffer, name))
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_mark`
+/// Returns `None` if the mark is not set.
+pub fn nvim_buf_get_mark(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<Option<Position>> {
+    // The api uses a 1-based row, with `(0, 0)` meaning the mark is not set
+    let (row, col): (u64, u64) = vim::api::get(lua)?
+        .call_function::<_, _, Position>("nvim_buf_get_mark", (buffer, name))?
+        .into();
+    Ok((row != 0).then(|| Position::from_cursor(row, col)))
+}
+
+/// Corresponds to `vim.api.nvim_buf_set_mark`
+pub fn nvim_buf_set_mark(lua: &Lua, buffer: Buffer, name: &str, pos: Position) -> LuaResult<bool> {
+    let (row, col) = pos.to_cursor();
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_set_mark",
+        (buffer, name, row, col, lua.create_table()?),
+    )
+}
+
+/// Corresponds to `vim.api.nvim_buf_del_mark`
+pub fn nvim_buf_del_mark(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_buf_del_mark", (buffer, name))
+}
+
+/// Corresponds to `vim.api.nvim_buf_is_valid`
+pub fn nvim_buf_is_valid(lua: &Lua, buffer: Buffer) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_buf_is_valid", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_buf_is_loaded`
+pub fn nvim_buf_is_loaded(lua: &Lua, buffer: Buffer) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_buf_is_loaded", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_buf_delete`
+pub fn nvim_buf_delete(lua: &Lua, buffer: Buffer, force: bool, unload: bool) -> LuaResult<()> {
+    let opts = lua.create_table()?;
+    opts.set("force", force)?;
+    opts.set("unload", unload)?;
+    vim::api::get(lua)?.call_function("nvim_buf_delete", (buffer, opts))
+}
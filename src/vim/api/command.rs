@@ -0,0 +1,474 @@
+//! User commands, corresponding to `nvim_create_user_command` and related functions
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::api::command::{CommandBuilder, Nargs};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     CommandBuilder::new("Greet")
+//!         .with_nargs(Nargs::Optional)
+//!         .with_bang(true)
+//!         .with_complete_fn(|_lua, args| {
+//!             Ok(["world", "neovim"]
+//!                 .into_iter()
+//!                 .filter(|s| s.starts_with(&args.arg_lead))
+//!                 .map(String::from)
+//!                 .collect())
+//!         })
+//!         .with_handler(|lua, args| {
+//!             let who = args.fargs.first().map(String::as_str).unwrap_or("world");
+//!             log::info(lua, &format!("Hello, {}{}", who, if args.bang { "!" } else { "" }))
+//!         })
+//!         .create(lua)
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{prelude::*, MaybeSend};
+use vim::api::Buffer;
+
+/// The number of arguments a command accepts, see `:h :command-nargs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Nargs {
+    /// No arguments are allowed (`-nargs=0`)
+    #[default]
+    Zero,
+    /// Exactly one argument is required, including spaces (`-nargs=1`)
+    One,
+    /// Any number of arguments are allowed (`-nargs=*`)
+    Any,
+    /// Zero or one arguments are allowed (`-nargs=?`)
+    Optional,
+    /// Arguments must be supplied, but any number are allowed (`-nargs=+`)
+    OneOrMore,
+}
+
+impl Nargs {
+    /// The value Neovim uses for `nargs`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Nargs::Zero => "0",
+            Nargs::One => "1",
+            Nargs::Any => "*",
+            Nargs::Optional => "?",
+            Nargs::OneOrMore => "+",
+        }
+    }
+}
+
+impl<'a> ToLua<'a> for Nargs {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.as_str().to_lua(lua)
+    }
+}
+
+/// The range a command accepts, see `:h :command-range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandRange {
+    /// A range is allowed, the default is the current line (`-range`)
+    CurrentLine,
+    /// A range is allowed, the default is the whole file (`-range=%`)
+    WholeFile,
+    /// A count is allowed, with the given default (`-range=N`)
+    Count(u64),
+}
+
+impl<'a> ToLua<'a> for CommandRange {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        match self {
+            CommandRange::CurrentLine => true.to_lua(lua),
+            CommandRange::WholeFile => "%".to_lua(lua),
+            CommandRange::Count(n) => n.to_lua(lua),
+        }
+    }
+}
+
+/// Command modifiers, see `:h <mods>`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandModifiers {
+    pub browse: bool,
+    pub confirm: bool,
+    pub emsg_silent: bool,
+    pub hide: bool,
+    pub horizontal: bool,
+    pub keepalt: bool,
+    pub keepjumps: bool,
+    pub keepmarks: bool,
+    pub keeppatterns: bool,
+    pub lockmarks: bool,
+    pub noautocmd: bool,
+    pub noswapfile: bool,
+    pub sandbox: bool,
+    pub silent: bool,
+    /// One of `""`, `"aboveleft"`, `"belowright"`, `"topleft"` or `"botright"`
+    pub split: String,
+    /// The tab number given by `:tab`, `-1` if not used
+    pub tab: i64,
+    pub unsilent: bool,
+    /// The verbose level given by `:verbose`, `-1` if not used
+    pub verbose: i64,
+    pub vertical: bool,
+}
+
+/// The argument passed to command handlers and preview callbacks, see `:h nvim_create_user_command()`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandArgs {
+    /// The command name
+    pub name: String,
+    /// The arguments passed to the command, as a single string
+    pub args: String,
+    /// The arguments passed to the command, split by unescaped whitespace
+    pub fargs: Vec<String>,
+    /// Whether the command was executed with a `!`
+    pub bang: bool,
+    /// The starting line of the command range
+    pub line1: u64,
+    /// The final line of the command range
+    pub line2: u64,
+    /// The number of items in the command range: 0, 1 or 2
+    pub range: u8,
+    /// Any count supplied
+    pub count: i64,
+    /// The optional register, if specified
+    pub reg: String,
+    /// Command modifiers, as a string
+    pub mods: String,
+    /// Command modifiers, in a structured format
+    pub smods: CommandModifiers,
+}
+
+impl<'a> FromLua<'a> for CommandArgs {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        lua.from_value(lua_value)
+    }
+}
+
+/// The arguments Neovim passes to completion functions, see `:h :command-completion-customlist`
+#[derive(Debug, Clone)]
+pub struct CompleteArgs {
+    /// The leading portion of the argument currently being completed
+    pub arg_lead: String,
+    /// The entire command line
+    pub cmd_line: String,
+    /// The cursor position in the command line, in bytes
+    pub cursor_pos: u64,
+}
+
+/// The return value of a preview callback, see `:h :command-preview`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum PreviewResult {
+    /// Don't show a preview
+    None = 0,
+    /// Show the preview without opening the preview window
+    Preview = 1,
+    /// Show the preview and open the preview window
+    PreviewWindow = 2,
+}
+
+impl<'a> ToLua<'a> for PreviewResult {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        (self as u8).to_lua(lua)
+    }
+}
+
+/// The signature of a command handler
+pub type CommandHandler = maybe_send_box!(dyn Fn(&Lua, CommandArgs) -> LuaResult<()>);
+
+/// The signature of a command completion function
+pub type CompleteFn = maybe_send_box!(dyn Fn(&Lua, CompleteArgs) -> LuaResult<Vec<String>>);
+
+/// The signature of a command preview callback.<br>
+/// Receives the command arguments, the preview namespace and the preview buffer, if `inccommand=split`.
+pub type PreviewFn = maybe_send_box!(
+    dyn Fn(&Lua, CommandArgs, LuaInteger, Option<Buffer>) -> LuaResult<PreviewResult>
+);
+
+/// How arguments to a command are completed
+pub enum Complete {
+    /// One of the builtin completion types, like `"file"` or `"buffer"`, see `:h :command-complete`
+    Builtin(String),
+    /// A Rust function returning the completion candidates
+    Function(CompleteFn),
+}
+
+impl fmt::Debug for Complete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Complete::Builtin(name) => f.debug_tuple("Builtin").field(name).finish(),
+            Complete::Function(_) => f.debug_tuple("Function").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// `range` and `count` are mutually exclusive, so the builder keeps at most one of them
+#[derive(Debug)]
+enum RangeOrCount {
+    Range(CommandRange),
+    Count(u64),
+}
+
+enum CommandAction {
+    Handler(CommandHandler),
+    Command(String),
+}
+
+/// Builder for user commands, passed to [`nvim_create_user_command`] or [`nvim_buf_create_user_command`]
+pub struct CommandBuilder {
+    name: String,
+    nargs: Nargs,
+    range: Option<RangeOrCount>,
+    bang: bool,
+    bar: bool,
+    register: bool,
+    keepscript: bool,
+    buffer: Option<Buffer>,
+    desc: Option<String>,
+    force: bool,
+    complete: Option<Complete>,
+    preview: Option<PreviewFn>,
+    action: Option<CommandAction>,
+}
+
+impl fmt::Debug for CommandBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandBuilder")
+            .field("name", &self.name)
+            .field("nargs", &self.nargs)
+            .field("range", &self.range)
+            .field("bang", &self.bang)
+            .field("bar", &self.bar)
+            .field("register", &self.register)
+            .field("keepscript", &self.keepscript)
+            .field("buffer", &self.buffer)
+            .field("desc", &self.desc)
+            .field("force", &self.force)
+            .field("complete", &self.complete)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CommandBuilder {
+    /// Creates a new command builder. The name must start with an uppercase letter.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            nargs: Nargs::default(),
+            range: None,
+            bang: false,
+            bar: false,
+            register: false,
+            keepscript: false,
+            buffer: None,
+            desc: None,
+            force: true,
+            complete: None,
+            preview: None,
+            action: None,
+        }
+    }
+
+    /// Sets the number of arguments the command accepts
+    pub fn with_nargs(mut self, nargs: Nargs) -> Self {
+        self.nargs = nargs;
+        self
+    }
+
+    /// Allows the command to take a range, replacing any count set with [`CommandBuilder::with_count`]
+    pub fn with_range(mut self, range: CommandRange) -> Self {
+        self.range = Some(RangeOrCount::Range(range));
+        self
+    }
+
+    /// Allows the command to take a count, with the given default,
+    /// replacing any range set with [`CommandBuilder::with_range`]
+    pub fn with_count(mut self, default: u64) -> Self {
+        self.range = Some(RangeOrCount::Count(default));
+        self
+    }
+
+    /// Allows the command to take a `!`
+    pub fn with_bang(mut self, bang: bool) -> Self {
+        self.bang = bang;
+        self
+    }
+
+    /// Allows the command to be followed by `|` and another command
+    pub fn with_bar(mut self, bar: bool) -> Self {
+        self.bar = bar;
+        self
+    }
+
+    /// Allows the first argument to be a register name
+    pub fn with_register(mut self, register: bool) -> Self {
+        self.register = register;
+        self
+    }
+
+    /// Use the location of where the command was invoked for verbose messages
+    pub fn with_keepscript(mut self, keepscript: bool) -> Self {
+        self.keepscript = keepscript;
+        self
+    }
+
+    /// Makes the command local to a buffer
+    pub fn with_buffer(mut self, buffer: Buffer) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Sets the description of the command
+    pub fn with_desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = Some(desc.into());
+        self
+    }
+
+    /// Overrides an existing command with the same name, defaults to `true`
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Uses one of the builtin completion types, like `"file"` or `"buffer"`
+    pub fn with_complete(mut self, complete: impl Into<String>) -> Self {
+        self.complete = Some(Complete::Builtin(complete.into()));
+        self
+    }
+
+    /// Completes arguments using a Rust function returning the candidates
+    pub fn with_complete_fn<F>(mut self, complete: F) -> Self
+    where
+        F: Fn(&Lua, CompleteArgs) -> LuaResult<Vec<String>> + MaybeSend + 'static,
+    {
+        self.complete = Some(Complete::Function(Box::new(complete)));
+        self
+    }
+
+    /// Sets the preview callback used by `inccommand`, see `:h :command-preview`
+    pub fn with_preview<F>(mut self, preview: F) -> Self
+    where
+        F: Fn(&Lua, CommandArgs, LuaInteger, Option<Buffer>) -> LuaResult<PreviewResult>
+            + MaybeSend
+            + 'static,
+    {
+        self.preview = Some(Box::new(preview));
+        self
+    }
+
+    /// Sets the Rust function to run when the command is executed
+    pub fn with_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Lua, CommandArgs) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.action = Some(CommandAction::Handler(Box::new(handler)));
+        self
+    }
+
+    /// Sets an Ex command to run instead of a Rust function
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.action = Some(CommandAction::Command(command.into()));
+        self
+    }
+
+    /// Creates the command, buffer-local if [`CommandBuilder::with_buffer`] was used
+    pub fn create(self, lua: &Lua) -> LuaResult<()> {
+        match self.buffer {
+            Some(buffer) => nvim_buf_create_user_command(lua, buffer, self),
+            None => nvim_create_user_command(lua, self),
+        }
+    }
+
+    fn into_parts(self, lua: &Lua) -> LuaResult<(String, LuaValue<'_>, LuaTable<'_>)> {
+        let command = match self.action {
+            Some(CommandAction::Handler(handler)) => {
+                lua.pack(lua.create_function(move |lua, args: CommandArgs| handler(lua, args))?)?
+            }
+            Some(CommandAction::Command(command)) => lua.pack(command)?,
+            None => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Command {} has no handler",
+                    self.name
+                )))
+            }
+        };
+
+        let opts = lua.create_table()?;
+        opts.set("nargs", self.nargs)?;
+        match self.range {
+            Some(RangeOrCount::Range(range)) => opts.set("range", range)?,
+            Some(RangeOrCount::Count(count)) => opts.set("count", count)?,
+            None => {}
+        }
+        opts.set("bang", self.bang)?;
+        opts.set("bar", self.bar)?;
+        opts.set("register", self.register)?;
+        opts.set("keepscript", self.keepscript)?;
+        opts.set("desc", self.desc)?;
+        opts.set("force", self.force)?;
+        match self.complete {
+            Some(Complete::Builtin(name)) => opts.set("complete", name)?,
+            Some(Complete::Function(complete)) => opts.set(
+                "complete",
+                lua.create_function(
+                    move |lua, (arg_lead, cmd_line, cursor_pos): (String, String, u64)| {
+                        complete(
+                            lua,
+                            CompleteArgs {
+                                arg_lead,
+                                cmd_line,
+                                cursor_pos,
+                            },
+                        )
+                    },
+                )?,
+            )?,
+            None => {}
+        }
+        if let Some(preview) = self.preview {
+            opts.set(
+                "preview",
+                lua.create_function(
+                    move |lua, (args, ns, buf): (CommandArgs, LuaInteger, Option<Buffer>)| {
+                        preview(lua, args, ns, buf)
+                    },
+                )?,
+            )?;
+        }
+        Ok((self.name, command, opts))
+    }
+}
+
+/// Corresponds to `vim.api.nvim_create_user_command`
+pub fn nvim_create_user_command(lua: &Lua, command: CommandBuilder) -> LuaResult<()> {
+    let (name, command, opts) = command.into_parts(lua)?;
+    vim::api::get(lua)?.call_function("nvim_create_user_command", (name, command, opts))
+}
+
+/// Corresponds to `vim.api.nvim_buf_create_user_command`
+pub fn nvim_buf_create_user_command(
+    lua: &Lua,
+    buffer: Buffer,
+    command: CommandBuilder,
+) -> LuaResult<()> {
+    let (name, command, opts) = command.into_parts(lua)?;
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_create_user_command",
+        (buffer, name, command, opts),
+    )
+}
+
+/// Corresponds to `vim.api.nvim_del_user_command`
+pub fn nvim_del_user_command(lua: &Lua, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_del_user_command", name)
+}
+
+/// Corresponds to `vim.api.nvim_buf_del_user_command`
+pub fn nvim_buf_del_user_command(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_buf_del_user_command", (buffer, name))
+}
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, ops::RangeBounds};
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use vim::api::{line_range, Buffer, Chunk, Position};
+
+/// Where virtual text is displayed, see `:h nvim_buf_set_extmark()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VirtTextPos {
+    /// After the end of the line
+    Eol,
+    /// Over the text at the mark's position, hiding it
+    Overlay,
+    /// Right-aligned in the window
+    RightAlign,
+    /// Inside the text at the mark's position, shifting it to the right
+    Inline,
+}
+
+/// How the highlights of virtual text are combined with the text below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HlMode {
+    Replace,
+    Combine,
+    Blend,
+}
+
+/// Options for [`nvim_buf_set_extmark`], see `:h nvim_buf_set_extmark()`
+///
+/// This is also the shape of [`Extmark::details`]. Fields left as `None` use Neovim's defaults.
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{self, Buffer, Chunk, ExtmarkOpts, Position, VirtTextPos};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let ns = api::nvim_create_namespace(lua, "myplugin")?;
+///     let buf = Buffer::current(lua)?;
+///     buf.set_extmark(lua, ns, Position::new(0, 0), ExtmarkOpts {
+///         virt_text: Some(vec![Chunk::from(("3 errors", "DiagnosticError"))]),
+///         virt_text_pos: Some(VirtTextPos::Eol),
+///         sign_text: Some("E".to_owned()),
+///         ..Default::default()
+///     })?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtmarkOpts {
+    /// The id of the mark to create or move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<LuaInteger>,
+    /// The 0-based end row of the mark, exclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_row: Option<u64>,
+    /// The 0-based end column of the mark in bytes, exclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_col: Option<u64>,
+    /// Highlights the range between the start and the end of the mark
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hl_group: Option<String>,
+    /// Continue the highlight until the end of the screen line, for marks spanning multiple lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hl_eol: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hl_mode: Option<HlMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_text: Option<Vec<Chunk>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_text_pos: Option<VirtTextPos>,
+    /// Places virtual text at a fixed window column instead of using `virt_text_pos`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_text_win_col: Option<u64>,
+    /// Hides the virtual text when the text below it is hidden
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_text_hide: Option<bool>,
+    /// Virtual lines to add below the mark, each a list of chunks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_lines: Option<Vec<Vec<Chunk>>>,
+    /// Places `virt_lines` above the mark instead of below
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_lines_above: Option<bool>,
+    /// Places `virt_lines` in the leftmost column of the window, bypassing the sign and number columns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virt_lines_leftcol: Option<bool>,
+    /// Only draws the mark for the current redraw, used in `nvim_set_decoration_provider` callbacks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ephemeral: Option<bool>,
+    /// Whether the mark moves to the right when text is inserted at its position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_gravity: Option<bool>,
+    /// Whether the end of the mark moves to the right when text is inserted at its position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_right_gravity: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u64>,
+    /// Whether to error if the position is outside the buffer, defaults to `true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+    /// Up to two display cells of text to show in the sign column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign_hl_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number_hl_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_hl_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursorline_hl_group: Option<String>,
+    /// Conceals the range of the mark, replacing it with this character when it is not empty.
+    /// Requires `'conceallevel'`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conceal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spell: Option<bool>,
+}
+
+impl<'a> ToLua<'a> for ExtmarkOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        vim::to_api_value(lua, &self)
+    }
+}
+
+impl<'a> FromLua<'a> for ExtmarkOpts {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        vim::from_api_value(lua, lua_value)
+    }
+}
+
+/// An extmark returned by [`nvim_buf_get_extmarks`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extmark {
+    pub id: LuaInteger,
+    pub pos: Position,
+    /// Only set when the marks were requested with `details`
+    pub details: Option<ExtmarkOpts>,
+}
+
+impl Extmark {
+    /// Builds a mark from the `[row, col, details?]` part of the tables returned by the api
+    fn from_parts<'a>(id: LuaInteger, parts: &[LuaValue<'a>], lua: &'a Lua) -> LuaResult<Self> {
+        match parts {
+            [row, col, rest @ ..] => Ok(Extmark {
+                id,
+                pos: Position::new(
+                    u64::from_lua(row.clone(), lua)?,
+                    u64::from_lua(col.clone(), lua)?,
+                ),
+                details: match rest.first() {
+                    Some(details) => Some(ExtmarkOpts::from_lua(details.clone(), lua)?),
+                    None => None,
+                },
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "table",
+                to: "Extmark",
+                message: Some("Expected a table of at least two integers".to_string()),
+            }),
+        }
+    }
+}
+
+impl<'a> FromLua<'a> for Extmark {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        let parts = Vec::<LuaValue>::from_lua(lua_value, lua)?;
+        match parts.split_first() {
+            Some((id, rest)) => {
+                Extmark::from_parts(LuaInteger::from_lua(id.clone(), lua)?, rest, lua)
+            }
+            None => Extmark::from_parts(0, &[], lua),
+        }
+    }
+}
+
+/// Options for [`nvim_buf_get_extmarks`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GetExtmarksOpts {
+    /// The maximum number of marks to return
+    pub limit: Option<u64>,
+    /// Whether to include [`Extmark::details`]
+    pub details: Option<bool>,
+    /// Also return marks that start before the range but overlap it
+    pub overlap: Option<bool>,
+}
+
+impl<'a> ToLua<'a> for GetExtmarksOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        table.set("limit", self.limit)?;
+        table.set("details", self.details)?;
+        table.set("overlap", self.overlap)?;
+        lua.pack(table)
+    }
+}
+
+impl Buffer {
+    /// Creates or updates an extmark, using `vim.api.nvim_buf_set_extmark`
+    pub fn set_extmark(
+        &self,
+        lua: &Lua,
+        ns_id: LuaInteger,
+        pos: Position,
+        opts: ExtmarkOpts,
+    ) -> LuaResult<LuaInteger> {
+        nvim_buf_set_extmark(lua, *self, ns_id, pos, opts)
+    }
+
+    /// Gets the extmarks of a namespace in a range, using `vim.api.nvim_buf_get_extmarks`
+    pub fn get_extmarks(
+        &self,
+        lua: &Lua,
+        ns_id: LuaInteger,
+        start: Position,
+        end: Option<Position>,
+        opts: GetExtmarksOpts,
+    ) -> LuaResult<Vec<Extmark>> {
+        nvim_buf_get_extmarks(lua, *self, ns_id, start, end, opts)
+    }
+
+    /// Removes an extmark, using `vim.api.nvim_buf_del_extmark`
+    pub fn del_extmark(&self, lua: &Lua, ns_id: LuaInteger, id: LuaInteger) -> LuaResult<bool> {
+        nvim_buf_del_extmark(lua, *self, ns_id, id)
+    }
+
+    /// Clears a namespace's extmarks and highlights in a range of rows, using `vim.api.nvim_buf_clear_namespace`
+    pub fn clear_namespace(
+        &self,
+        lua: &Lua,
+        ns_id: LuaInteger,
+        rows: impl RangeBounds<u64>,
+    ) -> LuaResult<()> {
+        nvim_buf_clear_namespace(lua, *self, ns_id, rows)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_create_namespace`
+/// Returns the existing namespace if one with the same name exists. An empty name creates an anonymous namespace.
+pub fn nvim_create_namespace(lua: &Lua, name: &str) -> LuaResult<LuaInteger> {
+    vim::api::get(lua)?.call_function("nvim_create_namespace", name)
+}
+
+/// Corresponds to `vim.api.nvim_get_namespaces`
+pub fn nvim_get_namespaces(lua: &Lua) -> LuaResult<HashMap<String, LuaInteger>> {
+    vim::api::get(lua)?.call_function("nvim_get_namespaces", ())
+}
+
+/// Corresponds to `vim.api.nvim_buf_set_extmark`
+/// Returns the id of the mark.
+pub fn nvim_buf_set_extmark(
+    lua: &Lua,
+    buffer: Buffer,
+    ns_id: LuaInteger,
+    pos: Position,
+    opts: ExtmarkOpts,
+) -> LuaResult<LuaInteger> {
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_set_extmark",
+        (buffer, ns_id, pos.row, pos.col, opts),
+    )
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_extmark_by_id`
+/// Returns `None` if the mark doesn't exist.
+pub fn nvim_buf_get_extmark_by_id(
+    lua: &Lua,
+    buffer: Buffer,
+    ns_id: LuaInteger,
+    id: LuaInteger,
+    details: bool,
+) -> LuaResult<Option<Extmark>> {
+    let opts = lua.create_table()?;
+    opts.set("details", details)?;
+    let mark: Vec<LuaValue> = vim::api::get(lua)?
+        .call_function("nvim_buf_get_extmark_by_id", (buffer, ns_id, id, opts))?;
+    if mark.is_empty() {
+        Ok(None)
+    } else {
+        Extmark::from_parts(id, &mark, lua).map(Some)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_extmarks`
+/// `start` and `end` are inclusive, and an `end` of `None` means the end of the buffer.
+pub fn nvim_buf_get_extmarks(
+    lua: &Lua,
+    buffer: Buffer,
+    ns_id: LuaInteger,
+    start: Position,
+    end: Option<Position>,
+    opts: GetExtmarksOpts,
+) -> LuaResult<Vec<Extmark>> {
+    let end = match end {
+        Some(end) => lua.pack(end)?,
+        None => lua.pack(-1)?,
+    };
+    vim::api::get(lua)?.call_function("nvim_buf_get_extmarks", (buffer, ns_id, start, end, opts))
+}
+
+/// Corresponds to `vim.api.nvim_buf_del_extmark`
+/// Returns `false` if the mark didn't exist.
+pub fn nvim_buf_del_extmark(
+    lua: &Lua,
+    buffer: Buffer,
+    ns_id: LuaInteger,
+    id: LuaInteger,
+) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_buf_del_extmark", (buffer, ns_id, id))
+}
+
+/// Corresponds to `vim.api.nvim_buf_clear_namespace`
+/// `rows` are 0-based, an unbounded end means the end of the buffer. `ns_id` of `-1` clears all namespaces.
+pub fn nvim_buf_clear_namespace(
+    lua: &Lua,
+    buffer: Buffer,
+    ns_id: LuaInteger,
+    rows: impl RangeBounds<u64>,
+) -> LuaResult<()> {
+    let (line_start, line_end) = line_range(rows);
+    vim::api::get(lua)?.call_function(
+        "nvim_buf_clear_namespace",
+        (buffer, ns_id, line_start, line_end),
+    )
+}
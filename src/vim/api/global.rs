@@ -1,11 +1,13 @@
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 use crate::prelude::*;
+use vim::api::{Buffer, Tabpage, Window};
+use vim::keymap::{KeymapOpts, Mode};
 
 /// Corresponds to `vim.api.nvim_get_current_buf`
-pub fn nvim_get_current_buf(lua: &Lua) -> LuaResult<i64> {
+pub fn nvim_get_current_buf(lua: &Lua) -> LuaResult<Buffer> {
     vim::api::get(lua)?.call_function("nvim_get_current_buf", ())
 }
 
@@ -15,20 +17,103 @@ pub fn nvim_get_current_line(lua: &Lua) -> LuaResult<String> {
 }
 
 /// Corresponds to `vim.api.nvim_get_current_tabpage`
-pub fn nvim_get_current_tabpage(lua: &Lua) -> LuaResult<i64> {
+pub fn nvim_get_current_tabpage(lua: &Lua) -> LuaResult<Tabpage> {
     vim::api::get(lua)?.call_function("nvim_get_current_tabpage", ())
 }
 
 /// Corresponds to `vim.api.nvim_get_current_win`
-pub fn nvim_get_current_win(lua: &Lua) -> LuaResult<i64> {
+pub fn nvim_get_current_win(lua: &Lua) -> LuaResult<Window> {
     vim::api::get(lua)?.call_function("nvim_get_current_win", ())
 }
 
+/// Corresponds to `vim.api.nvim_set_current_buf`
+pub fn nvim_set_current_buf(lua: &Lua, buffer: Buffer) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_current_buf", buffer)
+}
+
+/// Corresponds to `vim.api.nvim_set_current_win`
+pub fn nvim_set_current_win(lua: &Lua, window: Window) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_current_win", window)
+}
+
+/// Corresponds to `vim.api.nvim_set_current_tabpage`
+pub fn nvim_set_current_tabpage(lua: &Lua, tabpage: Tabpage) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_current_tabpage", tabpage)
+}
+
 /// Corresponds to `vim.api.nvim_list_bufs`
-pub fn nvim_list_bufs(lua: &Lua) -> LuaResult<Vec<LuaInteger>> {
+pub fn nvim_list_bufs(lua: &Lua) -> LuaResult<Vec<Buffer>> {
     vim::api::get(lua)?.call_function("nvim_list_bufs", ())
 }
 
+/// Corresponds to `vim.api.nvim_list_wins`
+pub fn nvim_list_wins(lua: &Lua) -> LuaResult<Vec<Window>> {
+    vim::api::get(lua)?.call_function("nvim_list_wins", ())
+}
+
+/// Corresponds to `vim.api.nvim_list_tabpages`
+pub fn nvim_list_tabpages(lua: &Lua) -> LuaResult<Vec<Tabpage>> {
+    vim::api::get(lua)?.call_function("nvim_list_tabpages", ())
+}
+
+/// Options for `nvim_get_option_value` and `nvim_set_option_value`
+#[derive(Debug, Clone, Default)]
+pub struct OptionValueOpts {
+    /// Either `"global"` or `"local"`, analogous to `:setglobal` and `:setlocal`
+    pub scope: Option<String>,
+    /// The window to get or set a window-local option for
+    pub win: Option<Window>,
+    /// The buffer to get or set a buffer-local option for
+    pub buf: Option<Buffer>,
+}
+
+impl OptionValueOpts {
+    /// Options targeting a buffer-local option of `buf`
+    pub fn buf(buf: Buffer) -> Self {
+        Self {
+            buf: Some(buf),
+            ..Default::default()
+        }
+    }
+
+    /// Options targeting a window-local option of `win`
+    pub fn win(win: Window) -> Self {
+        Self {
+            win: Some(win),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> ToLua<'a> for OptionValueOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        table.set("scope", self.scope)?;
+        table.set("win", self.win)?;
+        table.set("buf", self.buf)?;
+        lua.pack(table)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_get_option_value`
+pub fn nvim_get_option_value<'a, T: FromLua<'a>>(
+    lua: &'a Lua,
+    name: &str,
+    opts: OptionValueOpts,
+) -> LuaResult<T> {
+    vim::api::get(lua)?.call_function("nvim_get_option_value", (name, opts))
+}
+
+/// Corresponds to `vim.api.nvim_set_option_value`
+pub fn nvim_set_option_value<'a>(
+    lua: &'a Lua,
+    name: &str,
+    value: impl ToLua<'a>,
+    opts: OptionValueOpts,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_option_value", (name, value, opts))
+}
+
 /// Corresponds to `vim.api.nvim_exec`
 pub fn nvim_exec<'a>(lua: &'a Lua, cmd: &str, output: bool) -> LuaResult<LuaValue<'a>> {
     vim::api::get(lua)?.call_function("nvim_exec", (cmd, output))
@@ -58,7 +143,7 @@ pub fn nvim_get_mode(lua: &Lua) -> LuaResult<GetModeRes> {
 /// Corresponds to `vim.api.nvim_stats`
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_stats(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn nvim_stats(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_stats")?
         .call(())
@@ -76,7 +161,7 @@ pub fn nvim_chan_send(lua: &Lua, chan: i32, data: &str) -> LuaResult<()> {
 /// Corresponds to `vim.api.nvim_create_buf`
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_create_buf(lua: &Lua, listed: bool, scratch: bool) -> LuaResult<i64> {
+pub fn nvim_create_buf(lua: &Lua, listed: bool, scratch: bool) -> LuaResult<Buffer> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_create_buf")?
         .call((listed, scratch))
@@ -92,9 +177,7 @@ pub fn nvim_del_current_line(lua: &Lua) -> LuaResult<()> {
 }
 
 /// Corresponds to `vim.api.nvim_del_keymap`
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_del_keymap(lua: &Lua, mode: &str, lhs: &str) -> LuaResult<()> {
+pub fn nvim_del_keymap(lua: &Lua, mode: Mode, lhs: &str) -> LuaResult<()> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_del_keymap")?
         .call((mode, lhs))
@@ -207,13 +290,11 @@ impl<'a> FromLua<'a> for HighlightInfo {
                 let group = table.get::<_, String>("group")?;
                 Ok(HighlightInfo { start, group })
             }
-            _ => {
-                return Err(LuaError::FromLuaConversionError {
-                    from: "LuaValue",
-                    to: "HighlightInfo",
-                    message: Some("Expected LuaValue::Table".to_string()),
-                })
-            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "LuaValue",
+                to: "HighlightInfo",
+                message: Some("Expected LuaValue::Table".to_string()),
+            }),
         }
     }
 }
@@ -233,13 +314,11 @@ impl<'a> FromLua<'a> for EvalStatuslineRes {
                     highlights,
                 })
             }
-            _ => {
-                return Err(LuaError::FromLuaConversionError {
-                    from: "LuaValue",
-                    to: "EvalStatuslineRes",
-                    message: Some("Expected LuaValue::Table".to_string()),
-                })
-            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "LuaValue",
+                to: "EvalStatuslineRes",
+                message: Some("Expected LuaValue::Table".to_string()),
+            }),
         }
     }
 }
@@ -274,7 +353,7 @@ pub fn nvim_exec_lua<'a>(
 // TODO: return type
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_api_info(lua: &Lua) -> LuaResult<(LuaInteger, LuaTable)> {
+pub fn nvim_get_api_info(lua: &Lua) -> LuaResult<(LuaInteger, LuaTable<'_>)> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_get_api_info")?
         .call(())
@@ -317,13 +396,11 @@ impl<'a> FromLua<'a> for ChannelInfo<'a> {
                     client,
                 })
             }
-            _ => {
-                return Err(LuaError::FromLuaConversionError {
-                    from: "LuaValue",
-                    to: "ChannelInfo",
-                    message: Some("Expected LuaValue::Table".to_string()),
-                })
-            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "LuaValue",
+                to: "ChannelInfo",
+                message: Some("Expected LuaValue::Table".to_string()),
+            }),
         }
     }
 }
@@ -337,26 +414,6 @@ pub fn nvim_get_chan_info<'a>(lua: &'a Lua, chan: LuaInteger) -> LuaResult<Chann
         .call(chan)
 }
 
-/// Corresponds to `vim.api.nvim_get_color_by_name`
-/// Returns 24-bit RGB value or -1 for invalid color.
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_color_by_name(lua: &Lua, name: &str) -> LuaResult<i64> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_color_by_name")?
-        .call(name)
-}
-
-/// Corresponds to `vim.api.nvim_get_color_map`
-// TODO: return type
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_color_map(lua: &Lua) -> LuaResult<LuaTable> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_color_map")?
-        .call(())
-}
-
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
 pub type GetContextOpt = Vec<String>;
@@ -365,104 +422,86 @@ pub type GetContextOpt = Vec<String>;
 // TODO: return type
 #[cfg(feature = "unstable")]
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_context(lua: &Lua, opt: Option<GetContextOpt>) -> LuaResult<LuaTable> {
+pub fn nvim_get_context(lua: &Lua, opt: Option<GetContextOpt>) -> LuaResult<LuaTable<'_>> {
     vim::api::get(lua)?
         .get::<_, LuaFunction>("nvim_get_context")?
         .call(opt)
 }
 
-/// Corresponds to `vim.api.nvim_get_hl_by_id`
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_hl_by_id<'a>(lua: &'a Lua, id: LuaInteger, rgb: bool) -> LuaResult<LuaTable<'a>> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_hl_by_id")?
-        .call((id, rgb))
-}
-
-/// Corresponds to `vim.api.nvim_get_hl_by_name`
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_hl_by_name<'a>(lua: &'a Lua, name: &str, rgb: bool) -> LuaResult<LuaTable<'a>> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_hl_by_name")?
-        .call((name, rgb))
-}
-
-/// Corresponds to `vim.api.nvim_get_hl_id_by_name`
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_hl_id_by_name(lua: &Lua, name: &str) -> LuaResult<i64> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_hl_id_by_name")?
-        .call(name)
-}
-
-#[derive(Debug)]
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
+/// A mapping, as returned by `nvim_get_keymap` and `nvim_buf_get_keymap`
+#[derive(Debug, Clone)]
 pub struct Mapping<'a> {
-    pub buffer: LuaInteger,
-    pub expr: LuaValue<'a>,
     pub lhs: String,
+    /// The raw bytes of `lhs`
     pub lhsraw: Vec<u8>,
+    /// The key sequence the mapping runs, if it isn't a Lua callback
+    pub rhs: Option<String>,
+    /// The Lua callback the mapping runs, if any
+    pub callback: Option<LuaFunction<'a>>,
+    /// The modes the mapping applies to. Neovim combines them into one mapping, like `"nox"` after `:sunmap`.
+    pub mode: Vec<Mode>,
+    /// The options the mapping was created with. `buffer` is `None` for global mappings.
+    pub opts: KeymapOpts,
     pub lnum: LuaInteger,
-    pub mode: String,
-    pub noremap: bool,
-    pub nowait: bool,
-    pub script: LuaInteger,
     pub sid: LuaInteger,
-    pub silent: bool,
 }
 
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
 impl<'a> FromLua<'a> for Mapping<'a> {
     fn from_lua(lua_value: LuaValue<'a>, _lua: &'a Lua) -> LuaResult<Self> {
+        // Flags are returned as 0 or 1
+        fn flag(table: &LuaTable, key: &str) -> LuaResult<Option<bool>> {
+            Ok(table.get::<_, Option<LuaInteger>>(key)?.map(|v| v != 0))
+        }
+
         match lua_value {
             LuaValue::Table(table) => {
-                let buffer = table.get::<_, LuaInteger>("buffer")?;
-                let expr = table.get::<_, LuaValue>("expr")?;
-                let lhs = table.get::<_, String>("lhs")?;
-                let lhsraw = table.get::<_, Vec<u8>>("lhsraw")?;
-                let lnum = table.get::<_, LuaInteger>("lnum")?;
-                let mode = table.get::<_, String>("mode")?;
-                let noremap = table.get::<_, bool>("noremap")?;
-                let nowait = table.get::<_, bool>("nowait")?;
-                let script = table.get::<_, LuaInteger>("script")?;
-                let sid = table.get::<_, LuaInteger>("sid")?;
-                let silent = table.get::<_, bool>("silent")?;
-                Ok(Mapping {
+                let buffer = match table.get::<_, LuaInteger>("buffer")? {
+                    0 => None,
+                    buf => Some(Buffer::new(buf)),
+                };
+                let opts = KeymapOpts {
                     buffer,
-                    expr,
-                    lhs,
-                    lhsraw,
-                    lnum,
-                    mode,
-                    noremap,
-                    nowait,
-                    script,
-                    sid,
-                    silent,
-                })
-            }
-            _ => {
-                return Err(LuaError::FromLuaConversionError {
-                    from: "LuaValue",
-                    to: "Mapping",
-                    message: Some("Expected LuaValue::Table".to_string()),
+                    silent: flag(&table, "silent")?,
+                    noremap: flag(&table, "noremap")?,
+                    remap: None,
+                    expr: flag(&table, "expr")?,
+                    nowait: flag(&table, "nowait")?,
+                    unique: None,
+                    script: flag(&table, "script")?,
+                    desc: table.get("desc")?,
+                    replace_keycodes: flag(&table, "replace_keycodes")?,
+                };
+                Ok(Mapping {
+                    lhs: table.get("lhs")?,
+                    lhsraw: table
+                        .get::<_, Option<LuaString>>("lhsraw")?
+                        .map(|s| s.as_bytes().to_vec())
+                        .unwrap_or_default(),
+                    rhs: table.get("rhs")?,
+                    callback: table.get("callback")?,
+                    mode: Mode::parse_modes(&table.get::<_, String>("mode")?)?,
+                    opts,
+                    lnum: table.get::<_, Option<LuaInteger>>("lnum")?.unwrap_or(0),
+                    sid: table.get::<_, Option<LuaInteger>>("sid")?.unwrap_or(0),
                 })
             }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "LuaValue",
+                to: "Mapping",
+                message: Some("Expected LuaValue::Table".to_string()),
+            }),
         }
     }
 }
 
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-pub fn nvim_get_keymap<'a>(lua: &'a Lua, mode: &str) -> LuaResult<Vec<Mapping<'a>>> {
-    vim::api::get(lua)?
-        .get::<_, LuaFunction>("nvim_get_keymap")?
-        .call(mode)
+/// Corresponds to `vim.api.nvim_get_keymap`
+pub fn nvim_get_keymap(lua: &Lua, mode: Mode) -> LuaResult<Vec<Mapping<'_>>> {
+    vim::api::get(lua)?.call_function("nvim_get_keymap", mode)
+}
+
+/// Corresponds to `vim.api.nvim_buf_get_keymap`
+pub fn nvim_buf_get_keymap(lua: &Lua, buffer: Buffer, mode: Mode) -> LuaResult<Vec<Mapping<'_>>> {
+    vim::api::get(lua)?.call_function("nvim_buf_get_keymap", (buffer, mode))
 }
 
 #[cfg(feature = "unstable")]
@@ -0,0 +1,295 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::prelude::*;
+use vim::api::Window;
+
+/// A 24-bit RGB color
+///
+/// Parses from `#rrggbb` strings, and serializes to them when passed to Neovim.
+/// Color names like `"Red"` are resolved by [`Rgb::from_name`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Creates a color from a `0xRRGGBB` integer, as used by the Neovim api
+    pub const fn from_u32(rgb: u32) -> Self {
+        Self {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        }
+    }
+
+    /// Converts the color into a `0xRRGGBB` integer
+    pub const fn to_u32(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    /// Resolves a color name like `"Red"` or a `#rrggbb` string, using `vim.api.nvim_get_color_by_name`
+    /// Returns `None` if the name isn't a known color.
+    pub fn from_name(lua: &Lua, name: &str) -> LuaResult<Option<Self>> {
+        match name.parse() {
+            Ok(rgb) => Ok(Some(rgb)),
+            Err(_) => nvim_get_color_by_name(lua, name),
+        }
+    }
+}
+
+/// An error returned when parsing an [`Rgb`] from a string that isn't `#rrggbb`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRgbError(String);
+
+impl fmt::Display for ParseRgbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid color {:?}, expected #rrggbb", self.0)
+    }
+}
+
+impl std::error::Error for ParseRgbError {}
+
+impl FromStr for Rgb {
+    type Err = ParseRgbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                u32::from_str_radix(hex, 16)
+                    .map(Rgb::from_u32)
+                    .map_err(|_| ParseRgbError(s.to_owned()))
+            }
+            _ => Err(ParseRgbError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl From<u32> for Rgb {
+    fn from(rgb: u32) -> Self {
+        Rgb::from_u32(rgb)
+    }
+}
+
+impl From<Rgb> for u32 {
+    fn from(rgb: Rgb) -> Self {
+        rgb.to_u32()
+    }
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawRgb {
+            Int(u32),
+            Str(String),
+        }
+
+        match RawRgb::deserialize(deserializer)? {
+            RawRgb::Int(rgb) => Ok(Rgb::from_u32(rgb)),
+            RawRgb::Str(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl<'a> ToLua<'a> for Rgb {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.to_string().to_lua(lua)
+    }
+}
+
+impl<'a> FromLua<'a> for Rgb {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        lua.from_value(lua_value)
+    }
+}
+
+/// A highlight group definition, see `:h nvim_set_hl()`
+///
+/// Fields left as `None` are not set. This is also what [`nvim_get_hl`] returns,
+/// and accepts the `foreground`/`background`/`special` keys of the older `nvim_get_hl_by_*` functions.
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{self, Highlight, Rgb};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let comment = api::nvim_get_hl(lua, 0, "Comment")?;
+///     api::nvim_set_hl(lua, 0, "MyPluginHint", Highlight {
+///         fg: comment.fg,
+///         bg: Some("#1e1e2e".parse().map_err(LuaError::external)?),
+///         italic: Some(true),
+///         default: Some(true),
+///         ..Default::default()
+///     })?;
+///     api::nvim_set_hl(lua, 0, "MyPluginBorder", Highlight::link("FloatBorder"))
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Highlight {
+    #[serde(alias = "foreground", skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Rgb>,
+    #[serde(alias = "background", skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Rgb>,
+    /// The color of underlines and undercurls
+    #[serde(alias = "special", skip_serializing_if = "Option::is_none")]
+    pub sp: Option<Rgb>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ctermfg: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ctermbg: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undercurl: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underdouble: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underdotted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underdashed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverse: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standout: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nocombine: Option<bool>,
+    /// Makes this group a link to another group, other attributes are ignored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Don't override an existing definition, like `:hi default`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<bool>,
+    /// Blend level for floating windows and the popup menu, between 0 and 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend: Option<u8>,
+}
+
+impl Highlight {
+    /// A highlight group that links to another group
+    pub fn link(group: &str) -> Self {
+        Self {
+            link: Some(group.to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> ToLua<'a> for Highlight {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        vim::to_api_value(lua, &self)
+    }
+}
+
+impl<'a> FromLua<'a> for Highlight {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        match lua_value {
+            // Older versions of Neovim mark empty highlight definitions with `[true] = 6`,
+            // deserialize a copy without it so the caller's table isn't modified
+            LuaValue::Table(table) => {
+                let fields = lua.create_table()?;
+                for pair in table.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = pair?;
+                    if !matches!(key, LuaValue::Boolean(_)) {
+                        fields.raw_set(key, value)?;
+                    }
+                }
+                vim::from_api_value(lua, LuaValue::Table(fields))
+            }
+            value => vim::from_api_value(lua, value),
+        }
+    }
+}
+
+/// Corresponds to `vim.api.nvim_set_hl`
+/// `ns_id` of `0` sets the global highlight group.
+pub fn nvim_set_hl(lua: &Lua, ns_id: LuaInteger, name: &str, hl: Highlight) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_hl", (ns_id, name, hl))
+}
+
+/// Corresponds to `vim.api.nvim_get_hl` with `opts.name` set
+/// `ns_id` of `0` gets the global highlight group. Links are not followed.
+pub fn nvim_get_hl(lua: &Lua, ns_id: LuaInteger, name: &str) -> LuaResult<Highlight> {
+    let opts = lua.create_table()?;
+    opts.set("name", name)?;
+    opts.set("link", true)?;
+    vim::api::get(lua)?.call_function("nvim_get_hl", (ns_id, opts))
+}
+
+/// Corresponds to `vim.api.nvim_get_hl` without `opts.name`, returning every group in the namespace
+pub fn nvim_get_hl_all(lua: &Lua, ns_id: LuaInteger) -> LuaResult<HashMap<String, Highlight>> {
+    vim::api::get(lua)?.call_function("nvim_get_hl", (ns_id, lua.create_table()?))
+}
+
+/// Corresponds to `vim.api.nvim_set_hl_ns`
+/// Sets the active highlight namespace for all windows.
+pub fn nvim_set_hl_ns(lua: &Lua, ns_id: LuaInteger) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_set_hl_ns", ns_id)
+}
+
+/// Corresponds to `vim.api.nvim_win_set_hl_ns`
+/// Sets the highlight namespace for a window, overriding the one set by [`nvim_set_hl_ns`].
+pub fn nvim_win_set_hl_ns(lua: &Lua, window: Window, ns_id: LuaInteger) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_hl_ns", (window, ns_id))
+}
+
+/// Corresponds to `vim.api.nvim_get_hl_by_id` with `rgb = true`
+/// Without it, the colors would be cterm palette indexes instead of [`Rgb`] colors.
+pub fn nvim_get_hl_by_id(lua: &Lua, id: LuaInteger) -> LuaResult<Highlight> {
+    vim::api::get(lua)?.call_function("nvim_get_hl_by_id", (id, true))
+}
+
+/// Corresponds to `vim.api.nvim_get_hl_by_name` with `rgb = true`
+/// Without it, the colors would be cterm palette indexes instead of [`Rgb`] colors.
+pub fn nvim_get_hl_by_name(lua: &Lua, name: &str) -> LuaResult<Highlight> {
+    vim::api::get(lua)?.call_function("nvim_get_hl_by_name", (name, true))
+}
+
+/// Corresponds to `vim.api.nvim_get_hl_id_by_name`
+pub fn nvim_get_hl_id_by_name(lua: &Lua, name: &str) -> LuaResult<LuaInteger> {
+    vim::api::get(lua)?.call_function("nvim_get_hl_id_by_name", name)
+}
+
+/// Corresponds to `vim.api.nvim_get_color_by_name`
+/// Returns `None` for unknown colors.
+pub fn nvim_get_color_by_name(lua: &Lua, name: &str) -> LuaResult<Option<Rgb>> {
+    let rgb: i64 = vim::api::get(lua)?.call_function("nvim_get_color_by_name", name)?;
+    Ok(u32::try_from(rgb).ok().map(Rgb::from_u32))
+}
+
+/// Corresponds to `vim.api.nvim_get_color_map`
+pub fn nvim_get_color_map(lua: &Lua) -> LuaResult<HashMap<String, Rgb>> {
+    let map: HashMap<String, u32> = vim::api::get(lua)?.call_function("nvim_get_color_map", ())?;
+    Ok(map
+        .into_iter()
+        .map(|(name, rgb)| (name, Rgb::from_u32(rgb)))
+        .collect())
+}
@@ -3,13 +3,37 @@ use crate::prelude::*;
 mod global;
 pub use global::*;
 
+mod position;
+pub use position::*;
+
 mod buffer;
 pub use buffer::*;
 
+mod buf_attach;
+pub use buf_attach::*;
+
 mod window;
 pub use window::*;
 
+mod tabpage;
+pub use tabpage::*;
+
+mod win_config;
+pub use win_config::*;
+
+mod extmark;
+pub use extmark::*;
+
+mod highlight;
+pub use highlight::*;
+
+pub mod autocmd;
+pub use autocmd::*;
+
+pub mod command;
+pub use command::*;
+
 /// Gets the `vim.api` table
-pub fn get(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     vim::get(lua)?.get::<_, LuaTable>("api")
 }
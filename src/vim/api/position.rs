@@ -0,0 +1,249 @@
+use std::ops::{Bound, RangeBounds};
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// The unit a column is measured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnUnit {
+    /// UTF-8 bytes, used by the Neovim api
+    Byte,
+    /// Unicode scalar values, used by `charcol()` and `getcharpos()`
+    Char,
+    /// UTF-16 code units, used by LSP
+    Utf16,
+}
+
+impl ColumnUnit {
+    fn measure(self, c: char) -> u64 {
+        match self {
+            ColumnUnit::Byte => c.len_utf8() as u64,
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16() as u64,
+        }
+    }
+
+    /// Converts a byte column in `line` into this unit.
+    /// Returns `None` if the column is past the end of the line or inside a character.
+    pub fn from_byte_col(self, line: &str, col: u64) -> Option<u64> {
+        let prefix = line.get(..col as usize)?;
+        Some(prefix.chars().map(|c| self.measure(c)).sum())
+    }
+
+    /// Converts a column in this unit into a byte column in `line`.
+    /// Returns `None` if the column is past the end of the line or inside a character.
+    pub fn to_byte_col(self, line: &str, col: u64) -> Option<u64> {
+        let mut units = 0;
+        for (byte, c) in line.char_indices() {
+            if units == col {
+                return Some(byte as u64);
+            }
+            units += self.measure(c);
+            if units > col {
+                return None;
+            }
+        }
+        (units == col).then_some(line.len() as u64)
+    }
+}
+
+/// A position in a buffer, with a 0-based row and a 0-based column in bytes
+///
+/// This is the convention of most of the Neovim api, like `nvim_buf_get_text` and extmarks.
+/// Functions using other conventions convert to and from it, for example
+/// [`nvim_win_get_cursor`](crate::vim::api::nvim_win_get_cursor) uses a 1-based row and `getpos()` a 1-based row and column.
+///
+/// Serializes as a `[row, col]` array.
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{ColumnUnit, Position, Window};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let win = Window::current(lua)?;
+///     let cursor = win.get_cursor(lua)?;
+///     let line = win.get_buf(lua)?.get_lines(lua, cursor.row..=cursor.row, true)?.remove(0);
+///     if let Some(col) = cursor.col_in(ColumnUnit::Char, &line) {
+///         log::info(lua, &format!("Cursor is on character {} of the line", col))?;
+///     }
+///     win.set_cursor(lua, Position::new(cursor.row, 0))
+/// }
+/// ```
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(from = "(u64, u64)", into = "(u64, u64)")]
+pub struct Position {
+    /// The 0-based row
+    pub row: u64,
+    /// The 0-based column, in bytes
+    pub col: u64,
+}
+
+impl Position {
+    /// Creates a position from a 0-based row and 0-based byte column
+    pub const fn new(row: u64, col: u64) -> Self {
+        Self { row, col }
+    }
+
+    /// Creates a position from a 1-based row and 0-based byte column, like `nvim_win_get_cursor` and marks.
+    /// Row 0 is clamped to the first row.
+    pub const fn from_cursor(row: u64, col: u64) -> Self {
+        Self::new(row.saturating_sub(1), col)
+    }
+
+    /// Converts into a 1-based row and 0-based byte column, like `nvim_win_set_cursor` and marks
+    pub const fn to_cursor(self) -> (u64, u64) {
+        (self.row + 1, self.col)
+    }
+
+    /// Creates a position from a 1-based row and 1-based byte column, like `getpos()` and `line()`/`col()`.
+    /// Zeros are clamped to the first row and column.
+    pub const fn from_one_based(row: u64, col: u64) -> Self {
+        Self::new(row.saturating_sub(1), col.saturating_sub(1))
+    }
+
+    /// Converts into a 1-based row and 1-based byte column, like `setpos()` and `cursor()`
+    pub const fn to_one_based(self) -> (u64, u64) {
+        (self.row + 1, self.col + 1)
+    }
+
+    /// Gets the column in another unit, given the text of the position's line
+    pub fn col_in(self, unit: ColumnUnit, line: &str) -> Option<u64> {
+        unit.from_byte_col(line, self.col)
+    }
+
+    /// Creates a position from a column in another unit, given the text of the row's line
+    pub fn from_col_in(row: u64, col: u64, unit: ColumnUnit, line: &str) -> Option<Self> {
+        Some(Self::new(row, unit.to_byte_col(line, col)?))
+    }
+
+    /// Converts into an LSP position, given the text of the position's line
+    pub fn to_lsp(self, line: &str) -> Option<LspPosition> {
+        Some(LspPosition {
+            line: self.row as u32,
+            character: self.col_in(ColumnUnit::Utf16, line)? as u32,
+        })
+    }
+
+    /// Creates a position from an LSP position, given the text of its line
+    pub fn from_lsp(pos: LspPosition, line: &str) -> Option<Self> {
+        Self::from_col_in(
+            pos.line as u64,
+            pos.character as u64,
+            ColumnUnit::Utf16,
+            line,
+        )
+    }
+}
+
+impl From<(u64, u64)> for Position {
+    fn from((row, col): (u64, u64)) -> Self {
+        Self::new(row, col)
+    }
+}
+
+impl From<Position> for (u64, u64) {
+    fn from(pos: Position) -> Self {
+        (pos.row, pos.col)
+    }
+}
+
+impl<'a> ToLua<'a> for Position {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        [self.row, self.col].to_lua(lua)
+    }
+}
+
+impl<'a> FromLua<'a> for Position {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        match Vec::<u64>::from_lua(lua_value, lua)?[..] {
+            [row, col] => Ok(Self::new(row, col)),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "table",
+                to: "Position",
+                message: Some("Expected a table of two integers".to_string()),
+            }),
+        }
+    }
+}
+
+/// A range in a buffer between two [`Position`]s, end-exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Range {
+    /// The first position in the range
+    pub start: Position,
+    /// The position just after the range
+    pub end: Position,
+}
+
+impl Range {
+    /// Creates a range from `start` up to, but not including, `end`
+    pub const fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// A range covering whole lines, from the start of `start_row` to the start of `end_row`
+    pub const fn lines(start_row: u64, end_row: u64) -> Self {
+        Self::new(Position::new(start_row, 0), Position::new(end_row, 0))
+    }
+
+    /// Checks if the range covers no text, which is also the case if `end` is before `start`
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Checks if the position is in the range. The end is exclusive, so `contains(range.end)` is false.
+    pub fn contains(&self, pos: Position) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Converts into an LSP range, given the text of the start and end lines
+    pub fn to_lsp(self, start_line: &str, end_line: &str) -> Option<LspRange> {
+        Some(LspRange {
+            start: self.start.to_lsp(start_line)?,
+            end: self.end.to_lsp(end_line)?,
+        })
+    }
+
+    /// Creates a range from an LSP range, given the text of the start and end lines
+    pub fn from_lsp(range: LspRange, start_line: &str, end_line: &str) -> Option<Self> {
+        Some(Self::new(
+            Position::from_lsp(range.start, start_line)?,
+            Position::from_lsp(range.end, end_line)?,
+        ))
+    }
+}
+
+/// A position as used by LSP, with a 0-based line and a 0-based column in UTF-16 code units
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// A range as used by LSP, end-exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// Converts a range of 0-based rows into the `start, end` arguments of `nvim_buf_get_lines` and `nvim_buf_set_lines`
+pub(crate) fn line_range(range: impl RangeBounds<u64>) -> (LuaInteger, LuaInteger) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start as LuaInteger,
+        Bound::Excluded(&start) => start as LuaInteger + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end as LuaInteger + 1,
+        Bound::Excluded(&end) => end as LuaInteger,
+        Bound::Unbounded => -1,
+    };
+    (start, end)
+}
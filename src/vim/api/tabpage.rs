@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use vim::api::Window;
+
+/// A handle to a Neovim tabpage
+///
+/// Tabpage `0` always refers to the current tabpage, see [`Tabpage::CURRENT`].
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::Tabpage;
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     for win in Tabpage::current(lua)?.list_wins(lua)? {
+///         win.set_option(lua, "number", true)?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tabpage(LuaInteger);
+
+impl Tabpage {
+    /// The handle Neovim uses to refer to the current tabpage
+    pub const CURRENT: Tabpage = Tabpage(0);
+
+    /// Creates a tabpage handle from a raw tabpage id
+    pub const fn new(id: LuaInteger) -> Self {
+        Self(id)
+    }
+
+    /// Returns the raw tabpage id
+    pub const fn id(&self) -> LuaInteger {
+        self.0
+    }
+
+    /// Gets the current tabpage, using `vim.api.nvim_get_current_tabpage`
+    pub fn current(lua: &Lua) -> LuaResult<Self> {
+        vim::api::nvim_get_current_tabpage(lua)
+    }
+
+    /// Gets all tabpages, using `vim.api.nvim_list_tabpages`
+    pub fn list(lua: &Lua) -> LuaResult<Vec<Self>> {
+        vim::api::nvim_list_tabpages(lua)
+    }
+
+    /// Makes this the current tabpage, using `vim.api.nvim_set_current_tabpage`
+    pub fn set_current(&self, lua: &Lua) -> LuaResult<()> {
+        vim::api::nvim_set_current_tabpage(lua, *self)
+    }
+
+    /// Gets the windows in the tabpage, using `vim.api.nvim_tabpage_list_wins`
+    pub fn list_wins(&self, lua: &Lua) -> LuaResult<Vec<Window>> {
+        nvim_tabpage_list_wins(lua, *self)
+    }
+
+    /// Gets the current window in the tabpage, using `vim.api.nvim_tabpage_get_win`
+    pub fn get_win(&self, lua: &Lua) -> LuaResult<Window> {
+        nvim_tabpage_get_win(lua, *self)
+    }
+
+    /// Gets the tabpage number, using `vim.api.nvim_tabpage_get_number`
+    pub fn get_number(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_tabpage_get_number(lua, *self)
+    }
+
+    /// Gets a tabpage-scoped (`t:`) variable, using `vim.api.nvim_tabpage_get_var`
+    pub fn get_var<'a, T: FromLua<'a>>(&self, lua: &'a Lua, name: &str) -> LuaResult<T> {
+        nvim_tabpage_get_var(lua, *self, name)
+    }
+
+    /// Sets a tabpage-scoped (`t:`) variable, using `vim.api.nvim_tabpage_set_var`
+    pub fn set_var<'a>(&self, lua: &'a Lua, name: &str, value: impl ToLua<'a>) -> LuaResult<()> {
+        nvim_tabpage_set_var(lua, *self, name, value)
+    }
+
+    /// Removes a tabpage-scoped (`t:`) variable, using `vim.api.nvim_tabpage_del_var`
+    pub fn del_var(&self, lua: &Lua, name: &str) -> LuaResult<()> {
+        nvim_tabpage_del_var(lua, *self, name)
+    }
+
+    /// Checks if the tabpage handle is valid, using `vim.api.nvim_tabpage_is_valid`
+    pub fn is_valid(&self, lua: &Lua) -> LuaResult<bool> {
+        nvim_tabpage_is_valid(lua, *self)
+    }
+}
+
+impl From<LuaInteger> for Tabpage {
+    fn from(id: LuaInteger) -> Self {
+        Self(id)
+    }
+}
+
+impl From<Tabpage> for LuaInteger {
+    fn from(tab: Tabpage) -> Self {
+        tab.0
+    }
+}
+
+impl<'a> FromLua<'a> for Tabpage {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        LuaInteger::from_lua(lua_value, lua).map(Self)
+    }
+}
+
+impl<'a> ToLua<'a> for Tabpage {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.0.to_lua(lua)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_list_wins`
+pub fn nvim_tabpage_list_wins(lua: &Lua, tabpage: Tabpage) -> LuaResult<Vec<Window>> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_list_wins", tabpage)
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_get_win`
+pub fn nvim_tabpage_get_win(lua: &Lua, tabpage: Tabpage) -> LuaResult<Window> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_get_win", tabpage)
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_get_number`
+pub fn nvim_tabpage_get_number(lua: &Lua, tabpage: Tabpage) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_get_number", tabpage)
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_get_var`
+pub fn nvim_tabpage_get_var<'a, T: FromLua<'a>>(
+    lua: &'a Lua,
+    tabpage: Tabpage,
+    name: &str,
+) -> LuaResult<T> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_get_var", (tabpage, name))
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_set_var`
+pub fn nvim_tabpage_set_var<'a>(
+    lua: &'a Lua,
+    tabpage: Tabpage,
+    name: &str,
+    value: impl ToLua<'a>,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_set_var", (tabpage, name, value))
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_del_var`
+pub fn nvim_tabpage_del_var(lua: &Lua, tabpage: Tabpage, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_del_var", (tabpage, name))
+}
+
+/// Corresponds to `vim.api.nvim_tabpage_is_valid`
+pub fn nvim_tabpage_is_valid(lua: &Lua, tabpage: Tabpage) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_tabpage_is_valid", tabpage)
+}
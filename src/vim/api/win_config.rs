@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::prelude::*;
+use vim::api::{Buffer, Position, Window};
+
+/// What a floating window is positioned relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relative {
+    /// The global editor grid
+    Editor,
+    /// The window given by [`WinConfig::win`], or the current window
+    Win,
+    /// The cursor position in the current window
+    Cursor,
+    /// The mouse position
+    Mouse,
+}
+
+/// Which corner of a floating window is placed at `(row, col)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Anchor {
+    #[default]
+    #[serde(rename = "NW")]
+    NorthWest,
+    #[serde(rename = "NE")]
+    NorthEast,
+    #[serde(rename = "SW")]
+    SouthWest,
+    #[serde(rename = "SE")]
+    SouthEast,
+}
+
+/// The style of a floating window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    /// Disables number, cursorline, signcolumn, etc. for the window
+    Minimal,
+}
+
+/// The position of a floating window's title in its border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitlePos {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// A piece of text with an optional highlight group, used for borders, titles and virtual text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chunk {
+    pub text: String,
+    pub hl: Option<String>,
+}
+
+impl From<&str> for Chunk {
+    fn from(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            hl: None,
+        }
+    }
+}
+
+impl From<(&str, &str)> for Chunk {
+    fn from((text, hl): (&str, &str)) -> Self {
+        Self {
+            text: text.to_owned(),
+            hl: Some(hl.to_owned()),
+        }
+    }
+}
+
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.hl {
+            Some(hl) => (&self.text, hl).serialize(serializer),
+            None => self.text.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawChunk {
+            Text(String),
+            WithHl(String, String),
+            Single((String,)),
+        }
+
+        Ok(match RawChunk::deserialize(deserializer)? {
+            RawChunk::Text(text) | RawChunk::Single((text,)) => Chunk { text, hl: None },
+            RawChunk::WithHl(text, hl) => Chunk { text, hl: Some(hl) },
+        })
+    }
+}
+
+/// The border of a floating window, see `:h nvim_open_win()`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Border {
+    #[default]
+    None,
+    Single,
+    Double,
+    Rounded,
+    Solid,
+    Shadow,
+    /// Up to eight characters, clockwise from the top-left corner. Fewer characters are repeated.
+    Custom(Vec<Chunk>),
+}
+
+impl Serialize for Border {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Border::None => "none".serialize(serializer),
+            Border::Single => "single".serialize(serializer),
+            Border::Double => "double".serialize(serializer),
+            Border::Rounded => "rounded".serialize(serializer),
+            Border::Solid => "solid".serialize(serializer),
+            Border::Shadow => "shadow".serialize(serializer),
+            Border::Custom(chars) => chars.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Border {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawBorder {
+            Preset(String),
+            Custom(Vec<Chunk>),
+        }
+
+        Ok(match RawBorder::deserialize(deserializer)? {
+            RawBorder::Preset(preset) => match preset.as_str() {
+                "none" | "" => Border::None,
+                "single" => Border::Single,
+                "double" => Border::Double,
+                "rounded" => Border::Rounded,
+                "solid" => Border::Solid,
+                "shadow" => Border::Shadow,
+                other => {
+                    return Err(serde::de::Error::custom(format!(
+                        "unknown border style {:?}",
+                        other
+                    )))
+                }
+            },
+            RawBorder::Custom(chars) => Border::Custom(chars),
+        })
+    }
+}
+
+/// The title of a floating window, either plain text or highlighted chunks
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Title {
+    Text(String),
+    /// Highlighted chunks of text
+    Chunks(Vec<Chunk>),
+}
+
+impl From<&str> for Title {
+    fn from(text: &str) -> Self {
+        Title::Text(text.to_owned())
+    }
+}
+
+impl From<String> for Title {
+    fn from(text: String) -> Self {
+        Title::Text(text)
+    }
+}
+
+/// Window configuration for [`nvim_open_win`] and [`nvim_win_set_config`], see `:h nvim_open_win()`
+///
+/// Fields left as `None` are not sent to Neovim.
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{Buffer, Border, WinConfig};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let buf = Buffer::create(lua, false, true)?;
+///     buf.set_lines(lua, .., false, vec!["Hello from a float!"])?;
+///     let win = WinConfig::centered(lua, 40, 10)?
+///         .with_border(Border::Rounded)
+///         .with_title("Greeting")
+///         .open(lua, buf, true)?;
+///     win.set_option(lua, "wrap", false)
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WinConfig {
+    /// `None` means the window is not floating
+    #[serde(
+        deserialize_with = "deserialize_relative",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub relative: Option<Relative>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win: Option<Window>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Anchor>,
+    #[serde(
+        deserialize_with = "deserialize_float",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub row: Option<f64>,
+    #[serde(
+        deserialize_with = "deserialize_float",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub col: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    /// Places the float relative to a buffer position when `relative` is `Win`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bufpos: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focusable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zindex: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<Border>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<Title>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_pos: Option<TitlePos>,
+    /// Don't fire buffer-related autocommands when opening the window. Only used by [`nvim_open_win`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noautocmd: Option<bool>,
+}
+
+/// `nvim_win_get_config` returns `""` for windows that aren't floating
+fn deserialize_relative<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Relative>, D::Error> {
+    let relative = Option::<String>::deserialize(deserializer)?;
+    match relative.as_deref() {
+        None | Some("") => Ok(None),
+        Some(relative) => {
+            Relative::deserialize(serde::de::value::StrDeserializer::new(relative)).map(Some)
+        }
+    }
+}
+
+/// Older versions of Neovim return floats as `{ [true] = 6, [false] = value }` tables
+fn deserialize_float<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawFloat {
+        Float(f64),
+        Boxed(BTreeMap<bool, f64>),
+    }
+
+    Ok(match Option::<RawFloat>::deserialize(deserializer)? {
+        Some(RawFloat::Float(f)) => Some(f),
+        Some(RawFloat::Boxed(map)) => map.get(&false).copied(),
+        None => None,
+    })
+}
+
+impl WinConfig {
+    /// Creates an empty config
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A floating window of the given size, centered in the editor
+    pub fn centered(lua: &Lua, width: u64, height: u64) -> LuaResult<Self> {
+        let opts = vim::api::OptionValueOpts::default();
+        let columns: u64 = vim::api::nvim_get_option_value(lua, "columns", opts.clone())?;
+        let lines: u64 = vim::api::nvim_get_option_value(lua, "lines", opts)?;
+        Ok(Self::new()
+            .with_relative(Relative::Editor)
+            .with_size(width, height)
+            .with_position(
+                lines.saturating_sub(height) as f64 / 2.0,
+                columns.saturating_sub(width) as f64 / 2.0,
+            ))
+    }
+
+    /// A floating window of the given size, placed just below the cursor
+    pub fn at_cursor(width: u64, height: u64) -> Self {
+        Self::new()
+            .with_relative(Relative::Cursor)
+            .with_size(width, height)
+            .with_position(1.0, 0.0)
+    }
+
+    pub fn with_relative(mut self, relative: Relative) -> Self {
+        self.relative = Some(relative);
+        self
+    }
+
+    /// Positions the float relative to the given window, implies [`Relative::Win`]
+    pub fn with_win(mut self, win: Window) -> Self {
+        self.relative = Some(Relative::Win);
+        self.win = Some(win);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// Sets `row` and `col`, which may be fractional
+    pub fn with_position(mut self, row: f64, col: f64) -> Self {
+        self.row = Some(row);
+        self.col = Some(col);
+        self
+    }
+
+    /// Sets `width` and `height`
+    pub fn with_size(mut self, width: u64, height: u64) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_bufpos(mut self, pos: Position) -> Self {
+        self.bufpos = Some(pos);
+        self
+    }
+
+    pub fn with_focusable(mut self, focusable: bool) -> Self {
+        self.focusable = Some(focusable);
+        self
+    }
+
+    pub fn with_zindex(mut self, zindex: u64) -> Self {
+        self.zindex = Some(zindex);
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn with_border(mut self, border: Border) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    /// Sets the title. Requires a border.
+    pub fn with_title(mut self, title: impl Into<Title>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_title_pos(mut self, title_pos: TitlePos) -> Self {
+        self.title_pos = Some(title_pos);
+        self
+    }
+
+    pub fn with_noautocmd(mut self, noautocmd: bool) -> Self {
+        self.noautocmd = Some(noautocmd);
+        self
+    }
+
+    /// Opens a window displaying `buffer` with this config, see [`nvim_open_win`]
+    pub fn open(self, lua: &Lua, buffer: Buffer, enter: bool) -> LuaResult<Window> {
+        nvim_open_win(lua, buffer, enter, self)
+    }
+}
+
+impl<'a> ToLua<'a> for WinConfig {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        vim::to_api_value(lua, &self)
+    }
+}
+
+impl<'a> FromLua<'a> for WinConfig {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        lua.from_value(lua_value)
+    }
+}
+
+impl Window {
+    /// Opens a new window, see [`nvim_open_win`]
+    pub fn open(lua: &Lua, buffer: Buffer, enter: bool, config: WinConfig) -> LuaResult<Self> {
+        nvim_open_win(lua, buffer, enter, config)
+    }
+
+    /// Gets the window configuration, using `vim.api.nvim_win_get_config`
+    pub fn get_config(&self, lua: &Lua) -> LuaResult<WinConfig> {
+        nvim_win_get_config(lua, *self)
+    }
+
+    /// Reconfigures the window, using `vim.api.nvim_win_set_config`
+    pub fn set_config(&self, lua: &Lua, config: WinConfig) -> LuaResult<()> {
+        nvim_win_set_config(lua, *self, config)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_open_win`
+pub fn nvim_open_win(
+    lua: &Lua,
+    buffer: Buffer,
+    enter: bool,
+    config: WinConfig,
+) -> LuaResult<Window> {
+    vim::api::get(lua)?.call_function("nvim_open_win", (buffer, enter, config))
+}
+
+/// Corresponds to `vim.api.nvim_win_set_config`
+pub fn nvim_win_set_config(lua: &Lua, window: Window, config: WinConfig) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_config", (window, config))
+}
+
+/// Corresponds to `vim.api.nvim_win_get_config`
+pub fn nvim_win_get_config(lua: &Lua, window: Window) -> LuaResult<WinConfig> {
+    vim::api::get(lua)?.call_function("nvim_win_get_config", window)
+}
@@ -1,9 +1,273 @@
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
+use vim::api::{Buffer, Position, Tabpage};
+
+/// A handle to a Neovim window
+///
+/// Window `0` always refers to the current window, see [`Window::CURRENT`].
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{Position, Window};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let win = Window::current(lua)?;
+///     let cursor = win.get_cursor(lua)?;
+///     win.set_cursor(lua, Position::new(cursor.row, 0))?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Window(LuaInteger);
+
+impl Window {
+    /// The handle Neovim uses to refer to the current window
+    pub const CURRENT: Window = Window(0);
+
+    /// Creates a window handle from a raw window id
+    pub const fn new(id: LuaInteger) -> Self {
+        Self(id)
+    }
+
+    /// Returns the raw window id
+    pub const fn id(&self) -> LuaInteger {
+        self.0
+    }
+
+    /// Gets the current window, using `vim.api.nvim_get_current_win`
+    pub fn current(lua: &Lua) -> LuaResult<Self> {
+        vim::api::nvim_get_current_win(lua)
+    }
+
+    /// Gets all windows in all tabpages, using `vim.api.nvim_list_wins`
+    pub fn list(lua: &Lua) -> LuaResult<Vec<Self>> {
+        vim::api::nvim_list_wins(lua)
+    }
+
+    /// Makes this the current window, using `vim.api.nvim_set_current_win`
+    pub fn set_current(&self, lua: &Lua) -> LuaResult<()> {
+        vim::api::nvim_set_current_win(lua, *self)
+    }
+
+    /// Gets the cursor position, using `vim.api.nvim_win_get_cursor`
+    pub fn get_cursor(&self, lua: &Lua) -> LuaResult<Position> {
+        nvim_win_get_cursor(lua, *self)
+    }
+
+    /// Sets the cursor position, using `vim.api.nvim_win_set_cursor`
+    pub fn set_cursor(&self, lua: &Lua, pos: Position) -> LuaResult<()> {
+        nvim_win_set_cursor(lua, *self, pos)
+    }
+
+    /// Gets the window height in rows, using `vim.api.nvim_win_get_height`
+    pub fn get_height(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_win_get_height(lua, *self)
+    }
+
+    /// Sets the window height in rows, using `vim.api.nvim_win_set_height`
+    pub fn set_height(&self, lua: &Lua, height: u64) -> LuaResult<()> {
+        nvim_win_set_height(lua, *self, height)
+    }
+
+    /// Gets the window width in columns, using `vim.api.nvim_win_get_width`
+    pub fn get_width(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_win_get_width(lua, *self)
+    }
+
+    /// Sets the window width in columns, using `vim.api.nvim_win_set_width`
+    pub fn set_width(&self, lua: &Lua, width: u64) -> LuaResult<()> {
+        nvim_win_set_width(lua, *self, width)
+    }
+
+    /// Gets the buffer displayed in the window, using `vim.api.nvim_win_get_buf`
+    pub fn get_buf(&self, lua: &Lua) -> LuaResult<Buffer> {
+        nvim_win_get_buf(lua, *self)
+    }
+
+    /// Displays a buffer in the window, using `vim.api.nvim_win_set_buf`
+    pub fn set_buf(&self, lua: &Lua, buffer: Buffer) -> LuaResult<()> {
+        nvim_win_set_buf(lua, *self, buffer)
+    }
+
+    /// Gets the tabpage containing the window, using `vim.api.nvim_win_get_tabpage`
+    pub fn get_tabpage(&self, lua: &Lua) -> LuaResult<Tabpage> {
+        nvim_win_get_tabpage(lua, *self)
+    }
+
+    /// Gets the window position in display cells as `(row, col)`, using `vim.api.nvim_win_get_position`
+    pub fn get_position(&self, lua: &Lua) -> LuaResult<(u64, u64)> {
+        nvim_win_get_position(lua, *self)
+    }
+
+    /// Gets the window number, using `vim.api.nvim_win_get_number`
+    pub fn get_number(&self, lua: &Lua) -> LuaResult<u64> {
+        nvim_win_get_number(lua, *self)
+    }
+
+    /// Gets a window-local option, using `vim.api.nvim_get_option_value`
+    pub fn get_option<'a, T: FromLua<'a>>(&self, lua: &'a Lua, name: &str) -> LuaResult<T> {
+        vim::api::nvim_get_option_value(lua, name, vim::api::OptionValueOpts::win(*self))
+    }
+
+    /// Sets a window-local option, using `vim.api.nvim_set_option_value`
+    pub fn set_option<'a>(&self, lua: &'a Lua, name: &str, value: impl ToLua<'a>) -> LuaResult<()> {
+        vim::api::nvim_set_option_value(lua, name, value, vim::api::OptionValueOpts::win(*self))
+    }
+
+    /// Gets a window-scoped (`w:`) variable, using `vim.api.nvim_win_get_var`
+    pub fn get_var<'a, T: FromLua<'a>>(&self, lua: &'a Lua, name: &str) -> LuaResult<T> {
+        nvim_win_get_var(lua, *self, name)
+    }
+
+    /// Sets a window-scoped (`w:`) variable, using `vim.api.nvim_win_set_var`
+    pub fn set_var<'a>(&self, lua: &'a Lua, name: &str, value: impl ToLua<'a>) -> LuaResult<()> {
+        nvim_win_set_var(lua, *self, name, value)
+    }
+
+    /// Removes a window-scoped (`w:`) variable, using `vim.api.nvim_win_del_var`
+    pub fn del_var(&self, lua: &Lua, name: &str) -> LuaResult<()> {
+        nvim_win_del_var(lua, *self, name)
+    }
+
+    /// Closes the window, using `vim.api.nvim_win_close`
+    pub fn close(&self, lua: &Lua, force: bool) -> LuaResult<()> {
+        nvim_win_close(lua, *self, force)
+    }
+
+    /// Closes the window and hides its buffer, using `vim.api.nvim_win_hide`
+    pub fn hide(&self, lua: &Lua) -> LuaResult<()> {
+        nvim_win_hide(lua, *self)
+    }
+
+    /// Checks if the window handle is valid, using `vim.api.nvim_win_is_valid`
+    pub fn is_valid(&self, lua: &Lua) -> LuaResult<bool> {
+        nvim_win_is_valid(lua, *self)
+    }
+}
+
+impl From<LuaInteger> for Window {
+    fn from(id: LuaInteger) -> Self {
+        Self(id)
+    }
+}
+
+impl From<Window> for LuaInteger {
+    fn from(win: Window) -> Self {
+        win.0
+    }
+}
+
+impl<'a> FromLua<'a> for Window {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        LuaInteger::from_lua(lua_value, lua).map(Self)
+    }
+}
+
+impl<'a> ToLua<'a> for Window {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.0.to_lua(lua)
+    }
+}
+
+/// Corresponds to `vim.api.nvim_win_get_cursor`
+/// The api uses a 1-based row, which is converted to the 0-based row of [`Position`].
+pub fn nvim_win_get_cursor(lua: &Lua, window: Window) -> LuaResult<Position> {
+    let cursor = vim::api::get(lua)?.call_function("nvim_win_get_cursor", window)?;
+    let (row, col) = vim::from_api_value(lua, cursor)?;
+    Ok(Position::from_cursor(row, col))
+}
+
+/// Corresponds to `vim.api.nvim_win_set_cursor`
+/// The 0-based row of [`Position`] is converted to the 1-based row the api uses.
+pub fn nvim_win_set_cursor(lua: &Lua, window: Window, pos: Position) -> LuaResult<()> {
+    let (row, col) = pos.to_cursor();
+    vim::api::get(lua)?.call_function("nvim_win_set_cursor", (window, [row, col]))
+}
+
+/// Corresponds to `vim.api.nvim_win_get_height`
+pub fn nvim_win_get_height(lua: &Lua, window: Window) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_win_get_height", window)
+}
+
+/// Corresponds to `vim.api.nvim_win_set_height`
+pub fn nvim_win_set_height(lua: &Lua, window: Window, height: u64) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_height", (window, height))
+}
+
+/// Corresponds to `vim.api.nvim_win_get_width`
+pub fn nvim_win_get_width(lua: &Lua, window: Window) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_win_get_width", window)
+}
+
+/// Corresponds to `vim.api.nvim_win_set_width`
+pub fn nvim_win_set_width(lua: &Lua, window: Window, width: u64) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_width", (window, width))
+}
+
+/// Corresponds to `vim.api.nvim_win_get_buf`
+pub fn nvim_win_get_buf(lua: &Lua, window: Window) -> LuaResult<Buffer> {
+    vim::api::get(lua)?.call_function("nvim_win_get_buf", window)
+}
+
+/// Corresponds to `vim.api.nvim_win_set_buf`
+pub fn nvim_win_set_buf(lua: &Lua, window: Window, buffer: Buffer) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_buf", (window, buffer))
+}
+
+/// Corresponds to `vim.api.nvim_win_get_tabpage`
+pub fn nvim_win_get_tabpage(lua: &Lua, window: Window) -> LuaResult<Tabpage> {
+    vim::api::get(lua)?.call_function("nvim_win_get_tabpage", window)
+}
+
+/// Corresponds to `vim.api.nvim_win_get_position`
+pub fn nvim_win_get_position(lua: &Lua, window: Window) -> LuaResult<(u64, u64)> {
+    let position = vim::api::get(lua)?.call_function("nvim_win_get_position", window)?;
+    vim::from_api_value(lua, position)
+}
+
+/// Corresponds to `vim.api.nvim_win_get_number`
+pub fn nvim_win_get_number(lua: &Lua, window: Window) -> LuaResult<u64> {
+    vim::api::get(lua)?.call_function("nvim_win_get_number", window)
+}
+
+/// Corresponds to `vim.api.nvim_win_get_var`
+pub fn nvim_win_get_var<'a, T: FromLua<'a>>(
+    lua: &'a Lua,
+    window: Window,
+    name: &str,
+) -> LuaResult<T> {
+    vim::api::get(lua)?.call_function("nvim_win_get_var", (window, name))
+}
+
+/// Corresponds to `vim.api.nvim_win_set_var`
+pub fn nvim_win_set_var<'a>(
+    lua: &'a Lua,
+    window: Window,
+    name: &str,
+    value: impl ToLua<'a>,
+) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_set_var", (window, name, value))
+}
+
+/// Corresponds to `vim.api.nvim_win_del_var`
+pub fn nvim_win_del_var(lua: &Lua, window: Window, name: &str) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_del_var", (window, name))
+}
+
+/// Corresponds to `vim.api.nvim_win_close`
+pub fn nvim_win_close(lua: &Lua, window: Window, force: bool) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_close", (window, force))
+}
 
-pub fn nvim_win_get_cursor(lua: &Lua, window: u64) -> LuaResult<LuaTable> {
-    vim::api::get(lua)?.call_function("nvim_win_get_cursor", window)
+/// Corresponds to `vim.api.nvim_win_hide`
+pub fn nvim_win_hide(lua: &Lua, window: Window) -> LuaResult<()> {
+    vim::api::get(lua)?.call_function("nvim_win_hide", window)
 }
 
-pub fn nvim_win_set_cursor(lua: &Lua, window: u64, pos: (u64, u64)) -> LuaResult<()> {
-    vim::api::get(lua)?.call_function("nvim_win_set_cursor", (window, [pos.0, pos.1]))
+/// Corresponds to `vim.api.nvim_win_is_valid`
+pub fn nvim_win_is_valid(lua: &Lua, window: Window) -> LuaResult<bool> {
+    vim::api::get(lua)?.call_function("nvim_win_is_valid", window)
 }
@@ -3,6 +3,17 @@
 use crate::prelude::*;
 use std::path::PathBuf;
 
+mod debounce;
+#[cfg(all(feature = "send", unix))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "send", unix))))]
+pub mod jobs;
+pub mod mirror;
+pub mod operator;
+mod selection;
+
+pub use debounce::{debounce, throttle, DebounceBuilder, Debounced, DebouncedCallback};
+pub use selection::{selection, Selection, SelectionMode};
+
 /// Creats a session at the given path using `mksession!`
 ///
 /// ## Example
@@ -15,7 +26,7 @@ use std::path::PathBuf;
 ///     Ok(())
 /// }
 /// ```
-pub fn mksession<'a>(lua: &Lua, path: PathBuf) -> LuaResult<()> {
+pub fn mksession(lua: &Lua, path: PathBuf) -> LuaResult<()> {
     vim::cmd(
         lua,
         &format!("mksession! {}", String::from(path.to_string_lossy())),
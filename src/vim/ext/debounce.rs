@@ -0,0 +1,346 @@
+//! Debouncing and throttling callbacks, backed by `vim.uv` timers
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{prelude::*, MaybeSend};
+
+/// The signature of a debounced or throttled callback, called with the arguments of the call it stands for
+pub type DebouncedCallback =
+    maybe_send_box!(dyn for<'a> Fn(&'a Lua, LuaMultiValue<'a>) -> LuaResult<()>);
+
+#[derive(Default)]
+struct State {
+    /// The timer, only kept while a call is pending or the throttle window is open
+    timer: Option<LuaRegistryKey>,
+    /// The arguments of the latest call, if it should run on the trailing edge
+    pending: Option<LuaRegistryKey>,
+}
+
+struct Core {
+    wait: Duration,
+    throttle: bool,
+    leading: bool,
+    trailing: bool,
+    /// The callback, as a Lua function so the state can be shared with the timer
+    callback: LuaRegistryKey,
+    state: Mutex<State>,
+}
+
+impl Core {
+    fn call<'lua>(
+        &self,
+        lua: &'lua Lua,
+        args: LuaMultiValue<'lua>,
+        fire: &LuaFunction<'lua>,
+    ) -> LuaResult<()> {
+        let running = self.state.lock().unwrap().timer.is_some();
+        if !running {
+            self.start_timer(lua, fire)?;
+            if self.leading {
+                return self.invoke(lua, args);
+            }
+        } else if !self.throttle {
+            self.start_timer(lua, fire)?;
+        }
+        if self.trailing {
+            self.store(lua, args)?;
+        }
+        Ok(())
+    }
+
+    fn invoke<'lua>(&self, lua: &'lua Lua, args: LuaMultiValue<'lua>) -> LuaResult<()> {
+        lua.registry_value::<LuaFunction>(&self.callback)?
+            .call(args)
+    }
+
+    /// Runs when the timer fires, in a `vim.schedule` callback
+    fn fire(&self, lua: &Lua) -> LuaResult<()> {
+        {
+            let state = self.state.lock().unwrap();
+            let Some(timer) = &state.timer else {
+                // Cancelled or flushed before the callback was scheduled
+                return Ok(());
+            };
+            let timer: LuaValue = lua.registry_value(timer)?;
+            if !self.throttle
+                && vim::uv::get(lua)?.call_function::<_, _, bool>("is_active", timer)?
+            {
+                // Restarted by a call before the callback was scheduled
+                return Ok(());
+            }
+        }
+        match self.take_pending(lua)? {
+            Some(args) => {
+                // A throttle timer repeats, keeping the window open after a trailing call
+                if !self.throttle {
+                    self.close_timer(lua)?;
+                }
+                self.invoke(lua, args)
+            }
+            None => self.close_timer(lua),
+        }
+    }
+
+    /// Starts the timer, or restarts it if it's already running
+    fn start_timer<'lua>(&self, lua: &'lua Lua, fire: &LuaFunction<'lua>) -> LuaResult<()> {
+        let uv = vim::uv::get(lua)?;
+        let mut state = self.state.lock().unwrap();
+        let timer: LuaValue = match &state.timer {
+            Some(timer) => {
+                let timer = lua.registry_value(timer)?;
+                uv.call_function::<_, _, ()>("timer_stop", LuaValue::clone(&timer))?;
+                timer
+            }
+            None => {
+                let timer: LuaValue = uv.call_function("new_timer", ())?;
+                state.timer = Some(lua.create_registry_value(timer.clone())?);
+                timer
+            }
+        };
+        let ms = self.wait.as_millis() as u64;
+        let repeat = if self.throttle { ms } else { 0 };
+        uv.call_function::<_, _, ()>("timer_start", (timer, ms, repeat, fire.clone()))
+    }
+
+    fn close_timer(&self, lua: &Lua) -> LuaResult<()> {
+        let Some(timer) = self.state.lock().unwrap().timer.take() else {
+            return Ok(());
+        };
+        let handle: LuaValue = lua.registry_value(&timer)?;
+        lua.remove_registry_value(timer)?;
+        vim::uv::get(lua)?.call_function("close", handle)
+    }
+
+    fn store<'lua>(&self, lua: &'lua Lua, args: LuaMultiValue<'lua>) -> LuaResult<()> {
+        // Arguments are packed into a table with a length, since they can contain nils
+        let args = args.into_vec();
+        let table = lua.create_table_with_capacity(args.len() as i32, 1)?;
+        table.raw_set("n", args.len())?;
+        for (i, arg) in args.into_iter().enumerate() {
+            table.raw_set(i + 1, arg)?;
+        }
+        let key = lua.create_registry_value(table)?;
+        if let Some(old) = self.state.lock().unwrap().pending.replace(key) {
+            lua.remove_registry_value(old)?;
+        }
+        Ok(())
+    }
+
+    fn take_pending<'lua>(&self, lua: &'lua Lua) -> LuaResult<Option<LuaMultiValue<'lua>>> {
+        let Some(key) = self.state.lock().unwrap().pending.take() else {
+            return Ok(None);
+        };
+        let table: LuaTable = lua.registry_value(&key)?;
+        lua.remove_registry_value(key)?;
+        let n: usize = table.raw_get("n")?;
+        let args = (1..=n)
+            .map(|i| table.raw_get(i))
+            .collect::<LuaResult<Vec<LuaValue>>>()?;
+        Ok(Some(LuaMultiValue::from_vec(args)))
+    }
+}
+
+/// Builder for a [`Debounced`] function
+///
+/// A debounced function runs once calls have stopped for `wait`, a throttled function runs at most once every `wait`.
+/// On the leading edge the callback runs immediately, with the arguments of the first call.
+/// On the trailing edge it runs with the arguments of the last call, if there were calls since it last ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebounceBuilder {
+    wait: Duration,
+    throttle: bool,
+    leading: bool,
+    trailing: bool,
+}
+
+impl DebounceBuilder {
+    /// A debounced function, running on the trailing edge by default
+    pub fn debounce(wait: Duration) -> Self {
+        Self {
+            wait,
+            throttle: false,
+            leading: false,
+            trailing: true,
+        }
+    }
+
+    /// A throttled function, running on both edges by default
+    pub fn throttle(wait: Duration) -> Self {
+        Self {
+            wait,
+            throttle: true,
+            leading: true,
+            trailing: true,
+        }
+    }
+
+    /// Whether the callback runs immediately on the first call
+    pub fn with_leading(mut self, leading: bool) -> Self {
+        self.leading = leading;
+        self
+    }
+
+    /// Whether the callback runs with the last arguments after `wait`
+    pub fn with_trailing(mut self, trailing: bool) -> Self {
+        self.trailing = trailing;
+        self
+    }
+
+    /// Creates the function
+    pub fn build<F>(self, lua: &Lua, callback: F) -> LuaResult<Debounced>
+    where
+        F: for<'a> Fn(&'a Lua, LuaMultiValue<'a>) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let callback: DebouncedCallback = Box::new(callback);
+        let core = Arc::new(Core {
+            wait: self.wait,
+            throttle: self.throttle,
+            leading: self.leading,
+            trailing: self.trailing,
+            callback: lua.create_registry_value(lua.create_function(callback)?)?,
+            state: Mutex::default(),
+        });
+
+        // The timer only references the function's state while a call is pending,
+        // so nothing is kept alive once the function is no longer used
+        let fire_core = core.clone();
+        let fire = lua.create_function(move |lua, ()| fire_core.fire(lua))?;
+        // Timer callbacks run where most of the api isn't allowed
+        let fire: LuaFunction = vim::get(lua)?.call_function("schedule_wrap", fire)?;
+        let fire = lua.create_registry_value(fire)?;
+
+        let call_core = core.clone();
+        let function = lua.create_function(move |lua, args: LuaMultiValue| {
+            let fire: LuaFunction = lua.registry_value(&fire)?;
+            call_core.call(lua, args, &fire)
+        })?;
+
+        Ok(Debounced {
+            core,
+            function: Arc::new(lua.create_registry_value(function)?),
+        })
+    }
+}
+
+/// A debounced or throttled Lua function, created by [`debounce`], [`throttle`] or [`DebounceBuilder`]
+///
+/// The function can be passed to Lua anywhere a callback is expected, like `nvim_create_autocmd` or
+/// `nvim_buf_attach`. Calls are counted from the main thread, and the callback always runs on it,
+/// outside of `vim.uv` callbacks.
+#[derive(Clone)]
+pub struct Debounced {
+    core: Arc<Core>,
+    function: Arc<LuaRegistryKey>,
+}
+
+impl fmt::Debug for Debounced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Debounced")
+            .field("wait", &self.core.wait)
+            .field("throttle", &self.core.throttle)
+            .field("leading", &self.core.leading)
+            .field("trailing", &self.core.trailing)
+            .field("pending", &self.is_pending())
+            .finish()
+    }
+}
+
+impl Debounced {
+    /// Gets the Lua function
+    pub fn function<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaFunction<'lua>> {
+        lua.registry_value(&self.function)
+    }
+
+    /// Calls the function from Rust, like a call from Lua would
+    pub fn call<'lua>(&self, lua: &'lua Lua, args: impl ToLuaMulti<'lua>) -> LuaResult<()> {
+        self.function(lua)?.call(args)
+    }
+
+    /// Whether a call is waiting to run on the trailing edge
+    pub fn is_pending(&self) -> bool {
+        self.core.state.lock().unwrap().pending.is_some()
+    }
+
+    /// Drops the pending call, and resets the timer so the next call starts over
+    pub fn cancel(&self, lua: &Lua) -> LuaResult<()> {
+        if let Some(pending) = self.core.state.lock().unwrap().pending.take() {
+            lua.remove_registry_value(pending)?;
+        }
+        self.core.close_timer(lua)
+    }
+
+    /// Runs the pending call immediately, and resets the timer so the next call starts over
+    pub fn flush(&self, lua: &Lua) -> LuaResult<()> {
+        let pending = self.core.take_pending(lua)?;
+        self.core.close_timer(lua)?;
+        match pending {
+            Some(args) => self.core.invoke(lua, args),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'lua> ToLua<'lua> for Debounced {
+    fn to_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        self.function(lua).map(LuaValue::Function)
+    }
+}
+
+/// Creates a function that runs `callback` once it hasn't been called for `wait`, with the last arguments
+///
+/// ## Example
+/// ```rust
+/// use std::time::Duration;
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::{autocmd::{AutocmdBuilder, Event}, Buffer};
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     let on_change = vim::ext::debounce(lua, Duration::from_millis(200), |lua, args| {
+///         let buf = Buffer::from_lua_multi(args, lua)?;
+///         log::info(lua, &format!("Buffer {} changed", buf.id()))
+///     })?;
+///     AutocmdBuilder::new([Event::TextChanged, Event::TextChangedI])
+///         .with_callback(move |lua, args| {
+///             on_change.call(lua, args.buf)?;
+///             Ok(false)
+///         })
+///         .create(lua)?;
+///     Ok(())
+/// }
+/// ```
+pub fn debounce<F>(lua: &Lua, wait: Duration, callback: F) -> LuaResult<Debounced>
+where
+    F: for<'a> Fn(&'a Lua, LuaMultiValue<'a>) -> LuaResult<()> + MaybeSend + 'static,
+{
+    DebounceBuilder::debounce(wait).build(lua, callback)
+}
+
+/// Creates a function that runs `callback` at most once every `wait`, on the first call and with the last arguments
+///
+/// ## Example
+/// ```rust
+/// use std::time::Duration;
+/// use nvim_utils::prelude::*;
+/// use nvim_utils::vim::api::Window;
+///
+/// // Returned to Lua, where it can be passed to `nvim_create_autocmd` as `callback`
+/// fn my_module(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+///     let on_move = vim::ext::throttle(lua, Duration::from_millis(100), |lua, _args| {
+///         let (row, col) = vim::api::nvim_win_get_cursor(lua, Window::CURRENT)?.to_cursor();
+///         log::debug(lua, &format!("Cursor at {}:{}", row, col))
+///     })?;
+///     let module = lua.create_table()?;
+///     module.set("on_cursor_moved", on_move)?;
+///     Ok(module)
+/// }
+/// ```
+pub fn throttle<F>(lua: &Lua, wait: Duration, callback: F) -> LuaResult<Debounced>
+where
+    F: for<'a> Fn(&'a Lua, LuaMultiValue<'a>) -> LuaResult<()> + MaybeSend + 'static,
+{
+    DebounceBuilder::throttle(wait).build(lua, callback)
+}
@@ -0,0 +1,625 @@
+//! A bounded pool of worker threads for background jobs
+//!
+//! Jobs run on the pool's threads with a [`JobContext`], which carries a [`CancellationToken`] and reports progress.
+//! Their completion callbacks run on the main thread through a [`MainThreadHandle`], where the Neovim api can be used,
+//! or through another [`CompletionSink`].
+//! Progress can be shown with `vim.notify`, or broadcast with a `User` autocmd carrying a [`ProgressEvent`].
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::ext::jobs::JobPool;
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let pool = JobPool::new(lua, "myplugin", 4)?
+//!         .with_notify_progress(true)
+//!         .with_progress_autocmd("MyPluginProgress");
+//!     let files = vec!["a.rs", "b.rs", "c.rs"];
+//!     pool.submit(
+//!         "Indexing",
+//!         move |ctx| {
+//!             for (i, _file) in files.iter().enumerate() {
+//!                 if ctx.is_cancelled() {
+//!                     break;
+//!                 }
+//!                 ctx.report(Some(format!("{}/{}", i + 1, files.len())), Some((i * 100 / files.len()) as u8));
+//!             }
+//!             files.len()
+//!         },
+//!         |lua, result| match result {
+//!             Ok(count) => log::info(lua, &format!("Indexed {} files", count)),
+//!             Err(e) => log::warn(lua, &e.to_string()),
+//!         },
+//!     )?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use vim::{
+    api::{nvim_exec_autocmds, Event, ExecAutocmdsOpts},
+    thread::{Closed, MainThreadCallback, MainThreadHandle},
+};
+
+/// Identifies a job within its pool
+pub type JobId = u64;
+
+/// A flag shared between a job and whoever may cancel it
+///
+/// Cancellation is cooperative: the job has to check [`CancellationToken::is_cancelled`] and stop early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// The latest progress reported by a job
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub message: Option<String>,
+    /// Between 0 and 100
+    pub percentage: Option<u8>,
+}
+
+/// Whether a job is waiting for a worker or running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobState {
+    Queued,
+    Running,
+}
+
+/// A job that hasn't completed yet, as listed by [`JobPool::jobs`]
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub title: String,
+    pub state: JobState,
+    pub progress: Progress,
+    pub submitted: Instant,
+    pub started: Option<Instant>,
+}
+
+/// Why a job didn't produce a result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    /// The job was cancelled, either before it started or while it ran
+    Cancelled,
+    /// The job panicked, with the panic message if it was a string
+    Panicked(String),
+    /// The pool was shut down before the job started
+    ShutDown,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Cancelled => write!(f, "job was cancelled"),
+            JobError::Panicked(msg) => write!(f, "job panicked: {}", msg),
+            JobError::ShutDown => write!(f, "job pool was shut down"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+impl From<JobError> for LuaError {
+    fn from(e: JobError) -> Self {
+        LuaError::external(e)
+    }
+}
+
+/// The `data` of the `User` autocmd set with [`JobPool::with_progress_autocmd`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    /// The name of the pool
+    pub pool: String,
+    pub id: JobId,
+    pub title: String,
+    pub message: Option<String>,
+    pub percentage: Option<u8>,
+    /// Set on the last event of a job, when it completed
+    pub done: bool,
+}
+
+/// Where a pool sends completion callbacks and progress updates, to run them on the main thread
+///
+/// [`JobPool::new`] uses a [`MainThreadHandle`]. Other sinks can be passed to [`JobPool::with_sink`],
+/// for example to run the callbacks in tests.
+pub trait CompletionSink: Send + Sync + 'static {
+    /// Queues a callback. Can be called from any thread.
+    fn send(&self, f: MainThreadCallback) -> Result<(), Closed>;
+
+    /// Stops accepting callbacks, called by [`JobPool::shutdown`] on the main thread
+    fn close(&self, lua: &Lua) -> LuaResult<()>;
+}
+
+impl CompletionSink for MainThreadHandle {
+    fn send(&self, f: MainThreadCallback) -> Result<(), Closed> {
+        MainThreadHandle::send(self, f)
+    }
+
+    fn close(&self, lua: &Lua) -> LuaResult<()> {
+        MainThreadHandle::close(self, lua)
+    }
+}
+
+/// Passed to a job, to check for cancellation and report progress
+#[derive(Clone)]
+pub struct JobContext {
+    id: JobId,
+    token: CancellationToken,
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for JobContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobContext")
+            .field("id", &self.id)
+            .field("token", &self.token)
+            .field("pool", &self.shared.name)
+            .finish()
+    }
+}
+
+impl JobContext {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Reports progress, which is shown by [`JobPool::jobs`] and sent to the main thread if the pool reports progress.
+    /// Reports that don't change the progress are ignored.
+    pub fn report(&self, message: Option<String>, percentage: Option<u8>) {
+        let progress = Progress {
+            message,
+            percentage: percentage.map(|p| p.min(100)),
+        };
+        let title = {
+            let mut jobs = self.shared.jobs.lock().unwrap();
+            match jobs.get_mut(&self.id) {
+                Some(job) if job.progress != progress => {
+                    job.progress = progress.clone();
+                    job.title.clone()
+                }
+                _ => return,
+            }
+        };
+        self.shared.emit(self.id, title, progress, false);
+    }
+}
+
+/// A handle to a submitted job
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    id: JobId,
+    token: CancellationToken,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Requests the job to stop. A job that hasn't started won't run, and completes with [`JobError::Cancelled`].
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+struct QueuedJob {
+    id: JobId,
+    token: CancellationToken,
+    run: Box<dyn FnOnce(JobContext, Result<(), JobError>) + Send + 'static>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Reporting {
+    notify: bool,
+    autocmd: Option<String>,
+}
+
+struct Shared {
+    name: String,
+    workers: usize,
+    queue: Mutex<VecDeque<QueuedJob>>,
+    available: Condvar,
+    jobs: Mutex<BTreeMap<JobId, JobInfo>>,
+    tokens: Mutex<BTreeMap<JobId, CancellationToken>>,
+    next_id: AtomicU64,
+    shut_down: AtomicBool,
+    reporting: Mutex<Reporting>,
+    sink: Box<dyn CompletionSink>,
+}
+
+/// Shuts the pool down when the last [`JobPool`] clone is dropped.
+/// Workers and job contexts only hold [`Shared`], so they don't keep the pool running.
+struct ShutdownGuard(Arc<Shared>);
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.0.shutdown();
+    }
+}
+
+/// A pool of worker threads running jobs, see the [module documentation](self)
+///
+/// Clones share the same workers. Dropping the last clone shuts the pool down like [`JobPool::shutdown`],
+/// except that the sink isn't closed. Requires the `send` feature.
+#[derive(Clone)]
+pub struct JobPool {
+    shared: Arc<Shared>,
+    _guard: Arc<ShutdownGuard>,
+}
+
+impl fmt::Debug for JobPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobPool")
+            .field("name", &self.shared.name)
+            .field("workers", &self.shared.workers)
+            .field("jobs", &self.shared.jobs.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl JobPool {
+    /// Creates a pool and starts `workers` threads, at least one. Must be called on the main thread.
+    pub fn new(lua: &Lua, name: &str, workers: usize) -> LuaResult<Self> {
+        Self::with_sink(name, workers, MainThreadHandle::new(lua)?)
+    }
+
+    /// Creates a pool that sends completion callbacks and progress updates to `sink`,
+    /// and starts `workers` threads, at least one
+    pub fn with_sink(name: &str, workers: usize, sink: impl CompletionSink) -> LuaResult<Self> {
+        let shared = Arc::new(Shared {
+            name: name.to_owned(),
+            workers: workers.max(1),
+            queue: Mutex::new(VecDeque::new()),
+            available: Condvar::new(),
+            jobs: Mutex::new(BTreeMap::new()),
+            tokens: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            shut_down: AtomicBool::new(false),
+            reporting: Mutex::new(Reporting::default()),
+            sink: Box::new(sink),
+        });
+        let pool = Self {
+            shared: shared.clone(),
+            _guard: Arc::new(ShutdownGuard(shared)),
+        };
+        for i in 0..pool.shared.workers {
+            let worker = pool.shared.clone();
+            thread::Builder::new()
+                .name(format!("{}-worker-{}", name, i))
+                .spawn(move || worker.work())
+                .map_err(LuaError::external)?;
+        }
+        Ok(pool)
+    }
+
+    /// Shows progress and completion of jobs with `vim.notify`
+    pub fn with_notify_progress(self, notify: bool) -> Self {
+        self.shared.reporting.lock().unwrap().notify = notify;
+        self
+    }
+
+    /// Runs `User` autocmds with this pattern on progress and completion of jobs, with a [`ProgressEvent`] as `data`
+    pub fn with_progress_autocmd(self, pattern: &str) -> Self {
+        self.shared.reporting.lock().unwrap().autocmd = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Queues a job, which runs `work` on a worker thread and then `on_complete` on the main thread.
+    /// Can be called from any thread.
+    ///
+    /// `on_complete` receives the result of `work`, or a [`JobError`] if the job was cancelled or panicked.
+    pub fn submit<T, W, C>(
+        &self,
+        title: &str,
+        work: W,
+        on_complete: C,
+    ) -> Result<JobHandle, JobError>
+    where
+        T: Send + 'static,
+        W: FnOnce(&JobContext) -> T + Send + 'static,
+        C: FnOnce(&Lua, Result<T, JobError>) -> LuaResult<()> + Send + 'static,
+    {
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        let shared = self.shared.clone();
+        let job_title = title.to_owned();
+        let run = move |ctx: JobContext, status: Result<(), JobError>| {
+            let result = status.and_then(|()| {
+                if ctx.is_cancelled() {
+                    return Err(JobError::Cancelled);
+                }
+                let result = panic::catch_unwind(AssertUnwindSafe(|| work(&ctx)));
+                match result {
+                    Ok(_) if ctx.is_cancelled() => Err(JobError::Cancelled),
+                    Ok(value) => Ok(value),
+                    Err(payload) => Err(JobError::Panicked(panic_message(payload.as_ref()))),
+                }
+            });
+            shared.finish(id, job_title, result, on_complete);
+        };
+
+        // Checking for shutdown while holding the queue keeps jobs from being queued after it was drained
+        let mut queue = self.shared.queue.lock().unwrap();
+        if self.shared.shut_down.load(Ordering::Acquire) {
+            return Err(JobError::ShutDown);
+        }
+        self.shared.jobs.lock().unwrap().insert(
+            id,
+            JobInfo {
+                id,
+                title: title.to_owned(),
+                state: JobState::Queued,
+                progress: Progress::default(),
+                submitted: Instant::now(),
+                started: None,
+            },
+        );
+        self.shared.tokens.lock().unwrap().insert(id, token.clone());
+        queue.push_back(QueuedJob {
+            id,
+            token: token.clone(),
+            run: Box::new(run),
+        });
+        drop(queue);
+        self.shared.available.notify_one();
+        Ok(JobHandle { id, token })
+    }
+
+    /// Lists the jobs that are queued or running, in the order they were submitted
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.shared.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Cancels a job by id. Returns `false` if it already completed.
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.shared.tokens.lock().unwrap().get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every queued or running job
+    pub fn cancel_all(&self) {
+        self.shared.cancel_all();
+    }
+
+    /// Stops the workers once their current job is done. Queued jobs complete with [`JobError::ShutDown`],
+    /// running jobs are cancelled, and [`JobPool::submit`] fails from then on.
+    ///
+    /// The sink is closed after the callbacks of queued jobs ran, so the callbacks of running jobs don't run.
+    /// Must be called on the main thread.
+    pub fn shutdown(&self, lua: &Lua) -> LuaResult<()> {
+        self.shared.shutdown();
+        self.shared.sink.close(lua)
+    }
+
+    /// Reports the pool's workers and jobs with `vim.health`, for use in a plugin's `:checkhealth` module
+    ///
+    /// ```lua
+    /// -- lua/myplugin/health.lua
+    /// return { check = function() require("myplugin").check_health() end }
+    /// ```
+    pub fn check_health(&self, lua: &Lua) -> LuaResult<()> {
+        let health: LuaTable = vim::get(lua)?.get("health")?;
+        // `vim.health.start` and friends were called `report_start` etc. before Neovim 0.10
+        let report = |name: &str, msg: String| -> LuaResult<()> {
+            let f = match health.get::<_, Option<LuaFunction>>(name)? {
+                Some(f) => f,
+                None => health.get(format!("report_{}", name))?,
+            };
+            f.call(msg)
+        };
+
+        report("start", format!("{} jobs", self.shared.name))?;
+        let jobs = self.jobs();
+        let running = jobs
+            .iter()
+            .filter(|job| job.state == JobState::Running)
+            .count();
+        let summary = format!(
+            "{} workers, {} running, {} queued",
+            self.shared.workers,
+            running,
+            jobs.len() - running
+        );
+        if self.shared.shut_down.load(Ordering::Acquire) {
+            report("warn", format!("{}, shut down", summary))?;
+        } else {
+            report("ok", summary)?;
+        }
+
+        for job in jobs {
+            let mut line = format!("#{} {}: {:?}", job.id, job.title, job.state);
+            if let Some(percentage) = job.progress.percentage {
+                line.push_str(&format!(" {}%", percentage));
+            }
+            if let Some(message) = &job.progress.message {
+                line.push_str(&format!(" {}", message));
+            }
+            let since = job.started.unwrap_or(job.submitted);
+            line.push_str(&format!(" ({:.1}s)", since.elapsed().as_secs_f64()));
+            report("info", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn context(self: &Arc<Self>, id: JobId, token: CancellationToken) -> JobContext {
+        JobContext {
+            id,
+            token,
+            shared: self.clone(),
+        }
+    }
+
+    fn cancel_all(&self) {
+        for token in self.tokens.lock().unwrap().values() {
+            token.cancel();
+        }
+    }
+
+    fn shutdown(self: &Arc<Self>) {
+        let queued = {
+            let mut queue = self.queue.lock().unwrap();
+            self.shut_down.store(true, Ordering::Release);
+            std::mem::take(&mut *queue)
+        };
+        self.cancel_all();
+        for job in queued {
+            let ctx = self.context(job.id, job.token);
+            (job.run)(ctx, Err(JobError::ShutDown));
+        }
+        self.available.notify_all();
+    }
+
+    /// The loop run by each worker thread
+    fn work(self: Arc<Self>) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if self.shut_down.load(Ordering::Acquire) {
+                        return;
+                    }
+                    match queue.pop_front() {
+                        Some(job) => break job,
+                        None => queue = self.available.wait(queue).unwrap(),
+                    }
+                }
+            };
+            if let Some(info) = self.jobs.lock().unwrap().get_mut(&job.id) {
+                info.state = JobState::Running;
+                info.started = Some(Instant::now());
+            }
+            let ctx = self.context(job.id, job.token);
+            (job.run)(ctx, Ok(()));
+        }
+    }
+
+    /// Removes a completed job and runs its callback on the main thread
+    fn finish<T, C>(&self, id: JobId, title: String, result: Result<T, JobError>, on_complete: C)
+    where
+        T: Send + 'static,
+        C: FnOnce(&Lua, Result<T, JobError>) -> LuaResult<()> + Send + 'static,
+    {
+        let progress = self
+            .jobs
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(|job| job.progress)
+            .unwrap_or_default();
+        self.tokens.lock().unwrap().remove(&id);
+        self.emit(id, title, progress, true);
+        // If the sink was closed the pool was shut down or Neovim is exiting, so there's nowhere to report the result
+        let _ = self
+            .sink
+            .send(Box::new(move |lua| on_complete(lua, result)));
+    }
+
+    /// Sends a progress update to the main thread, if the pool reports progress
+    fn emit(&self, id: JobId, title: String, progress: Progress, done: bool) {
+        let reporting = self.reporting.lock().unwrap().clone();
+        if !reporting.notify && reporting.autocmd.is_none() {
+            return;
+        }
+        let event = ProgressEvent {
+            pool: self.name.clone(),
+            id,
+            title,
+            message: progress.message,
+            percentage: progress.percentage,
+            done,
+        };
+        let _ = self.sink.send(Box::new(move |lua| {
+            if reporting.notify {
+                log::info(lua, &format_progress(&event))?;
+            }
+            if let Some(pattern) = reporting.autocmd {
+                nvim_exec_autocmds(
+                    lua,
+                    [Event::User],
+                    ExecAutocmdsOpts {
+                        pattern: vec![pattern],
+                        data: Some(vim::to_api_value(lua, &event)?),
+                        ..Default::default()
+                    },
+                )?;
+            }
+            Ok(())
+        }));
+    }
+}
+
+fn format_progress(event: &ProgressEvent) -> String {
+    let mut msg = format!("[{}] {}", event.pool, event.title);
+    if event.done {
+        msg.push_str(": done");
+        return msg;
+    }
+    if let Some(percentage) = event.percentage {
+        msg.push_str(&format!(" {}%", percentage));
+    }
+    if let Some(message) = &event.message {
+        msg.push_str(&format!(": {}", message));
+    }
+    msg
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
@@ -0,0 +1,433 @@
+//! A Rust-side copy of a buffer's text, kept in sync incrementally with `on_bytes`
+//!
+//! [`BufferMirror`] takes one snapshot of the buffer when attached, and from then on only applies the
+//! changes reported by `nvim_buf_attach`, so expensive analysis can read the text without calling
+//! `nvim_buf_get_lines` on every change.
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::api::Buffer;
+//! use nvim_utils::vim::ext::mirror::BufferMirror;
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let mirror = BufferMirror::attach(lua, Buffer::current(lua)?)?;
+//!     let words = mirror.read(|text| {
+//!         text.lines().iter().map(|line| line.split_whitespace().count()).sum::<usize>()
+//!     });
+//!     log::info(lua, &format!("{} words at changedtick {}", words, mirror.changedtick()))
+//! }
+//! ```
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::prelude::*;
+use vim::api::{BufAttachOpts, Buffer, BytesEvent, Position};
+
+/// An error returned by [`MirrorText::splice`] when an edit doesn't fit the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpliceError {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for SpliceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "edit position ({}, {}) is outside the text",
+            self.row, self.col
+        )
+    }
+}
+
+impl std::error::Error for SpliceError {}
+
+/// The text of a buffer as a list of lines
+///
+/// Offsets treat every line as terminated by `\n`, including the last one, which is how
+/// Neovim counts bytes in `on_bytes`. A buffer always has at least one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorText {
+    lines: Vec<String>,
+}
+
+impl Default for MirrorText {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl MirrorText {
+    /// Creates a text from lines, without trailing newlines
+    pub fn new(lines: Vec<String>) -> Self {
+        let mut text = Self { lines };
+        if text.lines.is_empty() {
+            text.lines.push(String::new());
+        }
+        text
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Gets a line by its 0-based index
+    pub fn line(&self, row: usize) -> Option<&str> {
+        self.lines.get(row).map(String::as_str)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The length of the text in bytes, counting a newline after every line
+    pub fn len_bytes(&self) -> usize {
+        self.lines.iter().map(|line| line.len() + 1).sum()
+    }
+
+    /// Replaces a region of the text, with positions in the same form as [`BytesEvent`]
+    ///
+    /// The region starts at `(start_row, start_col)` and ends `(old_end_row, old_end_col)` after it,
+    /// where `old_end_col` is relative to `start_col` if `old_end_row` is 0. `new_text` may contain newlines.
+    pub fn splice(
+        &mut self,
+        start_row: usize,
+        start_col: usize,
+        old_end_row: usize,
+        old_end_col: usize,
+        new_text: &str,
+    ) -> Result<(), SpliceError> {
+        let end_row = start_row + old_end_row;
+        let end_col = if old_end_row == 0 {
+            start_col + old_end_col
+        } else {
+            old_end_col
+        };
+
+        // Text before the region on its first line, and after it on its last line.
+        // Positions on the line just past the end refer to the end of the text.
+        let prefix = match self.lines.get(start_row) {
+            Some(line) => line.get(..start_col),
+            None if start_row == self.lines.len() && start_col == 0 => Some(""),
+            None => None,
+        }
+        .ok_or(SpliceError {
+            row: start_row,
+            col: start_col,
+        })?;
+        let suffix = match self.lines.get(end_row) {
+            Some(line) => line.get(end_col..).map(|rest| format!("{}\n", rest)),
+            None if end_row == self.lines.len() && end_col == 0 => Some(String::new()),
+            None => None,
+        }
+        .ok_or(SpliceError {
+            row: end_row,
+            col: end_col,
+        })?;
+
+        let replaced = format!("{}{}{}", prefix, new_text, suffix);
+        let mut new_lines: Vec<String> = replaced.split('\n').map(str::to_owned).collect();
+        // Every line is newline-terminated, so the last piece is empty unless the edit left a partial line
+        if new_lines.last().is_some_and(String::is_empty) {
+            new_lines.pop();
+        }
+
+        let end = (end_row + 1).min(self.lines.len());
+        let start = start_row.min(end);
+        self.lines.splice(start..end, new_lines);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        Ok(())
+    }
+
+    /// Applies an `on_bytes` event, where `new_text` is the text that was inserted
+    pub fn apply(&mut self, event: &BytesEvent, new_text: &str) -> Result<(), SpliceError> {
+        self.splice(
+            event.start_row as usize,
+            event.start_col as usize,
+            event.old_end_row as usize,
+            event.old_end_col as usize,
+            new_text,
+        )
+    }
+
+    /// Gets the text from byte offset `start` to `end`
+    pub fn slice(&self, start: usize, end: usize) -> Option<String> {
+        let (start_row, start_col) = self.locate(start)?;
+        let (end_row, end_col) = self.locate(end)?;
+        let mut text = String::new();
+        for row in start_row..=end_row.min(self.lines.len() - 1) {
+            let line = &self.lines[row];
+            let from = if row == start_row { start_col } else { 0 };
+            if row == end_row {
+                text.push_str(line.get(from..end_col)?);
+            } else {
+                text.push_str(line.get(from..)?);
+                text.push('\n');
+            }
+        }
+        Some(text)
+    }
+
+    /// Converts a position into a byte offset
+    pub fn offset_of(&self, pos: Position) -> Option<usize> {
+        let (row, col) = (pos.row as usize, pos.col as usize);
+        if row == self.lines.len() && col == 0 {
+            return Some(self.len_bytes());
+        }
+        let line = self.lines.get(row)?;
+        if col > line.len() {
+            return None;
+        }
+        Some(self.lines[..row].iter().map(|l| l.len() + 1).sum::<usize>() + col)
+    }
+
+    /// Converts a byte offset into a position
+    pub fn position_of(&self, offset: usize) -> Option<Position> {
+        let (row, col) = self.locate(offset)?;
+        Some(Position::new(row as u64, col as u64))
+    }
+
+    /// Finds the row and byte column of a byte offset
+    fn locate(&self, offset: usize) -> Option<(usize, usize)> {
+        let mut start = 0;
+        for (row, line) in self.lines.iter().enumerate() {
+            if offset <= start + line.len() {
+                return Some((row, offset - start));
+            }
+            start += line.len() + 1;
+        }
+        (offset == start).then_some((self.lines.len(), 0))
+    }
+
+    /// Counts the units of text before a byte offset, using `unit` to measure each character.
+    /// Returns `None` if the offset is inside a character.
+    fn count_units(&self, offset: usize, unit: impl Fn(char) -> usize) -> Option<usize> {
+        let (row, col) = self.locate(offset)?;
+        let before: usize = self.lines[..row]
+            .iter()
+            .map(|line| line.chars().map(&unit).sum::<usize>() + 1)
+            .sum();
+        let line = self.lines.get(row).map_or("", String::as_str);
+        Some(before + line.get(..col)?.chars().map(unit).sum::<usize>())
+    }
+
+    /// Finds the byte offset after `units` units of text, using `unit` to measure each character.
+    /// Returns `None` if the count ends inside a character.
+    fn find_units(&self, mut units: usize, unit: impl Fn(char) -> usize) -> Option<usize> {
+        let mut offset = 0;
+        for line in &self.lines {
+            for c in line.chars().chain(std::iter::once('\n')) {
+                if units == 0 {
+                    return Some(offset);
+                }
+                units = units.checked_sub(unit(c))?;
+                offset += c.len_utf8();
+            }
+        }
+        (units == 0).then_some(offset)
+    }
+
+    /// Converts a byte offset into a character offset
+    pub fn byte_to_char(&self, offset: usize) -> Option<usize> {
+        self.count_units(offset, |_| 1)
+    }
+
+    /// Converts a character offset into a byte offset
+    pub fn char_to_byte(&self, offset: usize) -> Option<usize> {
+        self.find_units(offset, |_| 1)
+    }
+
+    /// Converts a byte offset into a UTF-16 code unit offset, as used by LSP
+    pub fn byte_to_utf16(&self, offset: usize) -> Option<usize> {
+        self.count_units(offset, char::len_utf16)
+    }
+
+    /// Converts a UTF-16 code unit offset into a byte offset
+    pub fn utf16_to_byte(&self, offset: usize) -> Option<usize> {
+        self.find_units(offset, char::len_utf16)
+    }
+}
+
+impl fmt::Display for MirrorText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct MirrorState {
+    text: MirrorText,
+    changedtick: u64,
+    attached: bool,
+    detach: bool,
+    check: bool,
+    mismatches: u64,
+}
+
+/// A copy of a buffer's text that follows changes through `on_bytes`, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct BufferMirror {
+    buffer: Buffer,
+    state: Arc<Mutex<MirrorState>>,
+}
+
+impl BufferMirror {
+    /// Snapshots the buffer and attaches to it. The buffer must be loaded.
+    pub fn attach(lua: &Lua, buffer: Buffer) -> LuaResult<Self> {
+        let mirror = Self {
+            buffer,
+            state: Arc::default(),
+        };
+        mirror.resync(lua)?;
+
+        let on_bytes = mirror.clone();
+        let on_changedtick = mirror.clone();
+        let on_detach = mirror.clone();
+        let on_reload = mirror.clone();
+        let opts = BufAttachOpts::new()
+            .with_on_bytes(move |lua, event| on_bytes.on_bytes(lua, event))
+            .with_on_changedtick(move |_, _, changedtick| {
+                let mut state = on_changedtick.lock();
+                state.changedtick = changedtick;
+                Ok(state.detach)
+            })
+            .with_on_detach(move |_, _| {
+                on_detach.lock().attached = false;
+                Ok(())
+            })
+            .with_on_reload(move |lua, _| on_reload.resync(lua));
+
+        if !buffer.attach(lua, false, opts)? {
+            return Err(LuaError::RuntimeError(format!(
+                "failed to attach to buffer {}",
+                buffer.id()
+            )));
+        }
+        mirror.lock().attached = true;
+        Ok(mirror)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MirrorState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn on_bytes(&self, lua: &Lua, event: BytesEvent) -> LuaResult<bool> {
+        if self.lock().detach {
+            return Ok(true);
+        }
+
+        // The buffer already contains the new text, so read the rows it covers
+        let new_text = if event.new_end_byte == 0 {
+            String::new()
+        } else {
+            let start = event.start_row;
+            let end = start + event.new_end_row + 1;
+            let rows = MirrorText::new(self.buffer.get_lines(lua, start..end, false)?);
+            let from = event.start_col as usize;
+            rows.slice(from, from + event.new_end_byte as usize)
+                .unwrap_or_default()
+        };
+
+        let (applied, check) = {
+            let mut state = self.lock();
+            state.changedtick = event.changedtick;
+            (state.text.apply(&event, &new_text), state.check)
+        };
+        if applied.is_err() || check {
+            self.check(lua)?;
+        }
+        Ok(false)
+    }
+
+    /// The buffer being mirrored
+    pub fn buffer(&self) -> Buffer {
+        self.buffer
+    }
+
+    /// The `b:changedtick` of the last change applied to the mirror
+    pub fn changedtick(&self) -> u64 {
+        self.lock().changedtick
+    }
+
+    /// Whether the mirror is still receiving changes. Unloading the buffer detaches it.
+    pub fn is_attached(&self) -> bool {
+        let state = self.lock();
+        state.attached && !state.detach
+    }
+
+    /// Stops following changes. The text stays as it was at the last change.
+    pub fn detach(&self) {
+        self.lock().detach = true;
+    }
+
+    /// Runs a function with the current text
+    pub fn read<R>(&self, f: impl FnOnce(&MirrorText) -> R) -> R {
+        f(&self.lock().text)
+    }
+
+    /// Clones the current text
+    pub fn snapshot(&self) -> MirrorText {
+        self.lock().text.clone()
+    }
+
+    /// Enables or disables comparing the mirror against the real buffer after every change.<br>
+    /// This defeats the purpose of the mirror, and is meant for tests and debugging.
+    pub fn set_consistency_check(&self, check: bool) {
+        self.lock().check = check;
+    }
+
+    /// The number of times the mirror was found to differ from the buffer and had to be resynced
+    pub fn mismatches(&self) -> u64 {
+        self.lock().mismatches
+    }
+
+    /// Compares the mirror against the real buffer, resyncing it if they differ.
+    /// Returns `true` if they matched.
+    pub fn check(&self, lua: &Lua) -> LuaResult<bool> {
+        let actual = MirrorText::new(self.buffer.get_lines(lua, .., false)?);
+        let mut state = self.lock();
+        if state.text == actual {
+            return Ok(true);
+        }
+        let row = state
+            .text
+            .lines()
+            .iter()
+            .zip(actual.lines())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| state.text.line_count().min(actual.line_count()));
+        state.text = actual;
+        state.mismatches += 1;
+        drop(state);
+        log::warn(
+            lua,
+            &format!(
+                "Mirror of buffer {} differed from the buffer at line {}, resynced",
+                self.buffer.id(),
+                row + 1
+            ),
+        )?;
+        Ok(false)
+    }
+
+    /// Replaces the mirrored text with a fresh snapshot of the buffer
+    pub fn resync(&self, lua: &Lua) -> LuaResult<()> {
+        let text = MirrorText::new(self.buffer.get_lines(lua, .., false)?);
+        let changedtick = self.buffer.get_changedtick(lua)?;
+        let mut state = self.lock();
+        state.text = text;
+        state.changedtick = changedtick;
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Operators implemented in Rust, using `operatorfunc` and `g@`
+//!
+//! An [`Operator`] stores its callback in a global Lua table and points `operatorfunc` at it through `v:lua`,
+//! so it takes a motion or text object like a builtin operator, works in visual mode, and is repeated by `.`.
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::{api::Buffer, ext::operator::Operator, keymap::{KeymapOpts, Mode}};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let upper = Operator::new(lua, "upper", |lua, motion| {
+//!         let selection = motion.selection(lua)?;
+//!         let rows = selection.start.row..=selection.end.row;
+//!         let lines = Buffer::current(lua)?.get_lines(lua, rows.clone(), true)?;
+//!         let lines: Vec<String> = lines.iter().map(|line| line.to_uppercase()).collect();
+//!         Buffer::current(lua)?.set_lines(lua, rows, true, lines)
+//!     })?;
+//!     upper.map(lua, &[Mode::Normal, Mode::VisualOnly], "gU", KeymapOpts::default())?;
+//!     upper.map_line(lua, "gUU", KeymapOpts::default())
+//! }
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use crate::{prelude::*, MaybeSend};
+use vim::{
+    api::{Buffer, Position},
+    ext::{Selection, SelectionMode},
+    keymap::{self, KeymapOpts, Mode, Rhs},
+};
+
+/// The global table holding the callbacks `operatorfunc` points to
+const OPERATORS: &str = "nvim_utils_operators";
+
+/// The type of motion an operator was called with, see `:h g@`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MotionType {
+    /// A linewise motion like `j`, or a `V` selection
+    Line,
+    /// A characterwise motion like `w`, or a `v` selection
+    Char,
+    /// A blockwise `CTRL-V` selection
+    Block,
+}
+
+impl MotionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MotionType::Line => "line",
+            MotionType::Char => "char",
+            MotionType::Block => "block",
+        }
+    }
+}
+
+impl fmt::Display for MotionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MotionType {
+    type Err = LuaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "line" => Ok(MotionType::Line),
+            "char" => Ok(MotionType::Char),
+            "block" => Ok(MotionType::Block),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: "string",
+                to: "MotionType",
+                message: Some(format!("Unknown motion type {:?}", s)),
+            }),
+        }
+    }
+}
+
+impl From<MotionType> for SelectionMode {
+    fn from(motion: MotionType) -> Self {
+        match motion {
+            MotionType::Line => SelectionMode::Linewise,
+            MotionType::Char => SelectionMode::Charwise,
+            MotionType::Block => SelectionMode::Blockwise { to_end: false },
+        }
+    }
+}
+
+impl<'a> FromLua<'a> for MotionType {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        String::from_lua(lua_value, lua)?.parse()
+    }
+}
+
+/// The text an operator was applied to, from the `'[` and `']` marks
+///
+/// `start` and `end` are inclusive, as set by Neovim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OperatorMotion {
+    pub motion: MotionType,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl OperatorMotion {
+    /// Reads the marks set by `g@` in the current buffer
+    pub fn current(lua: &Lua, motion: MotionType) -> LuaResult<Self> {
+        let start = vim::func::getpos(lua, "'[")?.unwrap_or_default();
+        let end = vim::func::getpos(lua, "']")?.unwrap_or(start);
+        Ok(Self { motion, start, end })
+    }
+
+    /// Gets the operated text as a [`Selection`], with the columns normalized to whole characters
+    pub fn selection(&self, lua: &Lua) -> LuaResult<Selection> {
+        let lines = Buffer::current(lua)?.get_lines(lua, self.start.row..=self.end.row, false)?;
+        let width = |text: &str, col| vim::func::strdisplaywidth(lua, text, col);
+        Selection::from_lines(self.motion.into(), self.start, self.end, &lines, width)
+    }
+}
+
+/// The signature of an operator callback
+pub type OperatorCallback = maybe_send_box!(dyn Fn(&Lua, OperatorMotion) -> LuaResult<()>);
+
+/// An operator backed by a Rust callback
+///
+/// The callback runs as `operatorfunc`, so Neovim records it for dot-repeat like any other `g@` operator:
+/// `.` calls it again with a motion of the same size at the new cursor position.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Operator {
+    name: String,
+}
+
+impl Operator {
+    /// Registers an operator callback under `name`, replacing any operator with the same name.
+    /// `name` must be a valid Lua identifier, since it's used in a `v:lua` expression.
+    pub fn new<F>(lua: &Lua, name: &str, callback: F) -> LuaResult<Self>
+    where
+        F: Fn(&Lua, OperatorMotion) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && name.chars().next().is_some_and(|c| !c.is_ascii_digit());
+        if !valid {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid operator name {:?}, expected a Lua identifier",
+                name
+            )));
+        }
+
+        let callback: OperatorCallback = Box::new(callback);
+        let f = lua.create_function(move |lua, motion: MotionType| {
+            callback(lua, OperatorMotion::current(lua, motion)?)
+        })?;
+        let globals = lua.globals();
+        let operators = match globals.get::<_, Option<LuaTable>>(OPERATORS)? {
+            Some(operators) => operators,
+            None => {
+                let operators = lua.create_table()?;
+                globals.set(OPERATORS, operators.clone())?;
+                operators
+            }
+        };
+        operators.set(name, f)?;
+
+        Ok(Self {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value `operatorfunc` is set to for this operator
+    pub fn operatorfunc(&self) -> String {
+        format!("v:lua.{}.{}", OPERATORS, self.name)
+    }
+
+    /// Sets `operatorfunc` to this operator and returns `g@` followed by `motion`,
+    /// for use as the result of an `expr` mapping
+    pub fn start(&self, lua: &Lua, motion: &str) -> LuaResult<String> {
+        vim::opt::set(
+            lua,
+            "operatorfunc",
+            self.operatorfunc(),
+            vim::opt::OptionScope::Global,
+        )?;
+        Ok(format!("g@{}", motion))
+    }
+
+    /// Maps `lhs` to this operator, waiting for a motion in normal mode and using the selection in visual mode
+    pub fn map(&self, lua: &Lua, modes: &[Mode], lhs: &str, opts: KeymapOpts) -> LuaResult<()> {
+        let operator = self.clone();
+        keymap::set(
+            lua,
+            modes,
+            lhs,
+            Rhs::expr(move |lua| operator.start(lua, "")),
+            opts,
+        )
+    }
+
+    /// Maps `lhs` in normal mode to apply this operator to `[count]` lines, like `dd` or `gUU`
+    pub fn map_line(&self, lua: &Lua, lhs: &str, opts: KeymapOpts) -> LuaResult<()> {
+        let operator = self.clone();
+        keymap::set(
+            lua,
+            &[Mode::Normal],
+            lhs,
+            Rhs::expr(move |lua| operator.start(lua, "_")),
+            opts,
+        )
+    }
+}
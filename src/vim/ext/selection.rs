@@ -0,0 +1,290 @@
+use crate::prelude::*;
+use vim::api::{Buffer, Position};
+
+/// The `v:maxcol` value Neovim uses for "end of line" columns, as a 1-based column
+const MAXCOL: u64 = 2147483647;
+
+/// The kind of a visual selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelectionMode {
+    /// `v`, from one character to another
+    Charwise,
+    /// `V`, whole lines
+    Linewise,
+    /// `CTRL-V`, a rectangle of columns.
+    /// `to_end` is set when the selection was extended with `$`, so every line is selected up to its end.
+    Blockwise { to_end: bool },
+}
+
+impl SelectionMode {
+    /// Parses the first character of `mode()` or `visualmode()`, including select mode.
+    /// Returns `None` for non-visual modes.
+    pub fn from_mode(mode: &str) -> Option<Self> {
+        match mode.chars().next()? {
+            'v' | 's' => Some(Self::Charwise),
+            'V' | 'S' => Some(Self::Linewise),
+            '\x16' | '\x13' => Some(Self::Blockwise { to_end: false }),
+            _ => None,
+        }
+    }
+}
+
+/// A visual selection in the current buffer, as returned by [`selection`]
+///
+/// `start` and `end` are inclusive and ordered, with `end.col` at the first byte of the last selected character.
+/// For linewise selections the columns span the whole lines, and for blockwise selections `start` and `end`
+/// are the top-left and bottom-right corners of the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub mode: SelectionMode,
+    pub start: Position,
+    pub end: Position,
+    /// The selected text, one entry per line
+    pub text: Vec<String>,
+}
+
+impl Selection {
+    /// Creates a selection from its two ends in any order, and the lines from the first to the last row of the selection.
+    ///
+    /// Columns are snapped to the start of the character they fall in and clamped to their line,
+    /// so positions like the `v:maxcol` column of a linewise `'>` mark are accepted.
+    ///
+    /// `width` measures the display width of text starting at a screen column, like `strdisplaywidth()`, see
+    /// [`vim::func::strdisplaywidth`]. Blockwise selections use it to find the edges of the block in screen columns,
+    /// like Neovim does, so tabs and wide characters are handled. It's called once per character of the block's lines
+    /// that isn't printable ASCII. A character that is only partly inside the block is replaced with spaces
+    /// for the part that is, like a blockwise yank.
+    pub fn from_lines(
+        mode: SelectionMode,
+        a: Position,
+        b: Position,
+        lines: &[String],
+        mut width: impl FnMut(&str, u64) -> LuaResult<u64>,
+    ) -> LuaResult<Self> {
+        let (first, last) = if a <= b { (a, b) } else { (b, a) };
+        let line = |row: u64| {
+            lines
+                .get((row - first.row) as usize)
+                .map(String::as_str)
+                .unwrap_or("")
+        };
+
+        match mode {
+            SelectionMode::Charwise => {
+                let start = Position::new(first.row, char_start(line(first.row), first.col));
+                let end = Position::new(last.row, char_start(line(last.row), last.col));
+                let text = (first.row..=last.row)
+                    .map(|row| {
+                        let text = line(row);
+                        let from = if row == start.row {
+                            start.col as usize
+                        } else {
+                            0
+                        };
+                        let to = if row == end.row {
+                            char_end(text, end.col)
+                        } else {
+                            text.len()
+                        };
+                        text[from..to.max(from)].to_owned()
+                    })
+                    .collect();
+                Ok(Self {
+                    mode,
+                    start,
+                    end,
+                    text,
+                })
+            }
+            SelectionMode::Linewise => {
+                let last_line = line(last.row);
+                Ok(Self {
+                    mode,
+                    start: Position::new(first.row, 0),
+                    end: Position::new(last.row, char_start(last_line, last_line.len() as u64)),
+                    text: (first.row..=last.row)
+                        .map(|row| line(row).to_owned())
+                        .collect(),
+                })
+            }
+            SelectionMode::Blockwise { to_end } => {
+                let (a_left, a_right) = screen_cols(line(a.row), a.col, &mut width)?;
+                let (b_left, b_right) = screen_cols(line(b.row), b.col, &mut width)?;
+                let left = a_left.min(b_left);
+                let right = (!to_end).then(|| a_right.max(b_right));
+
+                let mut text = Vec::new();
+                let mut start = Position::new(first.row, 0);
+                let mut end = Position::new(last.row, 0);
+                for row in first.row..=last.row {
+                    let line = line(row);
+                    let (slice, from, to) = block_slice(line, left, right, &mut width)?;
+                    text.push(slice);
+                    let clamped = char_start(line, line.len() as u64);
+                    if row == first.row {
+                        start.col = from.unwrap_or(clamped);
+                    }
+                    if row == last.row {
+                        end.col = to.unwrap_or(clamped);
+                    }
+                }
+                Ok(Self {
+                    mode,
+                    start,
+                    end,
+                    text,
+                })
+            }
+        }
+    }
+
+    /// The selected text joined with newlines
+    pub fn joined(&self) -> String {
+        self.text.join("\n")
+    }
+}
+
+/// Gets the active visual selection, or the last one if Neovim isn't in visual or select mode
+///
+/// The active selection is read from `getpos("v")` and the cursor, the last one from the `'<` and `'>` marks.
+/// Returns `None` if there is no selection in the current buffer.
+///
+/// Neovim only records whether a blockwise selection was extended with `$` while it is active,
+/// so commands that need this should be mapped with `<Cmd>`, which doesn't leave visual mode.
+///
+/// ## Example
+/// ```rust
+/// use nvim_utils::prelude::*;
+///
+/// fn my_module(lua: &Lua) -> LuaResult<()> {
+///     if let Some(selection) = vim::ext::selection(lua)? {
+///         log::info(lua, &format!("Selected {:?}", selection.joined()))?;
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn selection(lua: &Lua) -> LuaResult<Option<Selection>> {
+    let (mode, a, b) = match SelectionMode::from_mode(&vim::func::mode(lua)?) {
+        Some(mode) => {
+            let mode = match mode {
+                SelectionMode::Blockwise { .. } => {
+                    let view: LuaTable = vim::func::get(lua)?.call_function("winsaveview", ())?;
+                    SelectionMode::Blockwise {
+                        to_end: view.get::<_, u64>("curswant")? >= MAXCOL,
+                    }
+                }
+                mode => mode,
+            };
+            (
+                mode,
+                vim::func::getpos(lua, "v")?,
+                vim::func::getpos(lua, ".")?,
+            )
+        }
+        None => match SelectionMode::from_mode(&vim::func::visualmode(lua)?) {
+            Some(mode) => (
+                mode,
+                vim::func::getpos(lua, "'<")?,
+                vim::func::getpos(lua, "'>")?,
+            ),
+            None => return Ok(None),
+        },
+    };
+    let (Some(a), Some(b)) = (a, b) else {
+        return Ok(None);
+    };
+
+    let rows = a.row.min(b.row)..=a.row.max(b.row);
+    let lines = Buffer::current(lua)?.get_lines(lua, rows, false)?;
+    let width = |text: &str, col| vim::func::strdisplaywidth(lua, text, col);
+    Ok(Some(Selection::from_lines(mode, a, b, &lines, width)?))
+}
+
+/// The byte column of the start of the character containing `col`, clamped to the last character of the line
+fn char_start(line: &str, col: u64) -> u64 {
+    line.char_indices()
+        .map(|(i, _)| i as u64)
+        .take_while(|&i| i <= col)
+        .last()
+        .unwrap_or(0)
+}
+
+/// The byte column just past the character containing `col`
+fn char_end(line: &str, col: u64) -> usize {
+    let start = char_start(line, col) as usize;
+    start + line[start..].chars().next().map_or(0, char::len_utf8)
+}
+
+/// The first and last screen column of the character containing byte column `col`, 0-based.
+/// Columns past the end of the line (like in a short line of a block) keep their distance from the end.
+fn screen_cols(
+    line: &str,
+    col: u64,
+    width: &mut impl FnMut(&str, u64) -> LuaResult<u64>,
+) -> LuaResult<(u64, u64)> {
+    if col as usize >= line.len() {
+        let col = width(line, 0)? + (col - line.len() as u64);
+        return Ok((col, col));
+    }
+    let start = char_start(line, col) as usize;
+    let left = width(&line[..start], 0)?;
+    let right = left + char_width(&line[start..char_end(line, col)], left, width)?;
+    Ok((left, right.saturating_sub(1).max(left)))
+}
+
+/// The display width of a single character starting at screen column `col`.
+/// Printable ASCII is always one cell wide, so only other characters are measured with `width`.
+fn char_width(
+    c: &str,
+    col: u64,
+    width: &mut impl FnMut(&str, u64) -> LuaResult<u64>,
+) -> LuaResult<u64> {
+    match c.as_bytes() {
+        [b' '..=b'~'] => Ok(1),
+        _ => width(c, col),
+    }
+}
+
+/// The part of `line` between the screen columns `left` and `right` (inclusive, or to the end of the line if `None`),
+/// with the byte columns of the first and last characters in it
+fn block_slice(
+    line: &str,
+    left: u64,
+    right: Option<u64>,
+    width: &mut impl FnMut(&str, u64) -> LuaResult<u64>,
+) -> LuaResult<(String, Option<u64>, Option<u64>)> {
+    let right = right.map_or(u64::MAX, |right| right + 1);
+    let mut text = String::new();
+    let (mut from, mut to) = (None, None);
+    let (mut start, mut included) = (0, false);
+    for (i, c) in line.char_indices() {
+        // The character covers the screen columns `start..end`
+        let end = start + char_width(&line[i..i + c.len_utf8()], start, width)?;
+        let (first, last) = (std::mem::replace(&mut start, end), end);
+        if first == last {
+            // Zero-width characters like combining marks belong to the previous character
+            if included {
+                text.push(c);
+            }
+            continue;
+        }
+        if first >= right {
+            break;
+        }
+        included = last > left;
+        if !included {
+            continue;
+        }
+        if first < left || last > right {
+            let covered = last.min(right) - first.max(left);
+            text.push_str(&" ".repeat(covered as usize));
+            // Spaces can't be followed by the marks of the character they replace
+            included = false;
+        } else {
+            text.push(c);
+        }
+        from.get_or_insert(i as u64);
+        to = Some(i as u64);
+    }
+    Ok((text, from, to))
+}
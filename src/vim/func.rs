@@ -2,9 +2,10 @@
 use std::path::PathBuf;
 
 use crate::prelude::*;
+use vim::api::Position;
 
 /// Gets the `vim.fn` table
-pub fn get(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     vim::get(lua)?.get::<_, LuaTable>("fn")
 }
 
@@ -19,25 +20,39 @@ pub fn tmpname(lua: &Lua) -> LuaResult<String> {
 }
 
 /// Corresponds to `vim.fn.line`
-pub fn line(lua: &Lua, line: &str) -> LuaResult<u64> {
-    self::get(lua)?.get::<_, LuaFunction>("line")?.call(line)
+/// Returns the 0-based row of a position like `"."` or `"'a"`, or `None` if it's not set. See [`getpos`] for the column.
+pub fn line(lua: &Lua, expr: &str) -> LuaResult<Option<u64>> {
+    let lnum: u64 = self::get(lua)?.get::<_, LuaFunction>("line")?.call(expr)?;
+    Ok((lnum != 0).then(|| Position::from_one_based(lnum, 1).row))
+}
+
+/// Converts the 1-based line returned by `foldclosed()` and `foldclosedend()` to a 0-based row, `None` for `-1`
+fn fold_row(lnum: i64) -> Option<u64> {
+    (lnum > 0).then(|| Position::from_one_based(lnum as u64, 1).row)
 }
 
 /// Corresponds to `vim.fn.foldclosedend`
-pub fn foldclosedend(lua: &Lua, line: u64) -> LuaResult<i64> {
-    self::get(lua)?
+/// Takes and returns 0-based rows. Returns `None` if `row` is not in a closed fold.
+pub fn foldclosedend(lua: &Lua, row: u64) -> LuaResult<Option<u64>> {
+    let lnum: i64 = self::get(lua)?
         .get::<_, LuaFunction>("foldclosedend")?
-        .call(line)
+        .call(row + 1)?;
+    Ok(fold_row(lnum))
 }
 
 /// Corresponds to `vim.fn.foldclosed`
-pub fn foldclosed(lua: &Lua, line: u64) -> LuaResult<i64> {
-    vim::func::get(lua)?.call_function("foldclosed", line)
+/// Takes and returns 0-based rows. Returns `None` if `row` is not in a closed fold.
+pub fn foldclosed(lua: &Lua, row: u64) -> LuaResult<Option<u64>> {
+    let lnum: i64 = vim::func::get(lua)?.call_function("foldclosed", row + 1)?;
+    Ok(fold_row(lnum))
 }
 
 /// Corresponds to `vim.fn.indent`
-pub fn indent(lua: &Lua, line: u64) -> LuaResult<u64> {
-    self::get(lua)?.get::<_, LuaFunction>("indent")?.call(line)
+/// `row` is 0-based.
+pub fn indent(lua: &Lua, row: u64) -> LuaResult<u64> {
+    self::get(lua)?
+        .get::<_, LuaFunction>("indent")?
+        .call(row + 1)
 }
 
 /// Corresponds to `vim.fn.shiftwidth`
@@ -94,13 +109,15 @@ impl GetLineResult {
 }
 
 /// Corresponds to `vim.fn.getline`
-pub fn getline(lua: &Lua, line: u64, end: Option<u64>) -> LuaResult<GetLineResult> {
+/// `row` is 0-based. With `end`, returns a table of the rows up to, but not including, `end`, like [`Range`](vim::api::Range).
+pub fn getline(lua: &Lua, row: u64, end: Option<u64>) -> LuaResult<GetLineResult> {
     let val = if let Some(end) = end {
-        self::get(lua)?.call_function("getline", (line, end))?
+        // Vim's inclusive 1-based end is the same number as the exclusive 0-based one
+        self::get(lua)?.call_function("getline", (row + 1, end))?
     } else {
         self::get(lua)?
             .get::<_, LuaFunction>("getline")?
-            .call(line)?
+            .call(row + 1)?
     };
     match &val {
         LuaValue::String(_) => Ok(GetLineResult::String(String::from_lua(val, lua)?)),
@@ -114,10 +131,13 @@ pub fn getline(lua: &Lua, line: u64, end: Option<u64>) -> LuaResult<GetLineResul
 }
 
 /// Corresponds to `vim.fn.setline`
-pub fn setline(lua: &Lua, line: u64, text: &str) -> LuaResult<bool> {
-    self::get(lua)?
+/// `row` is 0-based, and can be the row after the last one to append a line.
+/// Returns `false` if the line could not be set.
+pub fn setline(lua: &Lua, row: u64, text: &str) -> LuaResult<bool> {
+    let result: i64 = self::get(lua)?
         .get::<_, LuaFunction>("setline")?
-        .call((line, text))
+        .call((row + 1, text))?;
+    Ok(result == 0)
 }
 
 /// Corresponds to `vim.fn.getcwd`
@@ -131,3 +151,37 @@ pub fn getcwd(lua: &Lua, win_num: Option<u64>, tab_num: Option<u64>) -> LuaResul
 pub fn mode(lua: &Lua) -> LuaResult<String> {
     vim::func::get(lua)?.call_function("mode", ())
 }
+
+/// Corresponds to `vim.fn.visualmode`
+/// Returns the mode of the last visual selection, or an empty string if there was none.
+pub fn visualmode(lua: &Lua) -> LuaResult<String> {
+    self::get(lua)?.call_function("visualmode", ())
+}
+
+/// Corresponds to `vim.fn.strdisplaywidth`
+/// Returns the number of screen cells `text` takes in the current buffer when it starts at screen column `col`,
+/// which changes the width of tabs.
+pub fn strdisplaywidth(lua: &Lua, text: &str, col: u64) -> LuaResult<u64> {
+    self::get(lua)?.call_function("strdisplaywidth", (text, col))
+}
+
+/// Corresponds to `vim.fn.exists`
+pub fn exists(lua: &Lua, expr: &str) -> LuaResult<bool> {
+    Ok(self::get(lua)?.call_function::<_, _, i64>("exists", expr)? != 0)
+}
+
+/// Corresponds to `vim.fn.getpos`
+/// Returns `None` if the mark in `expr` is not set. Vim's 1-based line and column are converted to a [`Position`].
+pub fn getpos(lua: &Lua, expr: &str) -> LuaResult<Option<Position>> {
+    let pos: [u64; 4] = self::get(lua)?.call_function("getpos", expr)?;
+    let [_bufnum, lnum, col, _off] = pos;
+    Ok((lnum != 0).then(|| Position::from_one_based(lnum, col)))
+}
+
+/// Corresponds to `vim.fn.setpos`
+/// Returns `false` if the position could not be set.
+pub fn setpos(lua: &Lua, expr: &str, pos: Position) -> LuaResult<bool> {
+    let (lnum, col) = pos.to_one_based();
+    let result: i64 = self::get(lua)?.call_function("setpos", (expr, [0, lnum, col, 0]))?;
+    Ok(result == 0)
+}
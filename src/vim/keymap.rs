@@ -1,20 +1,283 @@
 //! Corresponds to `vim.keymap`
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::keymap::{self, KeymapOpts, Mode, Rhs};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let opts = KeymapOpts {
+//!         silent: Some(true),
+//!         desc: Some("Say hello".to_owned()),
+//!         ..Default::default()
+//!     };
+//!     keymap::set(lua, &[Mode::Normal], "<leader>h", Rhs::callback(|lua| log::info(lua, "Hello!")), opts)?;
+//!     keymap::set(lua, &[Mode::Normal, Mode::Visual], "<leader>y", "\"+y", KeymapOpts::default())
+//! }
+//! ```
 
-use crate::prelude::*;
+use std::{fmt, str::FromStr};
+
+use crate::{prelude::*, MaybeSend};
+use vim::api::Buffer;
 
 /// Gets the `vim.keymap` table
-pub fn get(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     vim::get(lua)?.get::<_, LuaTable>("keymap")
 }
 
+/// A mapping mode, see `:h map-modes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Normal, Visual, Select and Operator-pending (`:map`)
+    NormalVisualOperator,
+    /// Normal mode (`n`)
+    Normal,
+    /// Visual and Select mode (`v`)
+    Visual,
+    /// Visual mode only (`x`)
+    VisualOnly,
+    /// Select mode (`s`)
+    Select,
+    /// Operator-pending mode (`o`)
+    OperatorPending,
+    /// Insert mode (`i`)
+    Insert,
+    /// Insert and Command-line mode (`!`)
+    InsertCommand,
+    /// Command-line mode (`c`)
+    Command,
+    /// Insert, Command-line and Lang-Arg mode (`l`)
+    Lang,
+    /// Terminal mode (`t`)
+    Terminal,
+}
+
+impl Mode {
+    /// The short name Neovim uses for the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::NormalVisualOperator => "",
+            Mode::Normal => "n",
+            Mode::Visual => "v",
+            Mode::VisualOnly => "x",
+            Mode::Select => "s",
+            Mode::OperatorPending => "o",
+            Mode::Insert => "i",
+            Mode::InsertCommand => "!",
+            Mode::Command => "c",
+            Mode::Lang => "l",
+            Mode::Terminal => "t",
+        }
+    }
+
+    /// Parses the combined modes of a mapping returned by `nvim_get_keymap`, like `"nox"` after `:sunmap`.
+    /// A space stands for [`Mode::NormalVisualOperator`].
+    pub fn parse_modes(s: &str) -> LuaResult<Vec<Self>> {
+        if s.is_empty() {
+            return Ok(vec![Mode::NormalVisualOperator]);
+        }
+        let mut buf = [0; 4];
+        s.chars().map(|c| c.encode_utf8(&mut buf).parse()).collect()
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Mode {
+    type Err = LuaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "" | " " => Mode::NormalVisualOperator,
+            "n" => Mode::Normal,
+            "v" => Mode::Visual,
+            "x" => Mode::VisualOnly,
+            "s" => Mode::Select,
+            "o" => Mode::OperatorPending,
+            "i" => Mode::Insert,
+            "!" => Mode::InsertCommand,
+            "c" => Mode::Command,
+            "l" => Mode::Lang,
+            "t" => Mode::Terminal,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "Mode",
+                    message: Some(format!("Unknown mode {:?}", s)),
+                })
+            }
+        })
+    }
+}
+
+impl<'a> ToLua<'a> for Mode {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.as_str().to_lua(lua)
+    }
+}
+
+impl<'a> FromLua<'a> for Mode {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        String::from_lua(lua_value, lua)?.parse()
+    }
+}
+
+/// Options for [`set`], see `:h vim.keymap.set()` and `:h :map-arguments`
+///
+/// Fields left as `None` use Neovim's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeymapOpts {
+    /// Makes the mapping local to a buffer
+    pub buffer: Option<Buffer>,
+    pub silent: Option<bool>,
+    pub noremap: Option<bool>,
+    /// Makes the mapping recursive. Inverse of `noremap`, which `vim.keymap.set` enables by default.
+    pub remap: Option<bool>,
+    pub expr: Option<bool>,
+    pub nowait: Option<bool>,
+    pub unique: Option<bool>,
+    pub script: Option<bool>,
+    pub desc: Option<String>,
+    /// Replace keycodes in the result of an `expr` mapping, enabled by default for `expr` mappings
+    pub replace_keycodes: Option<bool>,
+}
+
+impl<'a> ToLua<'a> for KeymapOpts {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        let table = lua.create_table()?;
+        table.set("buffer", self.buffer)?;
+        table.set("silent", self.silent)?;
+        table.set("noremap", self.noremap)?;
+        table.set("remap", self.remap)?;
+        table.set("expr", self.expr)?;
+        table.set("nowait", self.nowait)?;
+        table.set("unique", self.unique)?;
+        table.set("script", self.script)?;
+        table.set("desc", self.desc)?;
+        table.set("replace_keycodes", self.replace_keycodes)?;
+        lua.pack(table)
+    }
+}
+
+/// The signature of a mapping callback
+pub type KeymapCallback = maybe_send_box!(dyn Fn(&Lua) -> LuaResult<()>);
+
+/// The signature of an `expr` mapping callback, which returns the keys to execute
+pub type KeymapExprCallback = maybe_send_box!(dyn Fn(&Lua) -> LuaResult<String>);
+
+/// The right-hand side of a mapping
+pub enum Rhs {
+    /// A key sequence
+    Keys(String),
+    /// A Rust function to run
+    Callback(KeymapCallback),
+    /// A Rust function returning the keys to run, this implies `expr`
+    Expr(KeymapExprCallback),
+}
+
+impl Rhs {
+    /// Creates a right-hand side that runs a Rust function
+    pub fn callback<F>(callback: F) -> Self
+    where
+        F: Fn(&Lua) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        Rhs::Callback(Box::new(callback))
+    }
+
+    /// Creates a right-hand side that runs the keys returned by a Rust function
+    pub fn expr<F>(callback: F) -> Self
+    where
+        F: Fn(&Lua) -> LuaResult<String> + MaybeSend + 'static,
+    {
+        Rhs::Expr(Box::new(callback))
+    }
+}
+
+impl fmt::Debug for Rhs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rhs::Keys(keys) => f.debug_tuple("Keys").field(keys).finish(),
+            Rhs::Callback(_) => f.debug_tuple("Callback").finish_non_exhaustive(),
+            Rhs::Expr(_) => f.debug_tuple("Expr").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl From<&str> for Rhs {
+    fn from(keys: &str) -> Self {
+        Rhs::Keys(keys.to_owned())
+    }
+}
+
+impl From<String> for Rhs {
+    fn from(keys: String) -> Self {
+        Rhs::Keys(keys)
+    }
+}
+
+impl<'a> ToLua<'a> for Rhs {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        match self {
+            Rhs::Keys(keys) => keys.to_lua(lua),
+            Rhs::Callback(callback) => lua
+                .create_function(move |lua, ()| callback(lua))?
+                .to_lua(lua),
+            Rhs::Expr(callback) => lua
+                .create_function(move |lua, ()| callback(lua))?
+                .to_lua(lua),
+        }
+    }
+}
+
 /// Corresponds to `vim.keymap.set`
 pub fn set(
     lua: &Lua,
-    mode: LuaValue,
-    lhs: LuaValue,
-    rhs: LuaValue,
-    opts: Option<LuaValue>,
+    modes: &[Mode],
+    lhs: &str,
+    rhs: impl Into<Rhs>,
+    mut opts: KeymapOpts,
+) -> LuaResult<()> {
+    let rhs = rhs.into();
+    if let Rhs::Expr(_) = rhs {
+        opts.expr = Some(true);
+    }
+    self::get(lua)?.call_function("set", (modes.to_vec(), lhs, rhs, opts))
+}
+
+/// Corresponds to `vim.keymap.set` with `opts.buffer` set
+pub fn buf_set(
+    lua: &Lua,
+    buffer: Buffer,
+    modes: &[Mode],
+    lhs: &str,
+    rhs: impl Into<Rhs>,
+    opts: KeymapOpts,
 ) -> LuaResult<()> {
-    self::get(lua)?.call_function("set", (mode, lhs, rhs, opts))
+    self::set(
+        lua,
+        modes,
+        lhs,
+        rhs,
+        KeymapOpts {
+            buffer: Some(buffer),
+            ..opts
+        },
+    )
 }
 
+/// Corresponds to `vim.keymap.del`
+pub fn del(lua: &Lua, modes: &[Mode], lhs: &str) -> LuaResult<()> {
+    self::get(lua)?.call_function("del", (modes.to_vec(), lhs))
+}
+
+/// Corresponds to `vim.keymap.del` with `opts.buffer` set
+pub fn buf_del(lua: &Lua, buffer: Buffer, modes: &[Mode], lhs: &str) -> LuaResult<()> {
+    let opts = lua.create_table()?;
+    opts.set("buffer", buffer)?;
+    self::get(lua)?.call_function("del", (modes.to_vec(), lhs, opts))
+}
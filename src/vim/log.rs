@@ -2,6 +2,7 @@
 
 /// The log level of a message.
 /// Corresponds to `vim.log.levels`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum LogLevel {
     Trace = 0,
@@ -10,4 +11,4 @@ pub enum LogLevel {
     Warn = 3,
     Error = 4,
     Off = 5,
-}
\ No newline at end of file
+}
@@ -12,10 +12,45 @@ pub mod ext;
 pub mod func;
 pub mod keymap;
 pub mod log;
+pub mod opt;
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub mod runtime;
+#[cfg(all(feature = "send", unix))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "send", unix))))]
+pub mod thread;
+pub mod uv;
 pub mod v;
+pub mod var;
+
+pub use var::{b, g, t, w};
+
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::prelude::*;
 
+/// Serializes a value for passing to the Neovim api, where `None` must become `nil` rather than `vim.NIL`
+pub(crate) fn to_api_value<'a, T: Serialize + ?Sized>(
+    lua: &'a Lua,
+    value: &T,
+) -> LuaResult<LuaValue<'a>> {
+    lua.to_value_with(
+        value,
+        LuaSerializeOptions::new()
+            .set_array_metatable(false)
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false),
+    )
+}
+
+/// Deserializes a value returned by the Neovim api, skipping values like functions that have no serde equivalent
+pub(crate) fn from_api_value<T: DeserializeOwned>(lua: &Lua, value: LuaValue) -> LuaResult<T> {
+    lua.from_value_with(
+        value,
+        LuaDeserializeOptions::new().deny_unsupported_types(false),
+    )
+}
+
 /// Get global `vim`
 ///
 /// ## Example
@@ -28,7 +63,7 @@ use crate::prelude::*;
 ///     Ok(())
 /// }
 /// ```
-pub fn get(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     lua.globals().get("vim")
 }
 
@@ -74,3 +109,9 @@ pub fn inspect<'a>(lua: &'a Lua, value: impl ToLua<'a>) -> LuaResult<String> {
 pub fn notify(lua: &Lua, msg: &str, log_level: log::LogLevel) -> LuaResult<()> {
     self::get(lua)?.call_function("notify", (msg, log_level as u8))
 }
+
+/// Corresponds to `vim.schedule()`
+/// Runs the callback on the main loop, where it's safe to call the Neovim api from `vim.uv` callbacks.
+pub fn schedule<'a>(lua: &'a Lua, callback: LuaFunction<'a>) -> LuaResult<()> {
+    self::get(lua)?.call_function("schedule", callback)
+}
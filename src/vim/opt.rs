@@ -0,0 +1,387 @@
+//! Typed access to Neovim options, similar to `vim.o`, `vim.bo`, `vim.wo`, `vim.go` and `vim.opt`
+//!
+//! Options are read and written with `nvim_get_option_value` and `nvim_set_option_value`,
+//! falling back to `vim.o` and friends on versions of Neovim that don't have them.
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::api::Buffer;
+//! use nvim_utils::vim::opt::{self, CommaList, OptionScope};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let buf = Buffer::current(lua)?;
+//!     let expandtab: bool = opt::get(lua, "expandtab", OptionScope::Buffer(buf))?;
+//!     if !expandtab {
+//!         opt::set(lua, "shiftwidth", 8, OptionScope::Buffer(buf))?;
+//!     }
+//!     let completeopt: CommaList = opt::get(lua, "completeopt", OptionScope::Global)?;
+//!     if !completeopt.contains("noselect") {
+//!         opt::append(lua, "completeopt", &["noselect"], OptionScope::Global)?;
+//!     }
+//!     opt::remove(lua, "shortmess", &["I"], OptionScope::Global)?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+use vim::api::{Buffer, OptionValueOpts, Window};
+
+/// Which value of an option to access, see `:h option-summary`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionScope {
+    /// The global value, like `:setglobal`
+    Global,
+    /// The local value for the current buffer or window, like `:setlocal`
+    Local,
+    /// The value local to a buffer, like `vim.bo[buf]`
+    Buffer(Buffer),
+    /// The value local to a window, like `vim.wo[win]`
+    Window(Window),
+}
+
+impl From<OptionScope> for OptionValueOpts {
+    fn from(scope: OptionScope) -> Self {
+        match scope {
+            OptionScope::Global => OptionValueOpts {
+                scope: Some("global".to_owned()),
+                ..Default::default()
+            },
+            OptionScope::Local => OptionValueOpts {
+                scope: Some("local".to_owned()),
+                ..Default::default()
+            },
+            OptionScope::Buffer(buf) => OptionValueOpts::buf(buf),
+            OptionScope::Window(win) => OptionValueOpts::win(win),
+        }
+    }
+}
+
+/// An error returned when accessing an option
+#[derive(Debug, Clone)]
+pub enum OptionError {
+    /// The option does not exist
+    Unknown { name: String },
+    /// The option's value could not be converted to or from the requested type
+    Type {
+        name: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// [`append`], [`prepend`] or [`remove`] was used on an option that is not a comma or flag list
+    NotAList { name: String },
+    /// Any other error raised by Neovim
+    Lua(LuaError),
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionError::Unknown { name } => write!(f, "unknown option '{}'", name),
+            OptionError::Type {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "option '{}' has type {}, expected {}",
+                name, found, expected
+            ),
+            OptionError::NotAList { name } => write!(f, "option '{}' is not a list", name),
+            OptionError::Lua(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OptionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OptionError::Lua(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<LuaError> for OptionError {
+    fn from(err: LuaError) -> Self {
+        OptionError::Lua(err)
+    }
+}
+
+impl From<OptionError> for LuaError {
+    fn from(err: OptionError) -> Self {
+        match err {
+            OptionError::Lua(err) => err,
+            err => LuaError::external(err),
+        }
+    }
+}
+
+/// The type of an option's value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionType {
+    Boolean,
+    Number,
+    String,
+}
+
+/// Information about an option, returned by [`info`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OptionInfo {
+    pub name: String,
+    pub shortname: String,
+    #[serde(rename = "type")]
+    pub kind: OptionType,
+    /// Either `"global"`, `"buf"` or `"win"`
+    pub scope: String,
+    /// Whether a global option can also have a local value
+    pub global_local: bool,
+    /// Whether the value is a comma separated list, like `'completeopt'`
+    pub commalist: bool,
+    /// Whether the value is a list of single character flags, like `'shortmess'`
+    pub flaglist: bool,
+    pub was_set: bool,
+}
+
+/// A comma separated list option, like `'completeopt'` or `'wildmode'`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CommaList(pub Vec<String>);
+
+impl CommaList {
+    /// Checks if the list contains an item
+    pub fn contains(&self, item: &str) -> bool {
+        self.0.iter().any(|i| i == item)
+    }
+}
+
+impl From<&str> for CommaList {
+    fn from(value: &str) -> Self {
+        Self(
+            value
+                .split(',')
+                .filter(|item| !item.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for CommaList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join(","))
+    }
+}
+
+impl<'a> FromLua<'a> for CommaList {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        Ok(Self::from(String::from_lua(lua_value, lua)?.as_str()))
+    }
+}
+
+impl<'a> ToLua<'a> for CommaList {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.to_string().to_lua(lua)
+    }
+}
+
+/// A list of single character flags, like `'shortmess'` or `'formatoptions'`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FlagList(pub Vec<char>);
+
+impl FlagList {
+    /// Checks if a flag is set
+    pub fn contains(&self, flag: char) -> bool {
+        self.0.contains(&flag)
+    }
+}
+
+impl From<&str> for FlagList {
+    fn from(value: &str) -> Self {
+        Self(value.chars().collect())
+    }
+}
+
+impl fmt::Display for FlagList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.iter().collect::<String>())
+    }
+}
+
+impl<'a> FromLua<'a> for FlagList {
+    fn from_lua(lua_value: LuaValue<'a>, lua: &'a Lua) -> LuaResult<Self> {
+        Ok(Self::from(String::from_lua(lua_value, lua)?.as_str()))
+    }
+}
+
+impl<'a> ToLua<'a> for FlagList {
+    fn to_lua(self, lua: &'a Lua) -> LuaResult<LuaValue<'a>> {
+        self.to_string().to_lua(lua)
+    }
+}
+
+/// Checks if the running Neovim has `nvim_get_option_value` and `nvim_set_option_value`
+fn has_option_value_api(lua: &Lua) -> LuaResult<bool> {
+    vim::api::get(lua)?.contains_key("nvim_get_option_value")
+}
+
+/// Returns an error if the option doesn't exist.
+/// Uses `exists("&name")`, since `exists("+name")` is false for options that exist but aren't supported by the build.
+fn check_exists(lua: &Lua, name: &str) -> Result<(), OptionError> {
+    if vim::func::exists(lua, &format!("&{}", name))? {
+        Ok(())
+    } else {
+        Err(OptionError::Unknown {
+            name: name.to_owned(),
+        })
+    }
+}
+
+/// Gets the `vim.o`-style table to use for a scope on older versions of Neovim
+fn fallback_table<'a>(lua: &'a Lua, name: &str, scope: OptionScope) -> LuaResult<LuaTable<'a>> {
+    let vim = vim::get(lua)?;
+    match scope {
+        OptionScope::Global => vim.get("go"),
+        OptionScope::Local => match info(lua, name)?.scope.as_str() {
+            "buf" => vim.get("bo"),
+            "win" => vim.get("wo"),
+            _ => vim.get("o"),
+        },
+        OptionScope::Buffer(buf) => vim.get::<_, LuaTable>("bo")?.get(buf),
+        OptionScope::Window(win) => vim.get::<_, LuaTable>("wo")?.get(win),
+    }
+}
+
+/// Gets information about an option, using `vim.api.nvim_get_option_info2` or `vim.api.nvim_get_option_info`
+pub fn info(lua: &Lua, name: &str) -> Result<OptionInfo, OptionError> {
+    check_exists(lua, name)?;
+    let api = vim::api::get(lua)?;
+    let info: LuaValue = if api.contains_key("nvim_get_option_info2")? {
+        api.call_function("nvim_get_option_info2", (name, lua.create_table()?))?
+    } else {
+        api.call_function("nvim_get_option_info", name)?
+    };
+    Ok(lua.from_value(info)?)
+}
+
+/// Gets the value of an option
+pub fn get<'a, T: FromLua<'a>>(
+    lua: &'a Lua,
+    name: &str,
+    scope: OptionScope,
+) -> Result<T, OptionError> {
+    check_exists(lua, name)?;
+    let value: LuaValue = if has_option_value_api(lua)? {
+        vim::api::nvim_get_option_value(lua, name, scope.into())?
+    } else {
+        fallback_table(lua, name, scope)?.get(name)?
+    };
+    T::from_lua(value, lua).map_err(|err| match err {
+        LuaError::FromLuaConversionError { from, to, .. } => OptionError::Type {
+            name: name.to_owned(),
+            expected: to,
+            found: from,
+        },
+        err => OptionError::Lua(err),
+    })
+}
+
+/// Sets the value of an option
+pub fn set<'a>(
+    lua: &'a Lua,
+    name: &str,
+    value: impl ToLua<'a>,
+    scope: OptionScope,
+) -> Result<(), OptionError> {
+    check_exists(lua, name)?;
+    if has_option_value_api(lua)? {
+        vim::api::nvim_set_option_value(lua, name, value, scope.into())?;
+    } else {
+        fallback_table(lua, name, scope)?.set(name, value)?;
+    }
+    Ok(())
+}
+
+/// Applies `f` to the items of a comma or flag list option, then writes the result back
+fn modify_list(
+    lua: &Lua,
+    name: &str,
+    scope: OptionScope,
+    f: impl FnOnce(&mut Vec<String>),
+) -> Result<(), OptionError> {
+    let info = info(lua, name)?;
+    let value: String = get(lua, name, scope)?;
+    if info.commalist {
+        let mut items = CommaList::from(value.as_str()).0;
+        f(&mut items);
+        set(lua, name, CommaList(items), scope)
+    } else if info.flaglist {
+        let mut flags = value.chars().map(String::from).collect();
+        f(&mut flags);
+        set(lua, name, flags.concat(), scope)
+    } else {
+        Err(OptionError::NotAList {
+            name: name.to_owned(),
+        })
+    }
+}
+
+/// Splits flag list items like `"aI"` into single flags, leaving comma list items as they are
+fn split_items(lua: &Lua, name: &str, items: &[&str]) -> Result<Vec<String>, OptionError> {
+    Ok(if info(lua, name)?.flaglist {
+        items
+            .iter()
+            .flat_map(|i| i.chars())
+            .map(String::from)
+            .collect()
+    } else {
+        items.iter().map(|i| i.to_string()).collect()
+    })
+}
+
+/// Adds items to the end of a list option, like `vim.opt.name:append()` or `:set name+=`
+///
+/// Items that are already present are moved to the end.
+pub fn append(
+    lua: &Lua,
+    name: &str,
+    items: &[&str],
+    scope: OptionScope,
+) -> Result<(), OptionError> {
+    let new = split_items(lua, name, items)?;
+    modify_list(lua, name, scope, |list| {
+        list.retain(|i| !new.contains(i));
+        list.extend(new.iter().cloned());
+    })
+}
+
+/// Adds items to the start of a list option, like `vim.opt.name:prepend()` or `:set name^=`
+///
+/// Items that are already present are moved to the start.
+pub fn prepend(
+    lua: &Lua,
+    name: &str,
+    items: &[&str],
+    scope: OptionScope,
+) -> Result<(), OptionError> {
+    let new = split_items(lua, name, items)?;
+    modify_list(lua, name, scope, |list| {
+        list.retain(|i| !new.contains(i));
+        list.splice(0..0, new.iter().cloned());
+    })
+}
+
+/// Removes items from a list option, like `vim.opt.name:remove()` or `:set name-=`
+pub fn remove(
+    lua: &Lua,
+    name: &str,
+    items: &[&str],
+    scope: OptionScope,
+) -> Result<(), OptionError> {
+    let old = split_items(lua, name, items)?;
+    modify_list(lua, name, scope, |list| list.retain(|i| !old.contains(i)))
+}
@@ -0,0 +1,669 @@
+//! An executor for Rust futures, driven by Neovim's event loop
+//!
+//! Tasks are polled in `vim.schedule` callbacks, so they can call the Neovim api at any point.
+//! Wakeups from loop callbacks are picked up by a `vim.uv` check handle, and [`sleep`] uses `vim.uv` timers.
+//! Wakeups from other threads are only noticed once the loop handles another event,
+//! so threads should send their results through `vim::thread::MainThreadHandle` instead.
+//!
+//! Every Lua state has its own executor, which is only driven by that state's callbacks.
+//! Tasks don't borrow the state: they get it with [`with_lua`] while they're polled.
+//!
+//! Async functions added with [`ModuleBuilder::add_fn_async`](crate::builder::ModuleBuilder::add_fn_async)
+//! are spawned on this executor when called from Lua.
+//!
+//! ## Example
+//! ```rust
+//! use std::time::Duration;
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::runtime;
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     runtime::spawn(lua, async move {
+//!         runtime::sleep(Duration::from_millis(500)).await?;
+//!         runtime::with_lua(|lua| log::info(lua, "Half a second later"))
+//!     })?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use crate::prelude::*;
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Task ids that were woken and need to be polled
+#[derive(Default)]
+struct ReadyQueue(Mutex<VecDeque<u64>>);
+
+impl ReadyQueue {
+    fn push(&self, id: u64) {
+        let mut queue = self.0.lock().unwrap();
+        if !queue.contains(&id) {
+            queue.push_back(id);
+        }
+    }
+
+    fn take(&self) -> VecDeque<u64> {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+}
+
+struct TaskWaker {
+    id: u64,
+    queue: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.push(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.id);
+    }
+}
+
+/// The executor of one Lua state
+struct Executor {
+    tasks: HashMap<u64, Task>,
+    next_id: u64,
+    queue: Arc<ReadyQueue>,
+    /// The function passed to `vim.schedule` to poll ready tasks
+    run: LuaRegistryKey,
+    /// Whether `run` is already scheduled
+    scheduled: bool,
+}
+
+thread_local! {
+    // Tasks aren't `Send`, so executors stay on the thread of their state instead of in its app data,
+    // which only holds an `ExecutorId`.
+    static EXECUTORS: RefCell<HashMap<u64, Executor>> = RefCell::new(HashMap::new());
+    static NEXT_EXECUTOR: Cell<u64> = const { Cell::new(0) };
+    // The state whose tasks are being polled, see `with_lua`
+    static CURRENT: Cell<Option<*const Lua>> = const { Cell::new(None) };
+}
+
+/// Identifies the executor of a Lua state in its app data, and drops the executor's tasks when the state is dropped
+struct ExecutorId(u64);
+
+impl Drop for ExecutorId {
+    fn drop(&mut self) {
+        // Tasks are dropped outside of the borrow, since dropping them may spawn or wake other tasks
+        let executor = EXECUTORS
+            .try_with(|executors| executors.borrow_mut().remove(&self.0))
+            .ok()
+            .flatten();
+        drop(executor);
+    }
+}
+
+/// Runs `f` with mutable access to the executor of `lua`, if it was created
+fn with_executor<R>(lua: &Lua, f: impl FnOnce(&mut Executor) -> R) -> Option<R> {
+    let id = lua.app_data_ref::<ExecutorId>()?.0;
+    EXECUTORS.with(|executors| executors.borrow_mut().get_mut(&id).map(f))
+}
+
+/// Creates the executor of `lua`, with the function that polls ready tasks and the check handle that schedules it
+fn init(lua: &Lua) -> LuaResult<()> {
+    if lua.app_data_ref::<ExecutorId>().is_some() {
+        return Ok(());
+    }
+
+    let queue = Arc::new(ReadyQueue::default());
+    let run = lua.create_function(|lua, ()| run_ready(lua))?;
+    let run = lua.create_registry_value(run)?;
+
+    let check_queue = queue.clone();
+    let on_check = lua.create_function(move |lua, ()| {
+        if !check_queue.is_empty() {
+            schedule_run(lua)?;
+        }
+        Ok(())
+    })?;
+    let uv = vim::uv::get(lua)?;
+    let check: LuaValue = uv.call_function("new_check", ())?;
+    uv.call_function::<_, _, ()>("check_start", (check.clone(), on_check))?;
+    // Don't keep the loop alive just for the executor. The check keeps running after the state's executor is dropped,
+    // but then finds nothing to schedule.
+    uv.call_function::<_, _, ()>("unref", check)?;
+
+    let id = NEXT_EXECUTOR.with(|next| next.replace(next.get() + 1));
+    EXECUTORS.with(|executors| {
+        executors.borrow_mut().insert(
+            id,
+            Executor {
+                tasks: HashMap::new(),
+                next_id: 0,
+                queue,
+                run,
+                scheduled: false,
+            },
+        )
+    });
+    lua.set_app_data(ExecutorId(id));
+    Ok(())
+}
+
+/// Schedules polling the ready tasks with `vim.schedule`, unless it's already scheduled
+fn schedule_run(lua: &Lua) -> LuaResult<()> {
+    let run = with_executor(lua, |executor| {
+        if executor.scheduled {
+            return Ok(None);
+        }
+        executor.scheduled = true;
+        lua.registry_value::<LuaFunction>(&executor.run).map(Some)
+    });
+    match run.transpose()?.flatten() {
+        Some(run) => vim::schedule(lua, run),
+        None => Ok(()),
+    }
+}
+
+/// Makes `lua` available to [`with_lua`] until the guard is dropped
+struct CurrentGuard(Option<*const Lua>);
+
+impl CurrentGuard {
+    fn set(lua: &Lua) -> Self {
+        Self(CURRENT.with(|current| current.replace(Some(lua as *const Lua))))
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+/// Polls every task that was woken since the last run
+fn run_ready(lua: &Lua) -> LuaResult<()> {
+    let Some(queue) = with_executor(lua, |executor| {
+        executor.scheduled = false;
+        executor.queue.clone()
+    }) else {
+        return Ok(());
+    };
+
+    let _current = CurrentGuard::set(lua);
+    for id in queue.take() {
+        // The task is taken out while it's polled, so it can spawn other tasks
+        let Some(mut task) = with_executor(lua, |executor| executor.tasks.remove(&id)).flatten()
+        else {
+            continue;
+        };
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id,
+            queue: queue.clone(),
+        }));
+        if task
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending()
+        {
+            with_executor(lua, |executor| executor.tasks.insert(id, task));
+        }
+    }
+
+    // Tasks woken while polling, for example by `schedule`, run on the next iteration of the loop
+    if !queue.is_empty() {
+        schedule_run(lua)?;
+    }
+    Ok(())
+}
+
+/// Calls `f` with the Lua state whose task is being polled
+///
+/// Tasks can't borrow the state they were spawned on, since it may be dropped before them,
+/// so they use this to call Lua instead. Returns an error when called outside of a task or [`block_on`].
+pub fn with_lua<R>(f: impl FnOnce(&Lua) -> LuaResult<R>) -> LuaResult<R> {
+    match CURRENT.with(Cell::get) {
+        // SAFETY: `CURRENT` is only set while a task is polled, during which the state is borrowed,
+        // and `f` can't keep references to it.
+        Some(lua) => f(unsafe { &*lua }),
+        None => Err(LuaError::RuntimeError(
+            "vim::runtime::with_lua called outside of a task".to_owned(),
+        )),
+    }
+}
+
+/// Spawns a future on the executor of `lua`, returning a handle to await its output
+///
+/// The future is first polled in a `vim.schedule` callback, not during this call.
+/// It can't borrow `lua`, use [`with_lua`] to call Lua from the task.
+pub fn spawn<F>(lua: &Lua, future: F) -> LuaResult<JoinHandle<F::Output>>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    init(lua)?;
+    let (sender, receiver) = oneshot::channel();
+    let task: Task = Box::pin(async move {
+        let _ = sender.send(future.await);
+    });
+
+    with_executor(lua, |executor| {
+        let id = executor.next_id;
+        executor.next_id += 1;
+        executor.tasks.insert(id, task);
+        executor.queue.push(id);
+    });
+    schedule_run(lua)?;
+    Ok(JoinHandle(receiver))
+}
+
+/// A handle to a task spawned with [`spawn`], which can be awaited for its output
+///
+/// Dropping the handle doesn't cancel the task.
+#[derive(Debug)]
+pub struct JoinHandle<T>(oneshot::Receiver<T>);
+
+impl<T> JoinHandle<T> {
+    /// Whether the task has finished
+    pub fn is_finished(&self) -> bool {
+        self.0.is_ready()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, oneshot::Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Runs a future to completion, processing Neovim's events and other tasks while it's pending
+///
+/// This uses `vim.wait`, so it can't be called from `vim.uv` callbacks or from a task.
+/// It's intended for tests and scripts run with `nvim --headless`, and blocks the editor while waiting.
+/// The future can use [`with_lua`] like a task.
+pub fn block_on<F: Future>(lua: &Lua, future: F) -> LuaResult<F::Output> {
+    let future = RefCell::new(Box::pin(future));
+    let output = RefCell::new(None);
+    let waker = Waker::from(Arc::new(NoopWaker));
+
+    lua.scope(|scope| {
+        let poll = scope.create_function(|lua, ()| {
+            let _current = CurrentGuard::set(lua);
+            let mut output = output.borrow_mut();
+            if output.is_none() {
+                let mut cx = Context::from_waker(&waker);
+                if let Poll::Ready(value) = future.borrow_mut().as_mut().poll(&mut cx) {
+                    *output = Some(value);
+                }
+            }
+            Ok(output.is_some())
+        })?;
+        let wait: LuaFunction = vim::get(lua)?.get("wait")?;
+        while !wait.call::<_, bool>((1000, poll.clone(), 1))? {}
+        Ok(())
+    })?;
+
+    Ok(output
+        .into_inner()
+        .expect("block_on returned before the future completed"))
+}
+
+struct TimerState {
+    fired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// A future that completes after a duration, created by [`sleep`]
+pub struct Sleep {
+    duration: Duration,
+    state: Arc<TimerState>,
+    /// The timer, once it was started. It closes itself when it fires, and is closed early if the future is dropped.
+    timer: Option<LuaRegistryKey>,
+}
+
+impl Future for Sleep {
+    type Output = LuaResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.state.fired.load(Ordering::Acquire) {
+            return Poll::Ready(Ok(()));
+        }
+        *self.state.waker.lock().unwrap() = Some(cx.waker().clone());
+        if self.timer.is_none() {
+            match with_lua(|lua| self.start(lua)) {
+                Ok(timer) => self.timer = Some(timer),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Sleep {
+    fn start(&self, lua: &Lua) -> LuaResult<LuaRegistryKey> {
+        let state = self.state.clone();
+        let on_fire = lua.create_function(move |lua, ()| {
+            state.fired.store(true, Ordering::Release);
+            if let Some(waker) = state.waker.lock().unwrap().take() {
+                waker.wake();
+            }
+            // Timers run before the loop waits for events, so the check handle would only see this wakeup
+            // after the next event
+            schedule_run(lua)
+        })?;
+        let timer: LuaValue = lua
+            .load(
+                r#"
+                local uv, timeout, on_fire = ...
+                local timer = uv.new_timer()
+                uv.timer_start(timer, timeout, 0, function()
+                    uv.close(timer)
+                    on_fire()
+                end)
+                return timer
+                "#,
+            )
+            .call((
+                vim::uv::get(lua)?,
+                self.duration.as_millis() as u64,
+                on_fire,
+            ))?;
+        lua.create_registry_value(timer)
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        let Some(timer) = self.timer.take() else {
+            return;
+        };
+        if self.state.fired.load(Ordering::Acquire) {
+            return;
+        }
+        // Futures are usually dropped by their task, while it's polled. Otherwise the state is gone,
+        // or the timer fires later without waking anything and closes itself.
+        let _ = with_lua(|lua| {
+            let uv = vim::uv::get(lua)?;
+            let timer: LuaValue = lua.registry_value(&timer)?;
+            if !uv.call_function::<_, _, bool>("is_closing", timer.clone())? {
+                uv.call_function::<_, _, ()>("close", timer)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Waits for a duration using a `vim.uv` timer. It has to be awaited in a task or [`block_on`].
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        duration,
+        state: Arc::new(TimerState {
+            fired: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        }),
+        timer: None,
+    }
+}
+
+/// A future that yields once, created by [`schedule`]
+#[derive(Debug, Default)]
+pub struct Schedule {
+    yielded: bool,
+}
+
+impl Future for Schedule {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Yields to Neovim's main loop, letting it handle input and redraw before the task continues
+pub fn schedule() -> Schedule {
+    Schedule::default()
+}
+
+/// Wraps an async Rust function in a Lua function that runs it on the executor
+///
+/// When called from a coroutine, the coroutine is suspended until the future completes and then receives its results
+/// or its error. Otherwise the call returns immediately and errors are reported with `vim.notify`.
+///
+/// `func` is called with the arguments right away, and can use `lua` before returning the future.
+/// The future runs as a task, so it can't borrow `lua`, see [`with_lua`].
+pub fn create_async_function<'lua, A, R, F, FR>(
+    lua: &'lua Lua,
+    func: F,
+) -> LuaResult<LuaFunction<'lua>>
+where
+    F: 'static + Send + Fn(&'lua Lua, A) -> FR,
+    A: FromLuaMulti<'lua>,
+    R: for<'r> ToLuaMulti<'r>,
+    FR: 'static + Future<Output = LuaResult<R>>,
+{
+    let start = lua.create_function(
+        move |lua, (caller, args): (Option<LuaThread>, LuaMultiValue)| {
+            let future = func(lua, A::from_lua_multi(args, lua)?);
+            let caller = caller
+                .map(|caller| lua.create_registry_value(caller))
+                .transpose()?;
+            spawn(lua, async move {
+                let result = future.await;
+                let _ = with_lua(|lua| {
+                    let caller = caller
+                        .map(|caller| lua.registry_value::<LuaThread>(&caller))
+                        .transpose()?;
+                    let result = result.and_then(|ret| ret.to_lua_multi(lua));
+                    if let Err(e) = finish(lua, caller, result) {
+                        log::error(lua, &e.to_string())?;
+                    }
+                    Ok(())
+                });
+            })?;
+            Ok(())
+        },
+    )?;
+    lua.load(
+        r#"
+        local start = ...
+        local function finish(ok, ...)
+            if ok then
+                return ...
+            end
+            error((...), 0)
+        end
+        return function(...)
+            local co, main = coroutine.running()
+            if co == nil or main then
+                start(nil, ...)
+                return
+            end
+            start(co, ...)
+            return finish(coroutine.yield())
+        end
+        "#,
+    )
+    .call(start)
+}
+
+/// Resumes the coroutine that called an async function with its results, or reports the error if there's none
+fn finish<'lua>(
+    lua: &'lua Lua,
+    caller: Option<LuaThread<'lua>>,
+    result: LuaResult<LuaMultiValue<'lua>>,
+) -> LuaResult<()> {
+    let Some(caller) = caller else {
+        return result.map(|_| ());
+    };
+    let values = match result {
+        Ok(values) => {
+            let mut values = values.into_vec();
+            values.insert(0, LuaValue::Boolean(true));
+            values
+        }
+        Err(e) => vec![
+            LuaValue::Boolean(false),
+            LuaValue::String(lua.create_string(&e.to_string())?),
+        ],
+    };
+    caller.resume::<_, LuaMultiValue>(LuaMultiValue::from_vec(values))?;
+    Ok(())
+}
+
+pub mod oneshot {
+    //! A channel for sending a single value between tasks or threads
+
+    use std::{
+        fmt,
+        future::Future,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll, Waker},
+    };
+
+    use crate::prelude::*;
+
+    struct Inner<T> {
+        value: Option<T>,
+        waker: Option<Waker>,
+        closed: bool,
+    }
+
+    /// The sending half of a channel created by [`channel`]
+    pub struct Sender<T>(Arc<Mutex<Inner<T>>>);
+
+    /// The receiving half of a channel created by [`channel`], which can be awaited for the value
+    pub struct Receiver<T>(Arc<Mutex<Inner<T>>>);
+
+    /// The error returned by a [`Receiver`] when its [`Sender`] was dropped without sending a value
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Canceled;
+
+    impl fmt::Display for Canceled {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("the sender was dropped without sending a value")
+        }
+    }
+
+    impl std::error::Error for Canceled {}
+
+    impl From<Canceled> for LuaError {
+        fn from(e: Canceled) -> Self {
+            LuaError::external(e)
+        }
+    }
+
+    /// Creates a channel for sending a single value
+    pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        let inner = Arc::new(Mutex::new(Inner {
+            value: None,
+            waker: None,
+            closed: false,
+        }));
+        (Sender(inner.clone()), Receiver(inner))
+    }
+
+    impl<T> Sender<T> {
+        /// Sends the value, returning it back if the receiver was dropped
+        pub fn send(self, value: T) -> Result<(), T> {
+            let mut inner = self.0.lock().unwrap();
+            if inner.closed {
+                return Err(value);
+            }
+            inner.value = Some(value);
+            if let Some(waker) = inner.waker.take() {
+                waker.wake();
+            }
+            Ok(())
+        }
+
+        /// Whether the receiver was dropped
+        pub fn is_closed(&self) -> bool {
+            self.0.lock().unwrap().closed
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut inner = self.0.lock().unwrap();
+            inner.closed = true;
+            if let Some(waker) = inner.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    impl<T> Receiver<T> {
+        /// Whether a value was sent or the sender was dropped, so awaiting won't wait
+        pub fn is_ready(&self) -> bool {
+            let inner = self.0.lock().unwrap();
+            inner.value.is_some() || inner.closed
+        }
+
+        /// Takes the value if it was sent, without waiting
+        pub fn try_recv(&mut self) -> Option<T> {
+            self.0.lock().unwrap().value.take()
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().closed = true;
+        }
+    }
+
+    impl<T> Future for Receiver<T> {
+        type Output = Result<T, Canceled>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut inner = self.0.lock().unwrap();
+            if let Some(value) = inner.value.take() {
+                Poll::Ready(Ok(value))
+            } else if inner.closed {
+                Poll::Ready(Err(Canceled))
+            } else {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    impl<T> fmt::Debug for Sender<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Sender")
+                .field("closed", &self.is_closed())
+                .finish()
+        }
+    }
+
+    impl<T> fmt::Debug for Receiver<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Receiver")
+                .field("ready", &self.is_ready())
+                .finish()
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Running closures on Neovim's main thread from other threads
+//!
+//! The `Lua` state must only be used from the main thread. A [`MainThreadHandle`] can be cloned and sent to
+//! worker threads, which queue closures on it. A `vim.uv` async handle wakes the main loop immediately,
+//! and the closures run in a `vim.schedule` callback with the `Lua` state, where the whole Neovim api is available.
+//!
+//! Waking the loop calls libuv's `uv_async_send` directly, which is resolved from the Neovim executable when the
+//! plugin is loaded. Neovim only exports libuv's symbols on Unix, so this module isn't available on Windows.
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::thread::MainThreadHandle;
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let main = MainThreadHandle::new(lua)?;
+//!     std::thread::spawn(move || {
+//!         let count = (0..1_000_000u64).filter(|n| n % 7 == 0).count();
+//!         let _ = main.send(move |lua| log::info(lua, &format!("Indexed {} items", count)));
+//!     });
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    fmt,
+    os::raw::c_int,
+    sync::{Arc, Mutex},
+};
+
+use crate::prelude::*;
+
+extern "C" {
+    // Resolved from the Neovim executable, which exports libuv's symbols on Unix
+    fn uv_async_send(handle: *mut c_void) -> c_int;
+}
+
+/// The signature of a closure queued with [`MainThreadHandle::send`]
+pub type MainThreadCallback = Box<dyn FnOnce(&Lua) -> LuaResult<()> + Send + 'static>;
+
+/// The error returned by [`MainThreadHandle::send`] after the handle was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the main thread handle was closed")
+    }
+}
+
+impl std::error::Error for Closed {}
+
+impl From<Closed> for LuaError {
+    fn from(e: Closed) -> Self {
+        LuaError::external(e)
+    }
+}
+
+/// A pointer to the `uv_async_t` owned by the luv handle
+struct AsyncPtr(*mut c_void);
+
+// SAFETY: `uv_async_send` is the one libuv function that may be called from any thread,
+// and the pointer is only used while the handle is open.
+unsafe impl Send for AsyncPtr {}
+
+struct State {
+    queue: VecDeque<MainThreadCallback>,
+    /// `None` once the handle is closed
+    handle: Option<AsyncPtr>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// The luv handle, kept to close it from the main thread
+    key: Mutex<Option<LuaRegistryKey>>,
+}
+
+/// Finds the `uv_async_t` of a luv async handle
+///
+/// Older versions of luv store the libuv handle in the userdata and newer ones store a pointer to it,
+/// so it's read from `tostring(handle)`, which is `"uv_async_t: 0x..."` with the handle's address in both.
+fn async_ptr(lua: &Lua, handle: &LuaValue) -> LuaResult<*mut c_void> {
+    let name: String = lua
+        .globals()
+        .get::<_, LuaFunction>("tostring")?
+        .call(handle.clone())?;
+    name.strip_prefix("uv_async_t: ")
+        .map(|addr| addr.trim_start_matches("0x"))
+        .and_then(|addr| usize::from_str_radix(addr, 16).ok())
+        .filter(|&addr| addr != 0)
+        .map(|addr| addr as *mut c_void)
+        .ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "vim.uv.new_async did not return an async handle, got {}",
+                name
+            ))
+        })
+}
+
+/// A cloneable, thread-safe handle for running closures on Neovim's main thread
+///
+/// Closures run in the order they were sent. Errors they return are reported with `vim.notify`.
+#[derive(Clone)]
+pub struct MainThreadHandle {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for MainThreadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.state.lock().unwrap();
+        f.debug_struct("MainThreadHandle")
+            .field("queued", &state.queue.len())
+            .field("closed", &state.handle.is_none())
+            .finish()
+    }
+}
+
+impl MainThreadHandle {
+    /// Creates a handle, using `vim.uv.new_async`. Must be called on the main thread.
+    pub fn new(lua: &Lua) -> LuaResult<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                handle: None,
+            }),
+            key: Mutex::new(None),
+        });
+
+        let callback_shared = shared.clone();
+        let run = lua.create_function(move |lua, ()| {
+            // Take the whole queue first, so closures can send more without deadlocking
+            let queue = std::mem::take(&mut callback_shared.state.lock().unwrap().queue);
+            for f in queue {
+                if let Err(e) = f(lua) {
+                    log::error(lua, &e.to_string())?;
+                }
+            }
+            Ok(())
+        })?;
+        // Async callbacks run where most of the api isn't allowed
+        let callback: LuaFunction = vim::get(lua)?.call_function("schedule_wrap", run)?;
+
+        let uv = vim::uv::get(lua)?;
+        let handle: LuaValue = uv.call_function("new_async", callback)?;
+        let ptr = async_ptr(lua, &handle)?;
+        // Don't keep the loop alive just for this handle
+        uv.call_function::<_, _, ()>("unref", handle.clone())?;
+        shared.state.lock().unwrap().handle = Some(AsyncPtr(ptr));
+        *shared.key.lock().unwrap() = Some(lua.create_registry_value(handle)?);
+
+        Ok(Self { shared })
+    }
+
+    /// Queues a closure to run on the main thread, and wakes the main loop.
+    /// Can be called from any thread.
+    pub fn send<F>(&self, f: F) -> Result<(), Closed>
+    where
+        F: FnOnce(&Lua) -> LuaResult<()> + Send + 'static,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        let handle = state.handle.as_ref().ok_or(Closed)?.0;
+        state.queue.push_back(Box::new(f));
+        // Holding the lock keeps `close` from closing the handle during the call
+        unsafe { uv_async_send(handle) };
+        Ok(())
+    }
+
+    /// Whether [`MainThreadHandle::close`] was called on this handle or a clone of it
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().handle.is_none()
+    }
+
+    /// Closes the async handle, after which [`MainThreadHandle::send`] fails.
+    /// Closures that were already queued still run, and their errors are reported like in the async callback.
+    /// Must be called on the main thread.
+    pub fn close(&self, lua: &Lua) -> LuaResult<()> {
+        let key = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.handle = None;
+            self.shared.key.lock().unwrap().take()
+        };
+        let Some(key) = key else {
+            return Ok(());
+        };
+        let handle: LuaValue = lua.registry_value(&key)?;
+        lua.remove_registry_value(key)?;
+        // Run what was queued before closing, since the async callback won't be called again
+        let queue = std::mem::take(&mut self.shared.state.lock().unwrap().queue);
+        for f in queue {
+            if let Err(e) = f(lua) {
+                // Keep going, so the handle is closed even if reporting the error fails
+                let _ = log::error(lua, &e.to_string());
+            }
+        }
+        vim::uv::get(lua)?.call_function("close", handle)
+    }
+}
@@ -0,0 +1,712 @@
+//! Corresponds to `vim.uv`, Neovim's bindings to libuv
+//!
+//! Handles are closed when they're dropped, so they have to be kept alive for as long as their callbacks should run,
+//! or detached to keep them open after they're dropped.
+//! Callbacks run in the event loop, where most of the Neovim api isn't allowed, see [`vim::schedule`].
+//!
+//! ## Example
+//! ```rust
+//! use std::time::Duration;
+//! use nvim_utils::prelude::*;
+//! use nvim_utils::vim::uv::{Command, FsEvent, FsEventFlags, Timer};
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let timer = Timer::new(lua)?;
+//!     timer.start(Duration::from_secs(1), Duration::ZERO, |lua| log::info(lua, "One second passed"))?;
+//!     // Otherwise the timer would be closed before it fires, when `my_module` returns
+//!     timer.detach();
+//!
+//!     let watcher = FsEvent::new(lua)?;
+//!     watcher.start("Cargo.toml", FsEventFlags::default(), |lua, event| {
+//!         log::info(lua, &format!("Changed: {:?}", event?.filename))
+//!     })?;
+//!     watcher.detach();
+//!
+//!     let process = Command::new("cargo")
+//!         .arg("check")
+//!         .on_stderr(|lua, line| log::info(lua, line))
+//!         .on_exit(|lua, status| log::info(lua, &format!("cargo exited with {}", status.code)))
+//!         .spawn(lua)?;
+//!     // Keep the process running after `process` goes out of scope
+//!     process.detach();
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{prelude::*, MaybeSend};
+
+/// Gets the `vim.uv` table, or `vim.loop` on versions of Neovim before 0.10
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+    let vim = vim::get(lua)?;
+    match vim.get::<_, Option<LuaTable>>("uv")? {
+        Some(uv) => Ok(uv),
+        None => vim.get("loop"),
+    }
+}
+
+/// An error passed to a libuv callback, like `"ENOENT: no such file or directory"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UvError(pub String);
+
+impl fmt::Display for UvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UvError {}
+
+impl From<UvError> for LuaError {
+    fn from(e: UvError) -> Self {
+        LuaError::external(e)
+    }
+}
+
+/// Converts the `nil, err` pair returned by failing luv functions into an error
+fn check<'lua>(result: LuaMultiValue<'lua>, what: &str) -> LuaResult<LuaValue<'lua>> {
+    let mut values = result.into_iter();
+    match (values.next(), values.next()) {
+        (Some(LuaValue::Nil) | None, Some(LuaValue::String(err))) => Err(LuaError::RuntimeError(
+            format!("{} failed: {}", what, err.to_string_lossy()),
+        )),
+        (Some(value), _) => Ok(value),
+        (None, _) => Ok(LuaValue::Nil),
+    }
+}
+
+/// A luv handle that's closed when dropped, unless it's detached
+struct Handle<'lua> {
+    lua: &'lua Lua,
+    handle: LuaValue<'lua>,
+    /// Shared with callbacks that close a detached handle once it's done
+    detached: Arc<AtomicBool>,
+}
+
+impl<'lua> Handle<'lua> {
+    /// Creates a handle with a `uv.new_*` function
+    fn new(lua: &'lua Lua, constructor: &str, args: impl ToLuaMulti<'lua>) -> LuaResult<Self> {
+        let handle = check(get(lua)?.call_function(constructor, args)?, constructor)?;
+        Ok(Self {
+            lua,
+            handle,
+            detached: Arc::default(),
+        })
+    }
+
+    /// Drops the handle without closing it, when something else closes it later
+    fn keep_open(self) {
+        self.detached.store(true, Ordering::Release);
+    }
+
+    fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Acquire)
+    }
+
+    /// Calls a luv function with the handle as its first argument
+    fn call<R: FromLuaMulti<'lua>>(&self, name: &str, args: impl ToLuaMulti<'lua>) -> LuaResult<R> {
+        let mut args = args.to_lua_multi(self.lua)?.into_vec();
+        args.insert(0, self.handle.clone());
+        get(self.lua)?.call_function(name, LuaMultiValue::from_vec(args))
+    }
+
+    /// Like [`Handle::call`], for functions that return `0` or `nil, err`
+    fn call_checked(&self, name: &str, args: impl ToLuaMulti<'lua>) -> LuaResult<()> {
+        check(self.call(name, args)?, name).map(|_| ())
+    }
+
+    fn is_active(&self) -> LuaResult<bool> {
+        self.call("is_active", ())
+    }
+
+    fn close(&self) -> LuaResult<()> {
+        if !self.call::<bool>("is_closing", ())? {
+            self.call::<()>("close", ())?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Handle<'_> {
+    fn drop(&mut self) {
+        if !self.is_detached() {
+            let _ = self.close();
+        }
+    }
+}
+
+/// Closes a handle kept in the registry, when it's closed from its own callback
+fn close_registered(lua: &Lua, key: LuaRegistryKey) -> LuaResult<()> {
+    let handle: LuaValue = lua.registry_value(&key)?;
+    lua.remove_registry_value(key)?;
+    let uv = get(lua)?;
+    if !uv.call_function::<_, _, bool>("is_closing", handle.clone())? {
+        uv.call_function::<_, _, ()>("close", handle)?;
+    }
+    Ok(())
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// A timer, corresponds to `vim.uv.new_timer`
+pub struct Timer<'lua>(Handle<'lua>);
+
+impl<'lua> Timer<'lua> {
+    pub fn new(lua: &'lua Lua) -> LuaResult<Self> {
+        Handle::new(lua, "new_timer", ()).map(Self)
+    }
+
+    /// Runs `callback` after `timeout`, and then every `repeat` unless it's zero
+    pub fn start<F>(&self, timeout: Duration, repeat: Duration, callback: F) -> LuaResult<()>
+    where
+        F: Fn(&Lua) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let lua = self.0.lua;
+        let detached = self.0.detached.clone();
+        let key = Mutex::new(Some(lua.create_registry_value(self.0.handle.clone())?));
+        let callback = lua.create_function(move |lua, ()| {
+            let result = callback(lua);
+            // Nothing can restart a detached timer, so it's closed once it stops
+            if detached.load(Ordering::Acquire) {
+                let mut key = key.lock().unwrap();
+                if let Some(timer) = &*key {
+                    let timer: LuaValue = lua.registry_value(timer)?;
+                    if !get(lua)?.call_function::<_, _, bool>("is_active", timer)? {
+                        close_registered(lua, key.take().unwrap())?;
+                    }
+                }
+            }
+            result
+        })?;
+        self.0
+            .call_checked("timer_start", (millis(timeout), millis(repeat), callback))
+    }
+
+    pub fn stop(&self) -> LuaResult<()> {
+        self.0.call_checked("timer_stop", ())
+    }
+
+    /// Restarts a repeating timer with its repeat interval as the timeout
+    pub fn again(&self) -> LuaResult<()> {
+        self.0.call_checked("timer_again", ())
+    }
+
+    /// Sets the repeat interval, which takes effect the next time the timer fires
+    pub fn set_repeat(&self, repeat: Duration) -> LuaResult<()> {
+        self.0.call("timer_set_repeat", millis(repeat))
+    }
+
+    pub fn get_repeat(&self) -> LuaResult<Duration> {
+        self.0
+            .call("timer_get_repeat", ())
+            .map(Duration::from_millis)
+    }
+
+    /// The time until the timer fires, zero if it's not active
+    pub fn get_due_in(&self) -> LuaResult<Duration> {
+        self.0
+            .call("timer_get_due_in", ())
+            .map(Duration::from_millis)
+    }
+
+    pub fn is_active(&self) -> LuaResult<bool> {
+        self.0.is_active()
+    }
+
+    /// Keeps the timer open after it's dropped, so its callback still runs.
+    /// It's closed once it stops, after firing if `repeat` is zero.
+    pub fn detach(self) {
+        self.0.keep_open();
+    }
+}
+
+/// Options for [`FsEvent::start`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsEventFlags {
+    /// Watch subdirectories too, only supported on macOS and Windows
+    pub recursive: bool,
+    /// Watch the directory entry of `path` instead of its contents
+    pub watch_entry: bool,
+    /// Use stat polling instead of native file system events
+    pub stat: bool,
+}
+
+/// A change reported by [`FsEvent`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsEventArgs {
+    /// The changed file, relative to the watched directory. Not provided on every platform.
+    pub filename: Option<String>,
+    pub change: bool,
+    pub rename: bool,
+}
+
+/// A file system watcher using native events, corresponds to `vim.uv.new_fs_event`
+pub struct FsEvent<'lua>(Handle<'lua>);
+
+impl<'lua> FsEvent<'lua> {
+    pub fn new(lua: &'lua Lua) -> LuaResult<Self> {
+        Handle::new(lua, "new_fs_event", ()).map(Self)
+    }
+
+    /// Watches a file or directory, calling `callback` on every change
+    pub fn start<F>(
+        &self,
+        path: impl Into<PathBuf>,
+        flags: FsEventFlags,
+        callback: F,
+    ) -> LuaResult<()>
+    where
+        F: Fn(&Lua, Result<FsEventArgs, UvError>) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let lua = self.0.lua;
+        type Args<'a> = (Option<String>, Option<String>, Option<LuaTable<'a>>);
+        let callback = lua.create_function(move |lua, (err, filename, events): Args| {
+            let flag = |name: &str| -> LuaResult<bool> {
+                match &events {
+                    Some(events) => Ok(events.get::<_, Option<bool>>(name)?.unwrap_or(false)),
+                    None => Ok(false),
+                }
+            };
+            let args = match err {
+                Some(err) => Err(UvError(err)),
+                None => Ok(FsEventArgs {
+                    filename,
+                    change: flag("change")?,
+                    rename: flag("rename")?,
+                }),
+            };
+            callback(lua, args)
+        })?;
+        let opts = lua.create_table()?;
+        opts.set("recursive", flags.recursive)?;
+        opts.set("watch_entry", flags.watch_entry)?;
+        opts.set("stat", flags.stat)?;
+        let path = path.into().to_string_lossy().into_owned();
+        self.0
+            .call_checked("fs_event_start", (path, opts, callback))
+    }
+
+    pub fn stop(&self) -> LuaResult<()> {
+        self.0.call_checked("fs_event_stop", ())
+    }
+
+    pub fn is_active(&self) -> LuaResult<bool> {
+        self.0.is_active()
+    }
+
+    /// Keeps the watcher open after it's dropped, so it watches until Neovim exits
+    pub fn detach(self) {
+        self.0.keep_open();
+    }
+}
+
+/// A timestamp in a [`FsStat`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct Timespec {
+    pub sec: i64,
+    pub nsec: i64,
+}
+
+/// File metadata, as returned by `vim.uv.fs_stat`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FsStat {
+    pub size: u64,
+    pub mode: u32,
+    pub mtime: Timespec,
+    pub ctime: Timespec,
+    /// `"file"`, `"directory"`, `"link"` etc.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// The stats before and after a change reported by [`FsPoll`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsPollEvent {
+    pub prev: FsStat,
+    pub curr: FsStat,
+}
+
+/// A file watcher using stat polling, corresponds to `vim.uv.new_fs_poll`
+///
+/// Works on every file system, unlike [`FsEvent`], but only notices changes every `interval`.
+pub struct FsPoll<'lua>(Handle<'lua>);
+
+impl<'lua> FsPoll<'lua> {
+    pub fn new(lua: &'lua Lua) -> LuaResult<Self> {
+        Handle::new(lua, "new_fs_poll", ()).map(Self)
+    }
+
+    /// Checks `path` every `interval`, calling `callback` when its stats change
+    pub fn start<F>(
+        &self,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+        callback: F,
+    ) -> LuaResult<()>
+    where
+        F: Fn(&Lua, Result<FsPollEvent, UvError>) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let callback = self.0.lua.create_function(
+            move |lua, (err, prev, curr): (Option<String>, LuaValue, LuaValue)| {
+                let event = match err {
+                    Some(err) => Err(UvError(err)),
+                    None => Ok(FsPollEvent {
+                        prev: vim::from_api_value(lua, prev)?,
+                        curr: vim::from_api_value(lua, curr)?,
+                    }),
+                };
+                callback(lua, event)
+            },
+        )?;
+        let path = path.into().to_string_lossy().into_owned();
+        self.0
+            .call_checked("fs_poll_start", (path, millis(interval), callback))
+    }
+
+    pub fn stop(&self) -> LuaResult<()> {
+        self.0.call_checked("fs_poll_stop", ())
+    }
+
+    pub fn is_active(&self) -> LuaResult<bool> {
+        self.0.is_active()
+    }
+
+    /// Keeps the watcher open after it's dropped, so it polls until Neovim exits
+    pub fn detach(self) {
+        self.0.keep_open();
+    }
+}
+
+/// An async handle, corresponds to `vim.uv.new_async`
+///
+/// [`Async::send`] has to be called from the main thread, use
+/// [`MainThreadHandle`](crate::vim::thread::MainThreadHandle) to wake the loop from other threads.
+pub struct Async<'lua>(Handle<'lua>);
+
+impl<'lua> Async<'lua> {
+    /// Creates an async handle that runs `callback` after [`Async::send`].
+    /// Multiple sends before the callback runs only run it once.
+    pub fn new<F>(lua: &'lua Lua, callback: F) -> LuaResult<Self>
+    where
+        F: Fn(&Lua) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        let callback = lua.create_function(move |lua, ()| callback(lua))?;
+        Handle::new(lua, "new_async", callback).map(Self)
+    }
+
+    pub fn send(&self) -> LuaResult<()> {
+        self.0.call_checked("async_send", ())
+    }
+
+    /// Keeps the handle open after it's dropped, so sends queued before then still run the callback.
+    /// It stays open until Neovim exits.
+    pub fn detach(self) {
+        self.0.keep_open();
+    }
+}
+
+/// How a process exited, passed to [`Command::on_exit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExitStatus {
+    pub code: i64,
+    /// The signal that terminated the process, or `0`
+    pub signal: i64,
+}
+
+impl ExitStatus {
+    pub fn success(&self) -> bool {
+        self.code == 0 && self.signal == 0
+    }
+}
+
+/// The signature of a callback receiving output line by line
+pub type LineCallback = maybe_send_box!(dyn Fn(&Lua, &str) -> LuaResult<()>);
+
+/// The signature of a callback receiving the exit status of a process
+pub type ExitCallback = maybe_send_box!(dyn Fn(&Lua, ExitStatus) -> LuaResult<()>);
+
+/// A builder for spawning a process with `vim.uv.spawn`
+///
+/// Output is delivered line by line without the trailing newline. A final line without a newline is delivered
+/// when the output is closed, before `on_exit` if the process closed its output before exiting.
+#[derive(Default)]
+pub struct Command {
+    program: String,
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    detached: bool,
+    on_stdout: Option<LineCallback>,
+    on_stderr: Option<LineCallback>,
+    on_exit: Option<ExitCallback>,
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Command")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("cwd", &self.cwd)
+            .field("env", &self.env)
+            .field("detached", &self.detached)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Command {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Sets an environment variable. If any are set, the process doesn't inherit Neovim's environment.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Runs the process in its own process group, so it can outlive Neovim
+    pub fn detached(mut self, detached: bool) -> Self {
+        self.detached = detached;
+        self
+    }
+
+    pub fn on_stdout<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, &str) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.on_stdout = Some(Box::new(callback));
+        self
+    }
+
+    pub fn on_stderr<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, &str) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.on_stderr = Some(Box::new(callback));
+        self
+    }
+
+    pub fn on_exit<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Lua, ExitStatus) -> LuaResult<()> + MaybeSend + 'static,
+    {
+        self.on_exit = Some(Box::new(callback));
+        self
+    }
+
+    /// Starts the process
+    pub fn spawn(self, lua: &Lua) -> LuaResult<Process<'_>> {
+        let uv = get(lua)?;
+        let stdout = self.on_stdout.map(|cb| open_pipe(lua, cb)).transpose()?;
+        let stderr = self.on_stderr.map(|cb| open_pipe(lua, cb)).transpose()?;
+
+        let opts = lua.create_table()?;
+        opts.set("args", self.args)?;
+        if let Some(cwd) = self.cwd {
+            opts.set("cwd", cwd.to_string_lossy().into_owned())?;
+        }
+        if !self.env.is_empty() {
+            let env: Vec<String> = self
+                .env
+                .into_iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            opts.set("env", env)?;
+        }
+        opts.set("detached", self.detached)?;
+        // luv only reads as many entries as the length of `stdio`, which would be 0 for `{ [3] = pipe }`,
+        // so it's built with a constructor that always gives it a length of 3
+        let [stdout_pipe, stderr_pipe] = [&stdout, &stderr].map(|pipe| {
+            pipe.as_ref()
+                .map_or(LuaValue::Nil, |(pipe, _)| pipe.handle.clone())
+        });
+        let stdio: LuaTable = lua
+            .load("local stdin, stdout, stderr = ... return { stdin, stdout, stderr }")
+            .call((LuaValue::Nil, stdout_pipe, stderr_pipe))?;
+        opts.set("stdio", stdio)?;
+
+        // Set once spawned, so the exit callback can close a detached process
+        let key: Arc<Mutex<Option<LuaRegistryKey>>> = Arc::default();
+        let detached: Arc<AtomicBool> = Arc::default();
+        let exit_key = key.clone();
+        let exit_detached = detached.clone();
+        let on_exit = self.on_exit;
+        let exit = lua.create_function(move |lua, (code, signal): (i64, i64)| {
+            let result = match &on_exit {
+                Some(on_exit) => on_exit(lua, ExitStatus { code, signal }),
+                None => Ok(()),
+            };
+            if exit_detached.load(Ordering::Acquire) {
+                if let Some(key) = exit_key.lock().unwrap().take() {
+                    close_registered(lua, key)?;
+                }
+            }
+            result
+        })?;
+
+        let result: LuaMultiValue =
+            uv.call_function("spawn", (self.program.as_str(), opts, exit))?;
+        let mut values = result.into_iter();
+        let (handle, pid) = match (values.next(), values.next()) {
+            (Some(LuaValue::UserData(handle)), Some(pid)) => (LuaValue::UserData(handle), pid),
+            (_, err) => {
+                let err = err.map_or(Ok(String::new()), |err| String::from_lua(err, lua))?;
+                return Err(LuaError::RuntimeError(format!(
+                    "failed to spawn {}: {}",
+                    self.program, err
+                )));
+            }
+        };
+        // Wrapped right away, so the process handle is closed if anything below fails
+        let handle = Handle {
+            lua,
+            handle,
+            detached,
+        };
+        *key.lock().unwrap() = Some(lua.create_registry_value(handle.handle.clone())?);
+        let pid = u32::from_lua(pid, lua)?;
+
+        for (pipe, callback) in [stdout, stderr].into_iter().flatten() {
+            pipe.call_checked("read_start", callback)?;
+            // The pipe closes itself at the end of the output
+            pipe.keep_open();
+        }
+
+        Ok(Process { handle, pid })
+    }
+}
+
+/// Creates a pipe and the `read_start` callback that splits its output into lines
+fn open_pipe<'lua>(
+    lua: &'lua Lua,
+    callback: LineCallback,
+) -> LuaResult<(Handle<'lua>, LuaFunction<'lua>)> {
+    let pipe = Handle::new(lua, "new_pipe", false)?;
+    let pipe_key = lua.create_registry_value(pipe.handle.clone())?;
+    let buffer = Mutex::new(LineBuffer::new());
+    let read = lua.create_function(
+        move |lua, (err, data): (Option<String>, Option<LuaString>)| {
+            if let Some(err) = err {
+                return Err(LuaError::external(UvError(err)));
+            }
+            let mut buffer = buffer.lock().unwrap();
+            match data {
+                Some(data) => {
+                    for line in buffer.push(data.as_bytes()) {
+                        callback(lua, &line)?;
+                    }
+                }
+                None => {
+                    if let Some(line) = buffer.finish() {
+                        callback(lua, &line)?;
+                    }
+                    let pipe: LuaValue = lua.registry_value(&pipe_key)?;
+                    get(lua)?.call_function::<_, _, ()>("close", pipe)?;
+                }
+            }
+            Ok(())
+        },
+    )?;
+    Ok((pipe, read))
+}
+
+/// Splits chunks of output into lines, as they're delivered by [`Command`]
+///
+/// Lines end at `\n`, and a `\r` before it is removed. Invalid UTF-8 is replaced, once a line is complete,
+/// so characters split across chunks are kept intact.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineBuffer {
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk of output, returning the lines it completed
+    pub fn push(&mut self, data: &[u8]) -> Vec<String> {
+        self.partial.extend_from_slice(data);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while let Some(end) = self.partial[start..].iter().position(|&b| b == b'\n') {
+            let end = start + end;
+            lines.push(Self::line(&self.partial[start..end]));
+            start = end + 1;
+        }
+        self.partial.drain(..start);
+        lines
+    }
+
+    /// Takes the last line if the output didn't end with a newline
+    pub fn finish(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let line = Self::line(&self.partial);
+        self.partial.clear();
+        Some(line)
+    }
+
+    fn line(bytes: &[u8]) -> String {
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// A running process, created by [`Command::spawn`]
+///
+/// Dropping it closes the process handle, so `on_exit` won't be called, but doesn't kill the process.
+/// Use [`Process::detach`] to keep the handle open until the process exits.
+pub struct Process<'lua> {
+    handle: Handle<'lua>,
+    pid: u32,
+}
+
+impl Process<'_> {
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Sends a signal like `"sigterm"` or `"sigkill"` to the process
+    pub fn kill(&self, signal: &str) -> LuaResult<()> {
+        self.handle.call_checked("process_kill", signal)
+    }
+
+    /// Keeps the process handle open until the process exits, so `on_exit` is still called
+    pub fn detach(self) {
+        self.handle.keep_open();
+    }
+}
@@ -1,9 +1,54 @@
+//! Corresponds to `vim.v`, see `:h vim-variable`
+use serde::de::DeserializeOwned;
+
 use crate::prelude::*;
 
-pub fn get(lua: &Lua) -> LuaResult<LuaTable> {
+/// Gets the `vim.v` table
+pub fn get(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     vim::get(lua)?.get::<_, LuaTable>("v")
 }
 
+/// Gets any `v:` variable
+pub fn var<T: DeserializeOwned>(lua: &Lua, name: &str) -> LuaResult<T> {
+    vim::from_api_value(lua, self::get(lua)?.get(name)?)
+}
+
+/// Corresponds to `v:count`, the count given to the last normal mode command, or 0
 pub fn count(lua: &Lua) -> LuaResult<u64> {
     self::get(lua)?.get::<_, u64>("count")
 }
+
+/// Corresponds to `v:count1`, like [`count`] but defaults to 1
+pub fn count1(lua: &Lua) -> LuaResult<u64> {
+    self::get(lua)?.get::<_, u64>("count1")
+}
+
+/// Corresponds to `v:register`, the register given to the current normal mode command
+pub fn register(lua: &Lua) -> LuaResult<String> {
+    self::get(lua)?.get::<_, String>("register")
+}
+
+/// Corresponds to `v:event`, the event-specific data of the current autocommand
+pub fn event<T: DeserializeOwned>(lua: &Lua) -> LuaResult<T> {
+    self::var(lua, "event")
+}
+
+/// Corresponds to `v:char`, the character typed in `InsertCharPre` or the argument of some events
+pub fn char(lua: &Lua) -> LuaResult<String> {
+    self::get(lua)?.get::<_, String>("char")
+}
+
+/// Corresponds to `v:operator`, the last operator given in normal mode
+pub fn operator(lua: &Lua) -> LuaResult<String> {
+    self::get(lua)?.get::<_, String>("operator")
+}
+
+/// Corresponds to `v:shell_error`, the exit code of the last shell command
+pub fn shell_error(lua: &Lua) -> LuaResult<i64> {
+    self::get(lua)?.get::<_, i64>("shell_error")
+}
+
+/// Corresponds to `v:servername`, the primary RPC address of this Neovim instance
+pub fn servername(lua: &Lua) -> LuaResult<String> {
+    self::get(lua)?.get::<_, String>("servername")
+}
@@ -0,0 +1,163 @@
+//! Serde-backed access to Vim variables, corresponds to `vim.g`, `vim.b`, `vim.w` and `vim.t`
+//!
+//! Values are converted with serde, so any type implementing [`Deserialize`](serde::Deserialize) or [`Serialize`] can be used.
+//! Reading a variable that doesn't exist yields `nil`, so use `Option<T>` for variables that may be unset.
+//!
+//! ## Example
+//! ```rust
+//! use nvim_utils::prelude::*;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, Default)]
+//! #[serde(default)]
+//! struct Config {
+//!     enabled: bool,
+//!     width: u64,
+//! }
+//!
+//! fn my_module(lua: &Lua) -> LuaResult<()> {
+//!     let config: Option<Config> = vim::g::get(lua, "myplugin_config")?;
+//!     let config = config.unwrap_or_default();
+//!     vim::b::set(lua, vim::api::Buffer::CURRENT, "myplugin_width", &config.width)?;
+//!     vim::g::set(lua, "loaded_myplugin", &true)
+//! }
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::*;
+
+fn get_in<T: DeserializeOwned>(lua: &Lua, table: LuaTable, name: &str) -> LuaResult<T> {
+    vim::from_api_value(lua, table.get(name)?)
+}
+
+fn set_in<T: Serialize + ?Sized>(
+    lua: &Lua,
+    table: LuaTable,
+    name: &str,
+    value: &T,
+) -> LuaResult<()> {
+    table.set(name, vim::to_api_value(lua, value)?)
+}
+
+fn del_in(table: LuaTable, name: &str) -> LuaResult<()> {
+    table.set(name, LuaValue::Nil)
+}
+
+/// Global (`g:`) variables, corresponds to `vim.g`
+pub mod g {
+    use super::*;
+
+    /// Gets the `vim.g` table
+    pub fn get_table(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+        vim::get(lua)?.get("g")
+    }
+
+    /// Gets a global variable
+    pub fn get<T: DeserializeOwned>(lua: &Lua, name: &str) -> LuaResult<T> {
+        get_in(lua, get_table(lua)?, name)
+    }
+
+    /// Sets a global variable
+    pub fn set<T: Serialize + ?Sized>(lua: &Lua, name: &str, value: &T) -> LuaResult<()> {
+        set_in(lua, get_table(lua)?, name, value)
+    }
+
+    /// Removes a global variable
+    pub fn del(lua: &Lua, name: &str) -> LuaResult<()> {
+        del_in(get_table(lua)?, name)
+    }
+}
+
+/// Buffer-scoped (`b:`) variables, corresponds to `vim.b`
+pub mod b {
+    use super::*;
+    use vim::api::Buffer;
+
+    /// Gets the `vim.b` table for a buffer
+    pub fn get_table(lua: &Lua, buffer: Buffer) -> LuaResult<LuaTable<'_>> {
+        vim::get(lua)?.get::<_, LuaTable>("b")?.get(buffer)
+    }
+
+    /// Gets a buffer-scoped variable
+    pub fn get<T: DeserializeOwned>(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<T> {
+        get_in(lua, get_table(lua, buffer)?, name)
+    }
+
+    /// Sets a buffer-scoped variable
+    pub fn set<T: Serialize + ?Sized>(
+        lua: &Lua,
+        buffer: Buffer,
+        name: &str,
+        value: &T,
+    ) -> LuaResult<()> {
+        set_in(lua, get_table(lua, buffer)?, name, value)
+    }
+
+    /// Removes a buffer-scoped variable
+    pub fn del(lua: &Lua, buffer: Buffer, name: &str) -> LuaResult<()> {
+        del_in(get_table(lua, buffer)?, name)
+    }
+}
+
+/// Window-scoped (`w:`) variables, corresponds to `vim.w`
+pub mod w {
+    use super::*;
+    use vim::api::Window;
+
+    /// Gets the `vim.w` table for a window
+    pub fn get_table(lua: &Lua, window: Window) -> LuaResult<LuaTable<'_>> {
+        vim::get(lua)?.get::<_, LuaTable>("w")?.get(window)
+    }
+
+    /// Gets a window-scoped variable
+    pub fn get<T: DeserializeOwned>(lua: &Lua, window: Window, name: &str) -> LuaResult<T> {
+        get_in(lua, get_table(lua, window)?, name)
+    }
+
+    /// Sets a window-scoped variable
+    pub fn set<T: Serialize + ?Sized>(
+        lua: &Lua,
+        window: Window,
+        name: &str,
+        value: &T,
+    ) -> LuaResult<()> {
+        set_in(lua, get_table(lua, window)?, name, value)
+    }
+
+    /// Removes a window-scoped variable
+    pub fn del(lua: &Lua, window: Window, name: &str) -> LuaResult<()> {
+        del_in(get_table(lua, window)?, name)
+    }
+}
+
+/// Tabpage-scoped (`t:`) variables, corresponds to `vim.t`
+pub mod t {
+    use super::*;
+    use vim::api::Tabpage;
+
+    /// Gets the `vim.t` table for a tabpage
+    pub fn get_table(lua: &Lua, tabpage: Tabpage) -> LuaResult<LuaTable<'_>> {
+        vim::get(lua)?.get::<_, LuaTable>("t")?.get(tabpage)
+    }
+
+    /// Gets a tabpage-scoped variable
+    pub fn get<T: DeserializeOwned>(lua: &Lua, tabpage: Tabpage, name: &str) -> LuaResult<T> {
+        get_in(lua, get_table(lua, tabpage)?, name)
+    }
+
+    /// Sets a tabpage-scoped variable
+    pub fn set<T: Serialize + ?Sized>(
+        lua: &Lua,
+        tabpage: Tabpage,
+        name: &str,
+        value: &T,
+    ) -> LuaResult<()> {
+        set_in(lua, get_table(lua, tabpage)?, name, value)
+    }
+
+    /// Removes a tabpage-scoped variable
+    pub fn del(lua: &Lua, tabpage: Tabpage, name: &str) -> LuaResult<()> {
+        del_in(get_table(lua, tabpage)?, name)
+    }
+}
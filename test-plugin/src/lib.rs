@@ -1,11 +1,14 @@
 use nvim_utils::prelude::*;
+#[cfg(all(feature = "send", unix))]
+use nvim_utils::vim::thread::MainThreadHandle;
+use nvim_utils::vim::uv::Command;
 
 fn hello(lua: &Lua, _args: ()) -> LuaResult<()> {
     log::info(lua, "Hello from Rust and NeoVim!\n")?;
     Ok(())
 }
 
-fn get_plugin_info(lua: &Lua, _args: ()) -> LuaResult<LuaTable> {
+fn get_plugin_info(lua: &Lua, _args: ()) -> LuaResult<LuaTable<'_>> {
     ModuleBuilder::new(lua)
         .with_string("name", "example")?
         .with_string("version", "0.1.0")?
@@ -16,15 +19,64 @@ fn get_plugin_info(lua: &Lua, _args: ()) -> LuaResult<LuaTable> {
         .build()
 }
 
-/// The #[lua_module] attribute generates an entry point for the plugin.
-#[mlua::lua_module]
-pub fn test_plugin(lua: &Lua) -> LuaResult<LuaTable> {
+fn spawn_stderr_only(lua: &Lua, _args: ()) -> LuaResult<()> {
+    Command::new("sh")
+        .args(["-c", "echo out; echo err >&2"])
+        .on_stderr(|lua, line| {
+            // Output callbacks run in the event loop, where variables can't be set
+            let line = line.to_owned();
+            let set =
+                lua.create_function(move |lua, ()| vim::var::g::set(lua, "stderr_line", &line))?;
+            vim::schedule(lua, set)
+        })
+        .spawn(lua)?
+        .detach();
+    Ok(())
+}
+
+#[cfg(all(feature = "send", unix))]
+fn send_from_thread(lua: &Lua, value: i64) -> LuaResult<()> {
+    let main = MainThreadHandle::new(lua)?;
+    let worker = main.clone();
+    std::thread::spawn(move || {
+        let _ = worker.send(move |lua| {
+            vim::var::g::set(lua, "from_thread", &value)?;
+            main.close(lua)
+        });
+    });
+    Ok(())
+}
+
+#[cfg(all(feature = "send", unix))]
+fn close_after_error(lua: &Lua, _args: ()) -> LuaResult<()> {
+    let main = MainThreadHandle::new(lua)?;
+    main.send(|_| Err(LuaError::RuntimeError("expected failure".to_owned())))?;
+    main.send(|lua| vim::var::g::set(lua, "after_error", &true))?;
+    main.close(lua)?;
+    if !main.is_closed() || vim::var::g::get::<Option<bool>>(lua, "after_error")? != Some(true) {
+        return Err(LuaError::RuntimeError(
+            "close stopped at the failing closure".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// The #[module] attribute generates an entry point for the plugin, and exports its version for `cargo nvim`'s loader.
+#[nvim_utils::module(test_plugin)]
+pub fn test_plugin(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     // Create a new module builder
-    ModuleBuilder::new(lua)
+    let mut module = ModuleBuilder::new(lua);
+    module
         // Add the hello function to the module
-        .with_fn("hello", hello)?
+        .add_fn("hello", hello)?
         // Add the get_plugin_info function to the module
-        .with_fn("get_plugin_info", get_plugin_info)?
-        // Build the module
-        .build()
+        .add_fn("get_plugin_info", get_plugin_info)?
+        .add_fn("spawn_stderr_only", spawn_stderr_only)?;
+    // Functions for testing the main thread handle, which needs the `send` feature
+    #[cfg(all(feature = "send", unix))]
+    module
+        .add_fn("send_from_thread", send_from_thread)?
+        .add_fn("close_after_error", close_after_error)?;
+    // Build the module
+    module.build()
 }
@@ -0,0 +1,26 @@
+//! This module contains the tests for parsing and formatting `Rgb` colors
+
+use nvim_utils::vim::api::Rgb;
+
+#[test]
+fn rgb_parses_hex() {
+    assert_eq!("#ff8000".parse(), Ok(Rgb::new(0xff, 0x80, 0x00)));
+    assert_eq!("#1E1E2E".parse(), Ok(Rgb::new(0x1e, 0x1e, 0x2e)));
+}
+
+#[test]
+fn rgb_rejects_invalid() {
+    for s in [
+        "ff8000", "#ff800", "#ff80000", "#gg0000", "Red", "", "#+f8000",
+    ] {
+        assert!(s.parse::<Rgb>().is_err(), "{:?} should not parse", s);
+    }
+}
+
+#[test]
+fn rgb_roundtrips() {
+    let rgb = Rgb::from_u32(0x12abef);
+    assert_eq!(rgb.to_u32(), 0x12abef);
+    assert_eq!(rgb.to_string(), "#12abef");
+    assert_eq!(rgb.to_string().parse(), Ok(rgb));
+}
@@ -1 +1,25 @@
+// The mock runs in a LuaJIT of its own, which is only linked into tests
+#[cfg(feature = "mock")]
+extern crate nvim_utils_luajit;
+
+mod highlight;
+#[cfg(all(feature = "send", unix))]
+mod jobs;
+mod mirror;
+#[cfg(feature = "mock")]
+mod mock;
+// Calling the entry points needs Lua linked into the test binary, which the `mock` feature does
+#[cfg(feature = "mock")]
+mod module;
+#[cfg(feature = "testing")]
 mod nvim;
+mod operator;
+mod position;
+#[cfg(feature = "async")]
+mod runtime;
+mod selection;
+#[cfg(feature = "testing")]
+mod testing;
+mod uv;
+#[cfg(feature = "mock")]
+mod win_config;
@@ -0,0 +1,322 @@
+//! This module contains the tests for job cancellation and the job pool
+//! The pool tests run completion callbacks on the mock, so they need the `mock` feature
+
+use nvim_utils::vim::ext::jobs::{CancellationToken, JobError};
+
+#[test]
+fn cancellation_is_shared_between_clones() {
+    let token = CancellationToken::new();
+    let worker = token.clone();
+    assert!(!worker.is_cancelled());
+    token.cancel();
+    assert!(worker.is_cancelled());
+}
+
+#[test]
+fn cancellation_crosses_threads() {
+    let token = CancellationToken::new();
+    let worker = token.clone();
+    let handle = std::thread::spawn(move || {
+        while !worker.is_cancelled() {
+            std::thread::yield_now();
+        }
+    });
+    token.cancel();
+    handle.join().unwrap();
+}
+
+#[test]
+fn job_error_messages() {
+    assert_eq!(JobError::Cancelled.to_string(), "job was cancelled");
+    assert_eq!(
+        JobError::Panicked("oops".to_owned()).to_string(),
+        "job panicked: oops"
+    );
+}
+
+#[cfg(feature = "mock")]
+mod pool {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use nvim_utils::mock;
+    use nvim_utils::prelude::*;
+    use nvim_utils::vim::ext::jobs::{
+        CompletionSink, JobContext, JobError, JobHandle, JobPool, JobState,
+    };
+    use nvim_utils::vim::thread::{Closed, MainThreadCallback};
+
+    /// Collects callbacks, to run them on the test's thread
+    #[derive(Clone, Default)]
+    struct Collect {
+        callbacks: Arc<Mutex<Vec<MainThreadCallback>>>,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl CompletionSink for Collect {
+        fn send(&self, f: MainThreadCallback) -> Result<(), Closed> {
+            if self.closed.load(Ordering::SeqCst) {
+                return Err(Closed);
+            }
+            self.callbacks.lock().unwrap().push(f);
+            Ok(())
+        }
+
+        fn close(&self, _lua: &Lua) -> LuaResult<()> {
+            self.closed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    impl Collect {
+        fn len(&self) -> usize {
+            self.callbacks.lock().unwrap().len()
+        }
+
+        fn run(&self, lua: &Lua) -> LuaResult<()> {
+            let callbacks = std::mem::take(&mut *self.callbacks.lock().unwrap());
+            for f in callbacks {
+                f(lua)?;
+            }
+            Ok(())
+        }
+    }
+
+    type Results = Arc<Mutex<Vec<(String, Result<u32, JobError>)>>>;
+
+    /// Submits a job whose callback records its result under `title`
+    fn submit(
+        pool: &JobPool,
+        results: &Results,
+        title: &str,
+        work: impl FnOnce(&JobContext) -> u32 + Send + 'static,
+    ) -> Result<JobHandle, JobError> {
+        let results = results.clone();
+        let name = title.to_owned();
+        pool.submit(title, work, move |_, result| {
+            results.lock().unwrap().push((name, result));
+            Ok(())
+        })
+    }
+
+    /// Submits a job that runs until `release` is sent to, and waits for a worker to start it
+    fn submit_blocking(pool: &JobPool, results: &Results) -> mpsc::Sender<()> {
+        let (release, wait) = mpsc::channel();
+        let handle = submit(pool, results, "blocking", move |_| {
+            let _ = wait.recv();
+            0
+        })
+        .unwrap();
+        wait_until(|| {
+            pool.jobs()
+                .iter()
+                .any(|job| job.id == handle.id() && job.state == JobState::Running)
+        });
+        release
+    }
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn jobs_run_in_submission_order() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let sink = Collect::default();
+        let results = Results::default();
+        let pool = JobPool::with_sink("test", 1, sink.clone())?;
+        let release = submit_blocking(&pool, &results);
+        for (title, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            submit(&pool, &results, title, move |_| value)?;
+        }
+
+        let states = pool
+            .jobs()
+            .into_iter()
+            .map(|job| (job.title, job.state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                ("blocking".to_owned(), JobState::Running),
+                ("a".to_owned(), JobState::Queued),
+                ("b".to_owned(), JobState::Queued),
+                ("c".to_owned(), JobState::Queued),
+            ]
+        );
+
+        release.send(()).unwrap();
+        wait_until(|| sink.len() == 4);
+        sink.run(&lua)?;
+        assert!(pool.jobs().is_empty());
+        assert_eq!(
+            *results.lock().unwrap(),
+            vec![
+                ("blocking".to_owned(), Ok(0)),
+                ("a".to_owned(), Ok(1)),
+                ("b".to_owned(), Ok(2)),
+                ("c".to_owned(), Ok(3)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cancel_queued_and_running_jobs() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let sink = Collect::default();
+        let results = Results::default();
+        let pool = JobPool::with_sink("test", 1, sink.clone())?;
+
+        let (started, wait_started) = mpsc::channel();
+        let running = submit(&pool, &results, "running", move |ctx| {
+            started.send(()).unwrap();
+            while !ctx.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            1
+        })?;
+        let ran = Arc::new(AtomicBool::new(false));
+        let queued_ran = ran.clone();
+        let queued = submit(&pool, &results, "queued", move |_| {
+            queued_ran.store(true, Ordering::SeqCst);
+            2
+        })?;
+        wait_started.recv().unwrap();
+
+        queued.cancel();
+        assert!(pool.cancel(running.id()));
+        wait_until(|| sink.len() == 2);
+        sink.run(&lua)?;
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(
+            *results.lock().unwrap(),
+            vec![
+                ("running".to_owned(), Err(JobError::Cancelled)),
+                ("queued".to_owned(), Err(JobError::Cancelled)),
+            ]
+        );
+        // Completed jobs can't be cancelled
+        assert!(!pool.cancel(running.id()));
+        Ok(())
+    }
+
+    #[test]
+    fn panics_are_captured() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let sink = Collect::default();
+        let results = Results::default();
+        let pool = JobPool::with_sink("test", 1, sink.clone())?;
+        submit(&pool, &results, "panics", |_| panic!("boom"))?;
+        submit(&pool, &results, "after", |_| 1)?;
+
+        wait_until(|| sink.len() == 2);
+        sink.run(&lua)?;
+        assert_eq!(
+            *results.lock().unwrap(),
+            vec![
+                (
+                    "panics".to_owned(),
+                    Err(JobError::Panicked("boom".to_owned()))
+                ),
+                ("after".to_owned(), Ok(1)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shutdown_completes_queued_jobs() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let sink = Collect::default();
+        let results = Results::default();
+        let pool = JobPool::with_sink("test", 1, sink.clone())?;
+        let release = submit_blocking(&pool, &results);
+        let blocking = pool.jobs()[0].id;
+        submit(&pool, &results, "a", |_| 1)?;
+        submit(&pool, &results, "b", |_| 2)?;
+
+        pool.shutdown(&lua)?;
+        assert!(sink.closed.load(Ordering::SeqCst));
+        assert!(matches!(
+            submit(&pool, &results, "late", |_| 3),
+            Err(JobError::ShutDown)
+        ));
+        // Running jobs are cancelled
+        assert!(pool.cancel(blocking));
+        release.send(()).unwrap();
+
+        sink.run(&lua)?;
+        assert_eq!(
+            *results.lock().unwrap(),
+            vec![
+                ("a".to_owned(), Err(JobError::ShutDown)),
+                ("b".to_owned(), Err(JobError::ShutDown)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dropping_last_clone_stops_workers() -> LuaResult<()> {
+        let sink = Collect::default();
+        let pool = JobPool::with_sink("test", 2, sink.clone())?;
+        let clone = pool.clone();
+        drop(pool);
+        assert!(clone.submit("still running", |_| (), |_, _| Ok(())).is_ok());
+        drop(clone);
+        // The workers drop the pool's state, and the sink with it, once they stopped
+        wait_until(|| Arc::strong_count(&sink.closed) == 1);
+        Ok(())
+    }
+
+    #[test]
+    fn check_health_lists_jobs() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let sink = Collect::default();
+        let results = Results::default();
+        let pool = JobPool::with_sink("test", 1, sink.clone())?;
+        let release = submit_blocking(&pool, &results);
+        submit(&pool, &results, "queued", |_| 1)?;
+
+        pool.check_health(&lua)?;
+        let reports: Vec<String> = lua
+            .load("return vim.tbl_map(function(r) return r.kind .. ': ' .. r.message end, vim._mock.health)")
+            .eval()?;
+        assert_eq!(reports.len(), 4);
+        assert_eq!(reports[0], "start: test jobs");
+        assert_eq!(reports[1], "ok: 1 workers, 1 running, 1 queued");
+        assert!(
+            reports[2].starts_with("info: #1 blocking: Running"),
+            "{}",
+            reports[2]
+        );
+        assert!(
+            reports[3].starts_with("info: #2 queued: Queued"),
+            "{}",
+            reports[3]
+        );
+
+        release.send(()).unwrap();
+        wait_until(|| sink.len() == 2);
+        pool.shutdown(&lua)?;
+        pool.check_health(&lua)?;
+        let summary: String = lua
+            .load(
+                "local r = vim._mock.health[#vim._mock.health] return r.kind .. ': ' .. r.message",
+            )
+            .eval()?;
+        assert_eq!(summary, "warn: 1 workers, 0 running, 0 queued, shut down");
+        Ok(())
+    }
+}
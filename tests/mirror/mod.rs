@@ -0,0 +1,107 @@
+//! This module contains the tests for applying `on_bytes` edits to `MirrorText`
+
+use nvim_utils::vim::{api::Position, ext::mirror::MirrorText};
+
+fn text(lines: &[&str]) -> MirrorText {
+    MirrorText::new(lines.iter().map(|l| l.to_string()).collect())
+}
+
+#[test]
+fn splice_inserts_within_line() {
+    let mut t = text(&["hello world"]);
+    t.splice(0, 5, 0, 0, ",").unwrap();
+    assert_eq!(t, text(&["hello, world"]));
+}
+
+#[test]
+fn splice_deletes_within_line() {
+    let mut t = text(&["hello world"]);
+    t.splice(0, 5, 0, 6, "").unwrap();
+    assert_eq!(t, text(&["hello"]));
+}
+
+#[test]
+fn splice_splits_line() {
+    let mut t = text(&["foobar"]);
+    t.splice(0, 3, 0, 0, "\n").unwrap();
+    assert_eq!(t, text(&["foo", "bar"]));
+}
+
+#[test]
+fn splice_joins_lines() {
+    let mut t = text(&["foo", "bar", "baz"]);
+    // `J` on the first line replaces the newline with a space
+    t.splice(0, 3, 1, 0, " ").unwrap();
+    assert_eq!(t, text(&["foo bar", "baz"]));
+}
+
+#[test]
+fn splice_appends_line_at_end() {
+    let mut t = text(&["a", "b"]);
+    t.splice(2, 0, 0, 0, "c\n").unwrap();
+    assert_eq!(t, text(&["a", "b", "c"]));
+}
+
+#[test]
+fn splice_deletes_last_line() {
+    let mut t = text(&["a", "b"]);
+    t.splice(1, 0, 1, 0, "").unwrap();
+    assert_eq!(t, text(&["a"]));
+}
+
+#[test]
+fn splice_deleting_everything_keeps_one_line() {
+    let mut t = text(&["a", "b"]);
+    t.splice(0, 0, 2, 0, "").unwrap();
+    assert_eq!(t, text(&[""]));
+    assert_eq!(t.line_count(), 1);
+}
+
+#[test]
+fn splice_replaces_across_lines() {
+    let mut t = text(&["one", "two", "three"]);
+    t.splice(0, 1, 2, 2, "X\nY").unwrap();
+    assert_eq!(t, text(&["oX", "Yree"]));
+}
+
+#[test]
+fn splice_rejects_out_of_range() {
+    let mut t = text(&["abc"]);
+    assert!(t.splice(0, 4, 0, 0, "x").is_err());
+    assert!(t.splice(3, 0, 0, 0, "x").is_err());
+    assert_eq!(t, text(&["abc"]));
+}
+
+#[test]
+fn splice_rejects_inside_char() {
+    let mut t = text(&["é"]);
+    assert!(t.splice(0, 1, 0, 0, "x").is_err());
+}
+
+#[test]
+fn offsets_roundtrip() {
+    let t = text(&["ab", "", "cd"]);
+    assert_eq!(t.len_bytes(), 7);
+    assert_eq!(t.offset_of(Position::new(2, 1)), Some(5));
+    assert_eq!(t.position_of(5), Some(Position::new(2, 1)));
+    assert_eq!(t.position_of(2), Some(Position::new(0, 2)));
+    assert_eq!(t.position_of(3), Some(Position::new(1, 0)));
+    assert_eq!(t.position_of(7), Some(Position::new(3, 0)));
+    assert_eq!(t.position_of(8), None);
+    assert_eq!(t.slice(1, 5), Some("b\n\nc".to_owned()));
+}
+
+#[test]
+fn char_and_utf16_offsets() {
+    // "é" is 2 bytes and 1 UTF-16 unit, "😀" is 4 bytes and 2 UTF-16 units
+    let t = text(&["é😀x", "y"]);
+    assert_eq!(t.byte_to_char(6), Some(2));
+    assert_eq!(t.byte_to_utf16(6), Some(3));
+    assert_eq!(t.byte_to_char(8), Some(4));
+    assert_eq!(t.byte_to_utf16(8), Some(5));
+    assert_eq!(t.byte_to_char(1), None);
+    assert_eq!(t.char_to_byte(2), Some(6));
+    assert_eq!(t.utf16_to_byte(3), Some(6));
+    assert_eq!(t.utf16_to_byte(2), None);
+    assert_eq!(t.char_to_byte(4), Some(8));
+}
@@ -0,0 +1,485 @@
+//! This module contains the tests for the fake `vim` global
+//! They only run with the `mock` feature, which links a LuaJIT into the test binary
+
+use nvim_utils::mock::{self, Notification};
+use nvim_utils::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use vim::api::command::{CommandBuilder, CommandRange};
+use vim::api::{
+    AutocmdBuilder, Buffer, Event, ExecAutocmdsOpts, Highlight, Mapping, Position, Rgb, Window,
+};
+use vim::ext::{DebounceBuilder, DebouncedCallback};
+use vim::keymap::{KeymapOpts, Mode, Rhs};
+use vim::log::LogLevel;
+use vim::opt::OptionScope;
+use vim::uv::Command;
+
+#[test]
+fn buffer_lines() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let buf = Buffer::create(&lua, true, false)?;
+    buf.set_lines(&lua, .., false, vec!["one", "two", "three"])?;
+    buf.set_lines(&lua, 1..2, true, vec!["2", "2.5"])?;
+    assert_eq!(
+        buf.get_lines(&lua, .., false)?,
+        vec!["one", "2", "2.5", "three"]
+    );
+    assert_eq!(buf.line_count(&lua)?, 4);
+    assert!(buf.get_lines(&lua, 2..10, true).is_err());
+
+    assert_ne!(buf, Buffer::current(&lua)?);
+    Window::CURRENT.set_buf(&lua, buf)?;
+    assert_eq!(Buffer::current(&lua)?, buf);
+    Ok(())
+}
+
+#[test]
+fn func_rows_are_zero_based() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let buf = Buffer::current(&lua)?;
+    buf.set_lines(&lua, .., false, vec!["one", "  two", "three"])?;
+    Window::CURRENT.set_cursor(&lua, Position::new(1, 2))?;
+    assert_eq!(vim::func::line(&lua, ".")?, Some(1));
+    assert_eq!(vim::func::line(&lua, "$")?, Some(2));
+    assert_eq!(vim::func::line(&lua, "'x")?, None);
+
+    assert_eq!(vim::func::getline(&lua, 0, None)?.into_string()?, "one");
+    assert_eq!(
+        vim::func::getline(&lua, 1, Some(3))?.into_table()?,
+        vec!["  two", "three"]
+    );
+    assert!(vim::func::getline(&lua, 1, Some(1))?
+        .into_table()?
+        .is_empty());
+    assert_eq!(vim::func::indent(&lua, 1)?, 2);
+
+    assert!(vim::func::setline(&lua, 0, "first")?);
+    assert!(vim::func::setline(&lua, 3, "appended")?);
+    assert!(!vim::func::setline(&lua, 10, "nowhere")?);
+    assert_eq!(
+        buf.get_lines(&lua, .., false)?,
+        vec!["first", "  two", "three", "appended"]
+    );
+    assert_eq!(vim::func::foldclosed(&lua, 0)?, None);
+    Ok(())
+}
+
+#[test]
+fn options() -> LuaResult<()> {
+    let lua = mock::new()?;
+    assert_eq!(
+        vim::opt::get::<u64>(&lua, "shiftwidth", OptionScope::Local)?,
+        8
+    );
+    vim::opt::set(&lua, "sw", 4, OptionScope::Local)?;
+    assert_eq!(
+        vim::opt::get::<u64>(&lua, "shiftwidth", OptionScope::Local)?,
+        4
+    );
+
+    let other = Buffer::create(&lua, true, false)?;
+    vim::opt::set(&lua, "expandtab", true, OptionScope::Buffer(other))?;
+    assert!(vim::opt::get::<bool>(
+        &lua,
+        "expandtab",
+        OptionScope::Buffer(other)
+    )?);
+    assert!(!vim::opt::get::<bool>(
+        &lua,
+        "expandtab",
+        OptionScope::Local
+    )?);
+
+    vim::opt::append(&lua, "completeopt", &["noselect"], OptionScope::Global)?;
+    assert_eq!(
+        vim::opt::get::<String>(&lua, "completeopt", OptionScope::Global)?,
+        "menu,preview,noselect"
+    );
+    assert!(vim::opt::get::<bool>(&lua, "no_such_option", OptionScope::Global).is_err());
+    Ok(())
+}
+
+#[test]
+fn variables() -> LuaResult<()> {
+    let lua = mock::new()?;
+    vim::var::g::set(&lua, "answer", &42)?;
+    assert_eq!(vim::var::g::get::<i64>(&lua, "answer")?, 42);
+    vim::var::g::del(&lua, "answer")?;
+    assert_eq!(vim::var::g::get::<Option<i64>>(&lua, "answer")?, None);
+
+    vim::var::b::set(&lua, Buffer::CURRENT, "names", &vec!["a", "b"])?;
+    let names: Vec<String> = lua.load("return vim.b.names").eval()?;
+    assert_eq!(names, vec!["a", "b"]);
+    Ok(())
+}
+
+#[test]
+fn keymaps() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    vim::keymap::set(
+        &lua,
+        &[Mode::Normal],
+        "<leader>x",
+        Rhs::callback(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }),
+        KeymapOpts {
+            desc: Some("Count".to_owned()),
+            ..Default::default()
+        },
+    )?;
+
+    let maps = vim::api::nvim_get_keymap(&lua, Mode::Normal)?;
+    assert_eq!(maps.len(), 1);
+    assert_eq!(maps[0].lhs, "<leader>x");
+    assert_eq!(maps[0].mode, vec![Mode::Normal]);
+    assert_eq!(maps[0].opts.desc.as_deref(), Some("Count"));
+    assert_eq!(maps[0].opts.buffer, None);
+
+    vim::api::nvim_feedkeys(&lua, "<leader>x", "m", false)?;
+    vim::api::nvim_feedkeys(&lua, "dd", "n", false)?;
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(mock::commands(&lua)?, vec!["normal dd"]);
+
+    vim::keymap::del(&lua, &[Mode::Normal], "<leader>x")?;
+    assert!(vim::api::nvim_get_keymap(&lua, Mode::Normal)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn mapping_with_combined_modes() -> LuaResult<()> {
+    let lua = mock::new()?;
+    // What `:map x y` followed by `:sunmap x` leaves behind
+    let map: Mapping = lua
+        .load(r#"return { lhs = "x", rhs = "y", mode = "nox", buffer = 0, noremap = 0 }"#)
+        .eval()?;
+    assert_eq!(
+        map.mode,
+        vec![Mode::Normal, Mode::OperatorPending, Mode::VisualOnly]
+    );
+    assert_eq!(map.opts.noremap, Some(false));
+
+    assert_eq!(
+        Mode::parse_modes("ov")?,
+        vec![Mode::OperatorPending, Mode::Visual]
+    );
+    assert_eq!(Mode::parse_modes(" ")?, vec![Mode::NormalVisualOperator]);
+    assert_eq!(Mode::parse_modes("!")?, vec![Mode::InsertCommand]);
+    assert!(Mode::parse_modes("nq").is_err());
+    Ok(())
+}
+
+#[test]
+fn autocmds() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let entered = Arc::new(AtomicUsize::new(0));
+    let counter = entered.clone();
+    AutocmdBuilder::new([Event::BufEnter])
+        .with_pattern("*.rs")
+        .with_callback(move |_, args| {
+            assert_eq!(args.event, Event::BufEnter);
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(false)
+        })
+        .create(&lua)?;
+
+    let rust = Buffer::create(&lua, true, false)?;
+    rust.set_name(&lua, "main.rs")?;
+    let text = Buffer::create(&lua, true, false)?;
+    text.set_name(&lua, "notes.txt")?;
+    Window::CURRENT.set_buf(&lua, text)?;
+    Window::CURRENT.set_buf(&lua, rust)?;
+    assert_eq!(entered.load(Ordering::SeqCst), 1);
+
+    // Returning true deletes the autocommand
+    let fired = Arc::new(AtomicUsize::new(0));
+    let counter = fired.clone();
+    AutocmdBuilder::new([Event::User])
+        .with_pattern("Done")
+        .with_callback(move |lua, args| {
+            assert_eq!(args.data_as::<String>(lua)?, "payload");
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        })
+        .create(&lua)?;
+    for _ in 0..2 {
+        vim::api::nvim_exec_autocmds(
+            &lua,
+            [Event::User],
+            ExecAutocmdsOpts {
+                pattern: vec!["Done".to_owned()],
+                data: Some(lua.pack("payload")?),
+                ..Default::default()
+            },
+        )?;
+    }
+    assert_eq!(fired.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+// Without the `send` feature, callbacks can hold non-`Send` state
+#[cfg(not(feature = "send"))]
+#[test]
+fn autocmd_callbacks_without_send() -> LuaResult<()> {
+    use std::{cell::Cell, rc::Rc};
+
+    let lua = mock::new()?;
+    let fired = Rc::new(Cell::new(0));
+    let counter = fired.clone();
+    AutocmdBuilder::new([Event::User])
+        .with_callback(move |_, _| {
+            counter.set(counter.get() + 1);
+            Ok(false)
+        })
+        .create(&lua)?;
+    vim::api::nvim_exec_autocmds(&lua, [Event::User], ExecAutocmdsOpts::default())?;
+    assert_eq!(fired.get(), 1);
+    Ok(())
+}
+
+#[test]
+fn notifications() -> LuaResult<()> {
+    let lua = mock::new()?;
+    log::info(&lua, "hello")?;
+    log::error(&lua, "oops")?;
+    assert_eq!(
+        mock::notifications(&lua)?,
+        vec![
+            Notification {
+                message: "hello".to_owned(),
+                level: LogLevel::Info,
+            },
+            Notification {
+                message: "oops".to_owned(),
+                level: LogLevel::Error,
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn debounce_on_virtual_clock() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let debounced = vim::ext::debounce(&lua, Duration::from_millis(100), move |_, _| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    })?;
+
+    for _ in 0..3 {
+        debounced.call(&lua, LuaMultiValue::new())?;
+        mock::advance(&lua, Duration::from_millis(50))?;
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    mock::advance(&lua, Duration::from_millis(100))?;
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(!debounced.is_pending());
+    Ok(())
+}
+
+/// A debounced callback that records the number it was called with
+fn recorder() -> (Arc<Mutex<Vec<i64>>>, DebouncedCallback) {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let callback: DebouncedCallback = Box::new(move |lua, args| {
+        recorded
+            .lock()
+            .unwrap()
+            .push(i64::from_lua_multi(args, lua)?);
+        Ok(())
+    });
+    (calls, callback)
+}
+
+#[test]
+fn throttle_on_virtual_clock() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let (calls, callback) = recorder();
+    let throttled = vim::ext::throttle(&lua, Duration::from_millis(100), callback)?;
+
+    // The first call runs right away, the last one in the window at its end
+    for n in 1..=3 {
+        throttled.call(&lua, n)?;
+        mock::advance(&lua, Duration::from_millis(30))?;
+    }
+    assert_eq!(*calls.lock().unwrap(), vec![1]);
+    mock::advance(&lua, Duration::from_millis(10))?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 3]);
+
+    // The window stays open after a trailing call
+    throttled.call(&lua, 4)?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 3]);
+    mock::advance(&lua, Duration::from_millis(100))?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 3, 4]);
+
+    // Once a window passes without calls, the next call runs right away
+    mock::advance(&lua, Duration::from_millis(200))?;
+    throttled.call(&lua, 5)?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 3, 4, 5]);
+    Ok(())
+}
+
+#[test]
+fn debounce_on_leading_edge() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let (calls, callback) = recorder();
+    let debounced = DebounceBuilder::debounce(Duration::from_millis(100))
+        .with_leading(true)
+        .with_trailing(false)
+        .build(&lua, callback)?;
+
+    for n in 1..=3 {
+        debounced.call(&lua, n)?;
+        mock::advance(&lua, Duration::from_millis(50))?;
+    }
+    assert!(!debounced.is_pending());
+    mock::advance(&lua, Duration::from_millis(100))?;
+    assert_eq!(*calls.lock().unwrap(), vec![1]);
+
+    debounced.call(&lua, 4)?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 4]);
+    Ok(())
+}
+
+#[test]
+fn throttle_without_trailing_edge() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let (calls, callback) = recorder();
+    let throttled = DebounceBuilder::throttle(Duration::from_millis(100))
+        .with_trailing(false)
+        .build(&lua, callback)?;
+
+    throttled.call(&lua, 1)?;
+    throttled.call(&lua, 2)?;
+    assert!(!throttled.is_pending());
+    mock::advance(&lua, Duration::from_millis(250))?;
+    assert_eq!(*calls.lock().unwrap(), vec![1]);
+    throttled.call(&lua, 3)?;
+    assert_eq!(*calls.lock().unwrap(), vec![1, 3]);
+    Ok(())
+}
+
+#[test]
+fn debounce_cancel_and_flush() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let (calls, callback) = recorder();
+    let debounced = vim::ext::debounce(&lua, Duration::from_millis(100), callback)?;
+
+    debounced.call(&lua, 1)?;
+    assert!(debounced.is_pending());
+    debounced.cancel(&lua)?;
+    assert!(!debounced.is_pending());
+    mock::advance(&lua, Duration::from_millis(200))?;
+    assert!(calls.lock().unwrap().is_empty());
+
+    debounced.call(&lua, 2)?;
+    debounced.call(&lua, 3)?;
+    debounced.flush(&lua)?;
+    assert_eq!(*calls.lock().unwrap(), vec![3]);
+    mock::advance(&lua, Duration::from_millis(200))?;
+    assert_eq!(*calls.lock().unwrap(), vec![3]);
+
+    // The timer was reset, so the next call waits again
+    debounced.call(&lua, 4)?;
+    mock::advance(&lua, Duration::from_millis(50))?;
+    debounced.call(&lua, 5)?;
+    mock::advance(&lua, Duration::from_millis(50))?;
+    assert_eq!(*calls.lock().unwrap(), vec![3]);
+    mock::advance(&lua, Duration::from_millis(50))?;
+    assert_eq!(*calls.lock().unwrap(), vec![3, 5]);
+    Ok(())
+}
+
+#[test]
+fn module_builder() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let module = ModuleBuilder::new(&lua)
+        .with_fn("greet", |lua, name: String| {
+            log::info(lua, &format!("Hello, {}!", name))
+        })?
+        .build()?;
+    lua.globals().set("plugin", module)?;
+
+    lua.load("plugin.greet('world')").exec()?;
+    assert_eq!(mock::notifications(&lua)?[0].message, "Hello, world!");
+    Ok(())
+}
+
+#[test]
+fn highlight_from_lua_keeps_table() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let table: LuaTable = lua
+        .load("return { [true] = 6, fg = 255, bold = true }")
+        .eval()?;
+    let hl: Highlight = lua.unpack(LuaValue::Table(table.clone()))?;
+    assert_eq!(hl.bold, Some(true));
+    assert_eq!(table.raw_get::<_, i64>(true)?, 6);
+    Ok(())
+}
+
+#[test]
+fn highlight_by_name_has_rgb_colors() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let hl = Highlight {
+        fg: Some(Rgb::new(0x12, 0x34, 0x56)),
+        ..Default::default()
+    };
+    vim::api::nvim_set_hl(&lua, 0, "MyGroup", hl)?;
+    let hl = vim::api::nvim_get_hl_by_name(&lua, "MyGroup")?;
+    assert_eq!(hl.fg, Some(Rgb::new(0x12, 0x34, 0x56)));
+    let id = vim::api::nvim_get_hl_id_by_name(&lua, "MyGroup")?;
+    assert_eq!(vim::api::nvim_get_hl_by_id(&lua, id)?, hl);
+    Ok(())
+}
+
+#[test]
+fn spawn_with_only_stderr() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let err = Command::new("cargo")
+        .arg("check")
+        .on_stderr(|_, _| Ok(()))
+        .spawn(&lua)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("ENOSYS"), "{}", err);
+    // luv reads as many pipes as the length of `stdio`, so stderr is only used if it's 3
+    let (len, stdout, stderr): (usize, bool, bool) = lua
+        .load(
+            "local stdio = vim._mock.spawned[1].options.stdio
+            return #stdio, stdio[2] ~= nil, stdio[3] ~= nil",
+        )
+        .eval()?;
+    assert_eq!((len, stdout, stderr), (3, false, true));
+    Ok(())
+}
+
+#[test]
+fn command_sends_range_or_count() -> LuaResult<()> {
+    let lua = mock::new()?;
+    CommandBuilder::new("Ranged")
+        .with_count(3)
+        .with_range(CommandRange::WholeFile)
+        .with_command("echo")
+        .create(&lua)?;
+    CommandBuilder::new("Counted")
+        .with_range(CommandRange::CurrentLine)
+        .with_count(3)
+        .with_command("echo")
+        .create(&lua)?;
+    let (range, count): (Option<String>, Option<u64>) = lua
+        .load("return vim._mock.commands.Ranged.opts.range, vim._mock.commands.Ranged.opts.count")
+        .eval()?;
+    assert_eq!((range, count), (Some("%".into()), None));
+    let (range, count): (Option<bool>, Option<u64>) = lua
+        .load("return vim._mock.commands.Counted.opts.range, vim._mock.commands.Counted.opts.count")
+        .eval()?;
+    assert_eq!((range, count), (None, Some(3)));
+    Ok(())
+}
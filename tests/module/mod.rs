@@ -0,0 +1,46 @@
+//! This module contains the tests for the `#[module]` entry points
+//! They call the entry points with a LuaJIT state, so they need the `mock` feature
+
+use nvim_utils::prelude::*;
+
+#[nvim_utils::module(nvim_utils_test)]
+fn root(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+    let module = lua.create_table()?;
+    module.set("name", "root")?;
+    Ok(module)
+}
+
+#[nvim_utils::module(name = "nvim_utils_test.sub", name = "nvim_utils_test.alias")]
+fn sub(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+    let module = lua.create_table()?;
+    module.set("name", "sub")?;
+    Ok(module)
+}
+
+/// Loads a module through its entry point, like `require` does
+fn open(lua: &Lua, entry: mlua::lua_CFunction) -> LuaResult<LuaTable<'_>> {
+    unsafe { lua.create_c_function(entry)? }.call(())
+}
+
+#[test]
+fn entry_points() -> LuaResult<()> {
+    let lua = Lua::new();
+    let name = |module: LuaTable| module.get::<_, String>("name");
+    assert_eq!(name(open(&lua, luaopen_nvim_utils_test)?)?, "root");
+    assert_eq!(name(open(&lua, luaopen_nvim_utils_test_sub)?)?, "sub");
+    assert_eq!(name(open(&lua, luaopen_nvim_utils_test_alias)?)?, "sub");
+    Ok(())
+}
+
+#[test]
+fn version_entry_points() -> LuaResult<()> {
+    let lua = Lua::new();
+    for entry in [
+        nvim_utils_version_nvim_utils_test,
+        nvim_utils_version_nvim_utils_test_sub,
+    ] {
+        let version: String = unsafe { lua.create_c_function(entry)? }.call(())?;
+        assert_eq!(version, env!("CARGO_PKG_VERSION"));
+    }
+    Ok(())
+}
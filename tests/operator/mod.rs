@@ -0,0 +1,141 @@
+//! This module contains the tests for operator motion types and the operatorfunc trampoline
+//! The operatorfunc tests run on the mock, so they need the `mock` feature
+
+use nvim_utils::vim::ext::{operator::MotionType, SelectionMode};
+
+#[test]
+fn motion_type_parses() {
+    assert_eq!("line".parse::<MotionType>().unwrap(), MotionType::Line);
+    assert_eq!("char".parse::<MotionType>().unwrap(), MotionType::Char);
+    assert_eq!("block".parse::<MotionType>().unwrap(), MotionType::Block);
+    assert!("word".parse::<MotionType>().is_err());
+}
+
+#[test]
+fn motion_type_roundtrips() {
+    for motion in [MotionType::Line, MotionType::Char, MotionType::Block] {
+        assert_eq!(motion.to_string().parse::<MotionType>().unwrap(), motion);
+    }
+}
+
+#[test]
+fn motion_type_to_selection_mode() {
+    assert_eq!(
+        SelectionMode::from(MotionType::Line),
+        SelectionMode::Linewise
+    );
+    assert_eq!(
+        SelectionMode::from(MotionType::Char),
+        SelectionMode::Charwise
+    );
+    assert_eq!(
+        SelectionMode::from(MotionType::Block),
+        SelectionMode::Blockwise { to_end: false }
+    );
+}
+
+#[cfg(feature = "mock")]
+mod operatorfunc {
+    use std::sync::{Arc, Mutex};
+
+    use nvim_utils::mock;
+    use nvim_utils::prelude::*;
+    use nvim_utils::vim::api::{Buffer, Position};
+    use nvim_utils::vim::ext::operator::{MotionType, Operator};
+    use nvim_utils::vim::ext::{Selection, SelectionMode};
+    use nvim_utils::vim::keymap::{KeymapOpts, Mode};
+    use nvim_utils::vim::opt::OptionScope;
+
+    type Calls = Arc<Mutex<Vec<(MotionType, Selection)>>>;
+
+    fn setup(lua: &Lua) -> LuaResult<(Operator, Calls)> {
+        Buffer::current(lua)?.set_lines(lua, .., false, vec!["hello world", "second line"])?;
+        let calls: Calls = Arc::default();
+        let recorded = calls.clone();
+        let operator = Operator::new(lua, "record", move |lua, motion| {
+            let selection = motion.selection(lua)?;
+            recorded.lock().unwrap().push((motion.motion, selection));
+            Ok(())
+        })?;
+        Ok((operator, calls))
+    }
+
+    /// Sets the marks `g@` sets, and calls `operatorfunc` the way Neovim does
+    fn operate(lua: &Lua, motion: &str, start: Position, end: Position) -> LuaResult<()> {
+        vim::func::setpos(lua, "'[", start)?;
+        vim::func::setpos(lua, "']", end)?;
+        let operatorfunc: String = vim::opt::get(lua, "operatorfunc", OptionScope::Global)?;
+        let path = operatorfunc.strip_prefix("v:lua.").ok_or_else(|| {
+            LuaError::RuntimeError(format!("not a v:lua function: {}", operatorfunc))
+        })?;
+        lua.load(
+            r#"
+            local path, motion = ...
+            local f = _G
+            for part in path:gmatch("[^.]+") do
+              f = f[part]
+            end
+            f(motion)
+            "#,
+        )
+        .call((path, motion))
+    }
+
+    #[test]
+    fn mappings_start_the_operator() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (operator, _) = setup(&lua)?;
+        operator.map(&lua, &[Mode::Normal], "gr", KeymapOpts::default())?;
+        operator.map_line(&lua, "grr", KeymapOpts::default())?;
+
+        vim::api::nvim_feedkeys(&lua, "gr", "m", false)?;
+        vim::api::nvim_feedkeys(&lua, "grr", "m", false)?;
+        assert_eq!(mock::commands(&lua)?, vec!["normal g@", "normal g@_"]);
+        assert_eq!(
+            vim::opt::get::<String>(&lua, "operatorfunc", OptionScope::Global)?,
+            "v:lua.nvim_utils_operators.record"
+        );
+        assert_eq!(operator.operatorfunc(), "v:lua.nvim_utils_operators.record");
+        Ok(())
+    }
+
+    #[test]
+    fn operatorfunc_receives_motion_and_selection() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (operator, calls) = setup(&lua)?;
+        assert_eq!(operator.start(&lua, "iw")?, "g@iw");
+
+        operate(&lua, "char", Position::new(0, 6), Position::new(0, 10))?;
+        operate(&lua, "line", Position::new(0, 3), Position::new(1, 0))?;
+        operate(&lua, "block", Position::new(0, 1), Position::new(1, 3))?;
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        let (motion, selection) = &calls[0];
+        assert_eq!(*motion, MotionType::Char);
+        assert_eq!(selection.mode, SelectionMode::Charwise);
+        assert_eq!(
+            (selection.start, selection.end),
+            (Position::new(0, 6), Position::new(0, 10))
+        );
+        assert_eq!(selection.text, vec!["world"]);
+
+        let (motion, selection) = &calls[1];
+        assert_eq!(*motion, MotionType::Line);
+        assert_eq!(selection.mode, SelectionMode::Linewise);
+        assert_eq!(selection.text, vec!["hello world", "second line"]);
+
+        let (motion, selection) = &calls[2];
+        assert_eq!(*motion, MotionType::Block);
+        assert_eq!(selection.text, vec!["ell", "eco"]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_name() -> LuaResult<()> {
+        let lua = mock::new()?;
+        assert!(Operator::new(&lua, "not-an-identifier", |_, _| Ok(())).is_err());
+        assert!(Operator::new(&lua, "1st", |_, _| Ok(())).is_err());
+        Ok(())
+    }
+}
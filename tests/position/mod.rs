@@ -0,0 +1,69 @@
+//! This module contains the tests for converting `Position` and `Range`
+
+use nvim_utils::vim::api::{ColumnUnit, LspPosition, Position, Range};
+
+#[test]
+fn cursor_conversions() {
+    assert_eq!(Position::from_cursor(1, 0), Position::new(0, 0));
+    assert_eq!(Position::new(4, 2).to_cursor(), (5, 2));
+    assert_eq!(Position::from_cursor(0, 3), Position::new(0, 3));
+}
+
+#[test]
+fn one_based_conversions() {
+    assert_eq!(Position::from_one_based(1, 1), Position::new(0, 0));
+    assert_eq!(Position::new(4, 2).to_one_based(), (5, 3));
+}
+
+#[test]
+fn column_units() {
+    // "é" is 2 bytes and 1 UTF-16 unit, "😀" is 4 bytes and 2 UTF-16 units
+    let line = "é😀x";
+    let pos = Position::new(0, 6);
+    assert_eq!(pos.col_in(ColumnUnit::Byte, line), Some(6));
+    assert_eq!(pos.col_in(ColumnUnit::Char, line), Some(2));
+    assert_eq!(pos.col_in(ColumnUnit::Utf16, line), Some(3));
+    assert_eq!(Position::new(0, 1).col_in(ColumnUnit::Char, line), None);
+    assert_eq!(Position::new(0, 8).col_in(ColumnUnit::Char, line), None);
+
+    assert_eq!(
+        Position::from_col_in(0, 2, ColumnUnit::Char, line),
+        Some(pos)
+    );
+    assert_eq!(
+        Position::from_col_in(0, 3, ColumnUnit::Utf16, line),
+        Some(pos)
+    );
+    assert_eq!(Position::from_col_in(0, 2, ColumnUnit::Utf16, line), None);
+    assert_eq!(
+        Position::from_col_in(0, 4, ColumnUnit::Utf16, line),
+        Some(Position::new(0, 7))
+    );
+    assert_eq!(Position::from_col_in(0, 5, ColumnUnit::Utf16, line), None);
+}
+
+#[test]
+fn lsp_roundtrip() {
+    let line = "a😀b";
+    let pos = Position::new(3, 5);
+    let lsp = pos.to_lsp(line).unwrap();
+    assert_eq!(
+        lsp,
+        LspPosition {
+            line: 3,
+            character: 3
+        }
+    );
+    assert_eq!(Position::from_lsp(lsp, line), Some(pos));
+}
+
+#[test]
+fn range_contains() {
+    let range = Range::new(Position::new(1, 2), Position::new(3, 0));
+    assert!(range.contains(Position::new(1, 2)));
+    assert!(range.contains(Position::new(2, 100)));
+    assert!(!range.contains(Position::new(3, 0)));
+    assert!(!range.contains(Position::new(1, 1)));
+    assert!(Range::lines(2, 2).is_empty());
+    assert!(!Range::lines(2, 3).is_empty());
+}
@@ -0,0 +1,159 @@
+//! This module contains the tests for the async runtime
+//! The executor tests run on the mock, so they need the `mock` feature
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+};
+
+use nvim_utils::vim::runtime::{oneshot, schedule};
+
+#[derive(Default)]
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn poll<F: Future + Unpin>(future: &mut F, waker: &Arc<CountingWaker>) -> Poll<F::Output> {
+    let waker = Waker::from(waker.clone());
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+#[test]
+fn oneshot_delivers_value() {
+    let waker = Arc::new(CountingWaker::default());
+    let (sender, mut receiver) = oneshot::channel();
+    assert!(poll(&mut receiver, &waker).is_pending());
+    assert!(!receiver.is_ready());
+
+    sender.send(42).unwrap();
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    assert!(receiver.is_ready());
+    assert_eq!(poll(&mut receiver, &waker), Poll::Ready(Ok(42)));
+}
+
+#[test]
+fn oneshot_canceled_when_sender_dropped() {
+    let waker = Arc::new(CountingWaker::default());
+    let (sender, mut receiver) = oneshot::channel::<()>();
+    assert!(poll(&mut receiver, &waker).is_pending());
+    drop(sender);
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        poll(&mut receiver, &waker),
+        Poll::Ready(Err(oneshot::Canceled))
+    );
+}
+
+#[test]
+fn oneshot_send_fails_without_receiver() {
+    let (sender, receiver) = oneshot::channel();
+    drop(receiver);
+    assert!(sender.is_closed());
+    assert_eq!(sender.send("value"), Err("value"));
+}
+
+#[test]
+fn oneshot_works_across_threads() {
+    let (sender, mut receiver) = oneshot::channel();
+    std::thread::spawn(move || sender.send(String::from("hello")).unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(receiver.try_recv().as_deref(), Some("hello"));
+}
+
+#[test]
+fn schedule_yields_once() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut future = schedule();
+    assert!(poll(&mut future, &waker).is_pending());
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    assert!(poll(&mut future, &waker).is_ready());
+}
+
+#[cfg(feature = "mock")]
+mod executor {
+    use std::time::Duration;
+
+    use nvim_utils::mock;
+    use nvim_utils::prelude::*;
+    use nvim_utils::vim::runtime;
+
+    #[test]
+    fn states_on_one_thread_have_their_own_tasks() -> LuaResult<()> {
+        let first = mock::new()?;
+        let second = mock::new()?;
+        runtime::spawn(&first, async {
+            runtime::with_lua(|lua| vim::var::g::set(lua, "ran", &"first"))
+        })?;
+        runtime::spawn(&second, async {
+            runtime::with_lua(|lua| vim::var::g::set(lua, "ran", &"second"))
+        })?;
+
+        mock::run_scheduled(&second)?;
+        assert_eq!(vim::var::g::get::<Option<String>>(&first, "ran")?, None);
+        assert_eq!(vim::var::g::get::<String>(&second, "ran")?, "second");
+        mock::run_scheduled(&first)?;
+        assert_eq!(vim::var::g::get::<String>(&first, "ran")?, "first");
+        Ok(())
+    }
+
+    #[test]
+    fn sleep_on_virtual_clock() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let handle = runtime::spawn(&lua, async {
+            runtime::sleep(Duration::from_millis(100)).await?;
+            runtime::with_lua(|lua| vim::var::g::set(lua, "slept", &true))
+        })?;
+        mock::run_scheduled(&lua)?;
+        mock::advance(&lua, Duration::from_millis(50))?;
+        assert!(!handle.is_finished());
+        mock::advance(&lua, Duration::from_millis(50))?;
+        assert!(handle.is_finished());
+        assert!(vim::var::g::get::<bool>(&lua, "slept")?);
+        Ok(())
+    }
+
+    #[test]
+    fn block_on_provides_lua() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let value = runtime::block_on(&lua, async {
+            runtime::sleep(Duration::from_millis(10)).await?;
+            runtime::with_lua(|lua| lua.load("return 1 + 1").eval::<i64>())
+        })??;
+        assert_eq!(value, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn async_function_resumes_coroutine() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let module = ModuleBuilder::new(&lua)
+            .with_fn_async("double", |_, n: i64| async move {
+                runtime::sleep(Duration::from_millis(10)).await?;
+                Ok(n * 2)
+            })?
+            .build()?;
+        lua.globals().set("plugin", module)?;
+        lua.load("coroutine.wrap(function() vim.g.doubled = plugin.double(21) end)()")
+            .exec()?;
+
+        mock::run_scheduled(&lua)?;
+        mock::advance(&lua, Duration::from_millis(10))?;
+        assert_eq!(vim::var::g::get::<i64>(&lua, "doubled")?, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn with_lua_outside_of_task() {
+        assert!(runtime::with_lua(|_| Ok(())).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! This module contains the tests for normalizing selections and extracting their text
+
+use nvim_utils::prelude::*;
+use nvim_utils::vim::{
+    api::Position,
+    ext::{Selection, SelectionMode},
+};
+
+fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|l| l.to_string()).collect()
+}
+
+/// `strdisplaywidth()` with `tabstop=8`, where CJK characters are two cells wide
+fn width(text: &str, start: u64) -> LuaResult<u64> {
+    let end = text.chars().fold(start, |col, c| match c {
+        '\t' => col + 8 - col % 8,
+        '\u{3000}'..='\u{9fff}' => col + 2,
+        '\u{0300}'..='\u{036f}' => col,
+        _ => col + 1,
+    });
+    Ok(end - start)
+}
+
+#[test]
+fn charwise_single_line() -> LuaResult<()> {
+    let sel = Selection::from_lines(
+        SelectionMode::Charwise,
+        Position::new(0, 6),
+        Position::new(0, 10),
+        &lines(&["hello world"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["world"]));
+    assert_eq!(sel.start, Position::new(0, 6));
+    assert_eq!(sel.end, Position::new(0, 10));
+    Ok(())
+}
+
+#[test]
+fn charwise_reversed_multiline() -> LuaResult<()> {
+    let sel = Selection::from_lines(
+        SelectionMode::Charwise,
+        Position::new(4, 2),
+        Position::new(3, 3),
+        &lines(&["foo bar", "baz qux"]),
+        width,
+    )?;
+    assert_eq!(sel.start, Position::new(3, 3));
+    assert_eq!(sel.end, Position::new(4, 2));
+    assert_eq!(sel.text, lines(&[" bar", "baz"]));
+    assert_eq!(sel.joined(), " bar\nbaz");
+    Ok(())
+}
+
+#[test]
+fn charwise_multibyte_end() -> LuaResult<()> {
+    // "é" is 2 bytes and "😀" is 4, marks point at the first byte of the character
+    let sel = Selection::from_lines(
+        SelectionMode::Charwise,
+        Position::new(0, 0),
+        Position::new(0, 3),
+        &lines(&["é😀x"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["é😀"]));
+
+    // A column inside a character is snapped to its start
+    let sel = Selection::from_lines(
+        SelectionMode::Charwise,
+        Position::new(0, 1),
+        Position::new(0, 5),
+        &lines(&["é😀x"]),
+        width,
+    )?;
+    assert_eq!(sel.start, Position::new(0, 0));
+    assert_eq!(sel.end, Position::new(0, 2));
+    assert_eq!(sel.text, lines(&["é😀"]));
+    Ok(())
+}
+
+#[test]
+fn linewise_maxcol() -> LuaResult<()> {
+    let sel = Selection::from_lines(
+        SelectionMode::Linewise,
+        Position::new(1, 0),
+        Position::new(2, 2147483646),
+        &lines(&["one", "twö"]),
+        width,
+    )?;
+    assert_eq!(sel.start, Position::new(1, 0));
+    assert_eq!(sel.end, Position::new(2, 2));
+    assert_eq!(sel.text, lines(&["one", "twö"]));
+    Ok(())
+}
+
+#[test]
+fn blockwise_multibyte() -> LuaResult<()> {
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: false },
+        Position::new(0, 1),
+        Position::new(2, 2),
+        &lines(&["abcd", "äöüß", "xy"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["bc", "öü", "y"]));
+    assert_eq!(sel.start, Position::new(0, 1));
+    assert_eq!(sel.end, Position::new(2, 1));
+    Ok(())
+}
+
+#[test]
+fn blockwise_reversed_corners() -> LuaResult<()> {
+    // Anchor at the top right, cursor at the bottom left
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: false },
+        Position::new(0, 3),
+        Position::new(1, 1),
+        &lines(&["abcd", "efgh"]),
+        width,
+    )?;
+    assert_eq!(sel.start, Position::new(0, 1));
+    assert_eq!(sel.end, Position::new(1, 3));
+    assert_eq!(sel.text, lines(&["bcd", "fgh"]));
+    Ok(())
+}
+
+#[test]
+fn blockwise_to_end() -> LuaResult<()> {
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: true },
+        Position::new(0, 1),
+        Position::new(2, 1),
+        &lines(&["abcdef", "gh", "ijk"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["bcdef", "h", "jk"]));
+    assert_eq!(sel.end, Position::new(2, 2));
+    Ok(())
+}
+
+#[test]
+fn mode_from_vim() {
+    assert_eq!(SelectionMode::from_mode("v"), Some(SelectionMode::Charwise));
+    assert_eq!(
+        SelectionMode::from_mode("Vs"),
+        Some(SelectionMode::Linewise)
+    );
+    assert_eq!(
+        SelectionMode::from_mode("\x16"),
+        Some(SelectionMode::Blockwise { to_end: false })
+    );
+    assert_eq!(SelectionMode::from_mode("n"), None);
+    assert_eq!(SelectionMode::from_mode(""), None);
+}
+
+#[test]
+fn blockwise_tabs_and_wide_characters() -> LuaResult<()> {
+    // The block covers screen columns 2 to 5. "日本語" are two cells each, and the tab covers columns 1 to 7.
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: false },
+        Position::new(0, 2),
+        Position::new(3, 7),
+        &lines(&["abcdefgh", "日本語", "a\tb", "xye\u{301}fgh"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["cdef", "本語", "    ", "e\u{301}fgh"]));
+    assert_eq!(sel.start, Position::new(0, 2));
+    assert_eq!(sel.end, Position::new(3, 7));
+
+    // A wide character at a corner widens the block to cover it
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: false },
+        Position::new(0, 3),
+        Position::new(1, 2),
+        &lines(&["日本語", "abcdef"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["本", "cd"]));
+    assert_eq!(sel.start, Position::new(0, 3));
+    assert_eq!(sel.end, Position::new(1, 3));
+
+    // A wide character that is only partly inside the block is replaced with spaces
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: false },
+        Position::new(0, 3),
+        Position::new(1, 6),
+        &lines(&["abcdef", "日本語"]),
+        width,
+    )?;
+    assert_eq!(sel.text, lines(&["def", " 語"]));
+    assert_eq!(sel.end, Position::new(1, 6));
+    Ok(())
+}
+
+#[test]
+fn blockwise_measures_each_character_once() -> LuaResult<()> {
+    let line = "äöü\t".repeat(250);
+    let mut calls = 0;
+    let sel = Selection::from_lines(
+        SelectionMode::Blockwise { to_end: true },
+        Position::new(0, 0),
+        Position::new(1, 0),
+        &lines(&[&line, &line]),
+        |text: &str, col| {
+            calls += 1;
+            width(text, col)
+        },
+    )?;
+    assert_eq!(sel.text, lines(&[&line, &line]));
+    // Both corners, and each character of both lines
+    assert!(calls <= 4 + 2 * 1000, "{} calls", calls);
+
+    // Printable ASCII is one cell wide without being measured
+    calls = 0;
+    let ascii = "x".repeat(1000);
+    Selection::from_lines(
+        SelectionMode::Blockwise { to_end: true },
+        Position::new(0, 0),
+        Position::new(0, 0),
+        &lines(&[&ascii]),
+        |text: &str, col| {
+            calls += 1;
+            width(text, col)
+        },
+    )?;
+    assert!(calls <= 2, "{} calls", calls);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! This module contains the tests for the headless Neovim test harness
+//! None of them start Neovim, the `nvim` module does
+
+use nvim_utils::testing::{find_plugin, Harness, Report, TestError, TestResult};
+use std::time::Duration;
+
+fn result(name: &str, passed: bool) -> TestResult {
+    TestResult {
+        name: name.to_owned(),
+        passed,
+        error: (!passed).then(|| format!("{} failed", name)),
+        duration: Duration::from_millis(1),
+        stderr: String::new(),
+    }
+}
+
+#[test]
+fn report_splits_results() {
+    let report = Report {
+        results: vec![result("a", true), result("b", false), result("c", true)],
+    };
+    let passed = report.passed().map(|r| r.name.as_str()).collect::<Vec<_>>();
+    let failed = report.failed().map(|r| r.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(passed, vec!["a", "c"]);
+    assert_eq!(failed, vec!["b"]);
+    Report {
+        results: vec![result("a", true)],
+    }
+    .assert_passed();
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 Neovim tests failed")]
+fn report_panics_on_failure() {
+    Report {
+        results: vec![result("a", true), result("b", false)],
+    }
+    .assert_passed();
+}
+
+#[test]
+fn cases_in_order() {
+    let harness = Harness::new()
+        .with_lua_test("first", "assert(true)")
+        .with_rust_test("second", "plugin", "run");
+    let names = harness
+        .cases()
+        .iter()
+        .map(|case| case.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["first", "second"]);
+}
+
+#[test]
+fn missing_nvim() {
+    let err = Harness::new()
+        .with_nvim("/nonexistent/nvim")
+        .with_lua_test("never runs", "")
+        .run()
+        .unwrap_err();
+    assert!(matches!(err, TestError::Spawn { .. }), "{}", err);
+}
+
+#[test]
+fn missing_plugin() {
+    let err = find_plugin("no-such-plugin").unwrap_err();
+    match err {
+        TestError::PluginNotFound { name, searched } => {
+            assert_eq!(name, "no-such-plugin");
+            assert!(searched
+                .iter()
+                .all(|path| path.to_string_lossy().contains("no_such_plugin")));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
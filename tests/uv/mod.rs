@@ -0,0 +1,185 @@
+//! This module contains the tests for libuv handles and splitting process output into lines
+//! The handle tests run on the mock's virtual clock, so they need the `mock` feature
+
+use nvim_utils::vim::uv::{ExitStatus, LineBuffer};
+
+#[test]
+fn complete_lines() {
+    let mut buffer = LineBuffer::new();
+    assert_eq!(buffer.push(b"one\ntwo\n"), vec!["one", "two"]);
+    assert_eq!(buffer.finish(), None);
+}
+
+#[test]
+fn lines_split_across_chunks() {
+    let mut buffer = LineBuffer::new();
+    assert!(buffer.push(b"hel").is_empty());
+    assert_eq!(buffer.push(b"lo\nwor"), vec!["hello"]);
+    assert!(buffer.push(b"ld").is_empty());
+    assert_eq!(buffer.finish(), Some("world".to_owned()));
+    assert_eq!(buffer.finish(), None);
+}
+
+#[test]
+fn crlf_and_empty_lines() {
+    let mut buffer = LineBuffer::new();
+    assert_eq!(buffer.push(b"a\r\n\r\n\nb\r"), vec!["a", "", ""]);
+    assert_eq!(buffer.push(b"\n"), vec!["b"]);
+}
+
+#[test]
+fn utf8_split_across_chunks() {
+    let mut buffer = LineBuffer::new();
+    let bytes = "é😀\n".as_bytes();
+    assert!(buffer.push(&bytes[..1]).is_empty());
+    assert!(buffer.push(&bytes[1..4]).is_empty());
+    assert_eq!(buffer.push(&bytes[4..]), vec!["é😀"]);
+    assert_eq!(buffer.push(b"\xff\n"), vec!["\u{fffd}"]);
+}
+
+#[test]
+fn exit_status() {
+    assert!(ExitStatus { code: 0, signal: 0 }.success());
+    assert!(!ExitStatus { code: 1, signal: 0 }.success());
+    assert!(!ExitStatus {
+        code: 0,
+        signal: 15
+    }
+    .success());
+}
+
+#[cfg(feature = "mock")]
+mod handles {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use nvim_utils::mock;
+    use nvim_utils::prelude::*;
+    use nvim_utils::vim::uv::{Async, Timer};
+
+    fn counter() -> (
+        Arc<AtomicUsize>,
+        impl Fn(&Lua) -> LuaResult<()> + Send + 'static,
+    ) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = count.clone();
+        let callback = move |_: &Lua| {
+            inner.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        };
+        (count, callback)
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn timer_start_and_stop() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (count, callback) = counter();
+        let timer = Timer::new(&lua)?;
+        timer.start(ms(10), ms(5), callback)?;
+        assert!(timer.is_active()?);
+        assert_eq!(timer.get_repeat()?, ms(5));
+
+        mock::advance(&lua, ms(9))?;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        assert_eq!(timer.get_due_in()?, ms(1));
+        mock::advance(&lua, ms(1))?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        mock::advance(&lua, ms(10))?;
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        timer.stop()?;
+        assert!(!timer.is_active()?);
+        assert_eq!(timer.get_due_in()?, Duration::ZERO);
+        mock::advance(&lua, ms(100))?;
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn timer_again_restarts_with_the_repeat_interval() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (count, callback) = counter();
+        let timer = Timer::new(&lua)?;
+        timer.start(ms(10), ms(20), callback)?;
+        mock::advance(&lua, ms(5))?;
+        timer.again()?;
+        assert_eq!(timer.get_due_in()?, ms(20));
+        mock::advance(&lua, ms(19))?;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        mock::advance(&lua, ms(1))?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        timer.set_repeat(ms(0))?;
+        mock::advance(&lua, ms(20))?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(!timer.is_active()?);
+        Ok(())
+    }
+
+    #[test]
+    fn dropped_timer_is_closed() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (count, callback) = counter();
+        let timer = Timer::new(&lua)?;
+        timer.start(ms(10), Duration::ZERO, callback)?;
+        drop(timer);
+        mock::advance(&lua, ms(10))?;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn detached_timer_keeps_running() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (once, callback) = counter();
+        let timer = Timer::new(&lua)?;
+        timer.start(ms(10), Duration::ZERO, callback)?;
+        timer.detach();
+        let (repeating, callback) = counter();
+        let timer = Timer::new(&lua)?;
+        timer.start(ms(10), ms(10), callback)?;
+        timer.detach();
+
+        mock::advance(&lua, ms(30))?;
+        assert_eq!(once.load(Ordering::SeqCst), 1);
+        assert_eq!(repeating.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn async_runs_once_per_wakeup() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (count, callback) = counter();
+        let handle = Async::new(&lua, callback)?;
+        handle.send()?;
+        handle.send()?;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        mock::run_scheduled(&lua)?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        handle.send()?;
+        mock::run_scheduled(&lua)?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        drop(handle);
+        mock::run_scheduled(&lua)?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn detached_async_runs_pending_sends() -> LuaResult<()> {
+        let lua = mock::new()?;
+        let (count, callback) = counter();
+        let handle = Async::new(&lua, callback)?;
+        handle.send()?;
+        handle.detach();
+        mock::run_scheduled(&lua)?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+}
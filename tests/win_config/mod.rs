@@ -0,0 +1,157 @@
+//! This module contains the tests for converting `WinConfig` to and from Lua
+//! They only run with the `mock` feature, which links a LuaJIT into the test binary
+
+use nvim_utils::mock;
+use nvim_utils::prelude::*;
+use vim::api::{
+    Anchor, Border, Buffer, Chunk, Relative, Style, Title, TitlePos, WinConfig, Window,
+};
+use vim::opt::OptionScope;
+
+fn float() -> WinConfig {
+    WinConfig::new()
+        .with_relative(Relative::Editor)
+        .with_anchor(Anchor::NorthEast)
+        .with_position(2.0, 4.5)
+        .with_size(30, 5)
+        .with_zindex(60)
+        .with_style(Style::Minimal)
+        .with_focusable(false)
+}
+
+#[test]
+fn serializes_to_lua() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let config = float()
+        .with_border(Border::Custom(vec![
+            Chunk::from(("╭", "FloatBorder")),
+            Chunk::from("─"),
+        ]))
+        .with_title("Title")
+        .with_title_pos(TitlePos::Center);
+    lua.globals().set("config", config)?;
+    lua.load(
+        r#"
+        assert(config.relative == "editor", config.relative)
+        assert(config.anchor == "NE", config.anchor)
+        assert(config.row == 2 and config.col == 4.5)
+        assert(config.style == "minimal" and config.focusable == false)
+        assert(config.border[1][1] == "╭" and config.border[1][2] == "FloatBorder")
+        assert(config.border[2] == "─")
+        assert(config.title == "Title" and config.title_pos == "center")
+        assert(config.win == nil and config.bufpos == nil and config.noautocmd == nil)
+        "#,
+    )
+    .exec()?;
+
+    lua.globals()
+        .set("config", WinConfig::new().with_border(Border::Rounded))?;
+    lua.load(
+        r#"
+        assert(config.border == "rounded")
+        -- Fields left as `None` are not sent
+        local keys = 0
+        for _ in pairs(config) do
+          keys = keys + 1
+        end
+        assert(keys == 1, keys)
+        "#,
+    )
+    .exec()?;
+    Ok(())
+}
+
+#[test]
+fn border_presets_round_trip() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let buf = Buffer::create(&lua, false, true)?;
+    for border in [
+        Border::None,
+        Border::Single,
+        Border::Double,
+        Border::Rounded,
+        Border::Solid,
+        Border::Shadow,
+    ] {
+        let win = float().with_border(border.clone()).open(&lua, buf, false)?;
+        assert_eq!(win.get_config(&lua)?.border, Some(border));
+    }
+    Ok(())
+}
+
+#[test]
+fn custom_border_and_title_round_trip() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let buf = Buffer::create(&lua, false, true)?;
+    let config = float()
+        .with_border(Border::Custom(vec![
+            Chunk::from(("+", "FloatBorder")),
+            Chunk::from("-"),
+            Chunk::from(("+", "FloatBorder")),
+            Chunk::from("|"),
+        ]))
+        .with_title(Title::Chunks(vec![
+            Chunk::from(("Find", "Title")),
+            Chunk::from(" files"),
+        ]))
+        .with_title_pos(TitlePos::Right);
+    let win = config.clone().open(&lua, buf, true)?;
+    assert_eq!(Window::current(&lua)?, win);
+    assert_eq!(win.get_config(&lua)?, config);
+
+    let moved = WinConfig::new()
+        .with_relative(Relative::Editor)
+        .with_position(10.0, 20.0)
+        .with_title("Moved");
+    win.set_config(&lua, moved)?;
+    let config = win.get_config(&lua)?;
+    assert_eq!((config.row, config.col), (Some(10.0), Some(20.0)));
+    assert_eq!(config.title, Some(Title::from("Moved")));
+    assert_eq!(config.title_pos, Some(TitlePos::Right));
+    Ok(())
+}
+
+#[test]
+fn non_float_has_no_relative() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let config = Window::CURRENT.get_config(&lua)?;
+    assert_eq!(config.relative, None);
+    assert_eq!(config.width, Some(80));
+    Ok(())
+}
+
+#[test]
+fn legacy_boxed_floats() -> LuaResult<()> {
+    let lua = mock::new()?;
+    let config: WinConfig = lua
+        .load(r#"return { relative = "win", win = 1000, row = { [true] = 6, [false] = 2.5 }, col = 3 }"#)
+        .eval()?;
+    assert_eq!(config.relative, Some(Relative::Win));
+    assert_eq!(config.win, Some(Window::new(1000)));
+    assert_eq!((config.row, config.col), (Some(2.5), Some(3.0)));
+    Ok(())
+}
+
+#[test]
+fn centered_in_editor() -> LuaResult<()> {
+    let lua = mock::new()?;
+    vim::opt::set(&lua, "columns", 100, OptionScope::Global)?;
+    vim::opt::set(&lua, "lines", 31, OptionScope::Global)?;
+    let config = WinConfig::centered(&lua, 40, 10)?;
+    assert_eq!(config.relative, Some(Relative::Editor));
+    assert_eq!((config.width, config.height), (Some(40), Some(10)));
+    assert_eq!((config.row, config.col), (Some(10.5), Some(30.0)));
+
+    // Larger than the editor, so it starts at the top-left corner
+    let config = WinConfig::centered(&lua, 200, 50)?;
+    assert_eq!((config.row, config.col), (Some(0.0), Some(0.0)));
+    Ok(())
+}
+
+#[test]
+fn at_cursor() {
+    let config = WinConfig::at_cursor(20, 3);
+    assert_eq!(config.relative, Some(Relative::Cursor));
+    assert_eq!((config.width, config.height), (Some(20), Some(3)));
+    assert_eq!((config.row, config.col), (Some(1.0), Some(0.0)));
+}